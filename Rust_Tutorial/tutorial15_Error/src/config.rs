@@ -0,0 +1,392 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::error::Error as StdError;
+use std::fmt;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::error::{Error, IoContext, Result};
+
+/* Config loader replacing the hello.txt open-or-create match */
+
+pub struct ConfigLoader {
+    file_name: String,
+    explicit: Option<PathBuf>,
+    search_dirs: Vec<PathBuf>,
+    default_contents: String,
+    required: Vec<String>,
+}
+
+impl ConfigLoader {
+    /// Searches the current directory, then the XDG config dir for `app`
+    pub fn new(app: &str, file_name: &str) -> ConfigLoader {
+        let mut search_dirs = vec![PathBuf::from(".")];
+        if let Some(dir) = xdg_config_dir(app) {
+            search_dirs.push(dir);
+        }
+
+        ConfigLoader {
+            file_name: file_name.to_string(),
+            explicit: None,
+            search_dirs,
+            default_contents: String::new(),
+            required: Vec::new(),
+        }
+    }
+
+    /// A path given by the user, e.g. from `--config`. It replaces the search dirs
+    /// and has to exist, a typo there is an error rather than a new default file
+    pub fn explicit_path(mut self, path: impl Into<PathBuf>) -> ConfigLoader {
+        self.explicit = Some(path.into());
+        self
+    }
+
+    /// Replaces the default search dirs
+    pub fn search_dirs(mut self, dirs: Vec<PathBuf>) -> ConfigLoader {
+        self.search_dirs = dirs;
+        self
+    }
+
+    /// Written out on first run when no config file exists yet
+    pub fn default_contents(mut self, contents: &str) -> ConfigLoader {
+        self.default_contents = contents.to_string();
+        self
+    }
+
+    pub fn require(mut self, key: &str) -> ConfigLoader {
+        self.required.push(key.to_string());
+        self
+    }
+
+    /// Every place the file is looked for, in order
+    pub fn candidates(&self) -> Vec<PathBuf> {
+        match &self.explicit {
+            Some(path) => vec![path.clone()],
+            None => self.search_dirs.iter().map(|dir| dir.join(&self.file_name)).collect(),
+        }
+    }
+
+    pub fn load(&self) -> Result<Config> {
+        if let Some(path) = &self.explicit {
+            let text = fs::read_to_string(path).io_context("read", path)?;
+            return self.finish(path, &text, false);
+        }
+
+        let candidates = self.candidates();
+
+        for path in &candidates {
+            match fs::read_to_string(path) {
+                Ok(text) => return self.finish(path, &text, false),
+                Err(e) if e.kind() == ErrorKind::NotFound => continue,
+                Err(e) => return Err(Error::io("read", path, e)),
+            }
+        }
+
+        // First run: create the default file where it would have been found first
+        let path = match candidates.first() {
+            Some(path) => path,
+            None => return Err(Error::validation("search path", "no place to look for the config")),
+        };
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent).io_context("create", parent)?;
+        }
+        fs::write(path, &self.default_contents).io_context("create", path)?;
+
+        self.finish(path, &self.default_contents, true)
+    }
+
+    fn finish(&self, path: &Path, text: &str, created: bool) -> Result<Config> {
+        let entries = parse(text, path)?;
+
+        for key in &self.required {
+            if !entries.contains_key(key) {
+                return Err(Error::validation(
+                    key.as_str(),
+                    format!("required key is missing from {}", path.display()),
+                ));
+            }
+        }
+
+        Ok(Config {
+            path: path.to_path_buf(),
+            entries,
+            created,
+        })
+    }
+}
+
+/* $XDG_CONFIG_HOME/app, falling back to $HOME/.config/app */
+pub fn xdg_config_dir(app: &str) -> Option<PathBuf> {
+    let base = match env::var_os("XDG_CONFIG_HOME").filter(|v| !v.is_empty()) {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(env::var_os("HOME")?).join(".config"),
+    };
+    Some(base.join(app))
+}
+
+/* Loaded config */
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    pub value: String,
+    pub line: usize,
+}
+
+#[derive(Debug)]
+pub struct Config {
+    path: PathBuf,
+    entries: BTreeMap<String, Entry>,
+    created: bool,
+}
+
+impl Config {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// True when the file did not exist and the defaults were written
+    pub fn was_created(&self) -> bool {
+        self.created
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.entries.get(key).map(|entry| entry.value.as_str())
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.entries.keys().map(|key| key.as_str())
+    }
+
+    /// Parses a value, pointing at the line it came from when that fails
+    pub fn get_parsed<T>(&self, key: &str) -> Result<T>
+    where
+        T: FromStr,
+        T::Err: StdError + Send + Sync + 'static,
+    {
+        let entry = self
+            .entries
+            .get(key)
+            .ok_or_else(|| Error::validation(key, "key is missing"))?;
+
+        entry
+            .value
+            .parse()
+            .map_err(|e| Error::parse(format!("`{}`", key)).at(&self.path, entry.line).caused_by(e))
+    }
+}
+
+/* Parser for `key = value` lines with optional [section] headers */
+
+#[derive(Debug)]
+pub struct SyntaxError(String);
+
+impl fmt::Display for SyntaxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl StdError for SyntaxError {}
+
+fn syntax(path: &Path, line: usize, reason: &str) -> Error {
+    Error::parse("config")
+        .at(path, line)
+        .caused_by(SyntaxError(reason.to_string()))
+}
+
+/// Keys inside a `[section]` come back as `section.key`
+pub fn parse(text: &str, path: &Path) -> Result<BTreeMap<String, Entry>> {
+    let mut entries = BTreeMap::new();
+    let mut section = String::new();
+
+    for (index, raw) in text.lines().enumerate() {
+        let line = index + 1;
+        let trimmed = raw.trim();
+
+        if trimmed.is_empty() || trimmed.starts_with('#') || trimmed.starts_with(';') {
+            continue;
+        }
+
+        if let Some(rest) = trimmed.strip_prefix('[') {
+            let name = rest
+                .strip_suffix(']')
+                .ok_or_else(|| syntax(path, line, "section header is missing `]`"))?
+                .trim();
+            if !is_valid_key(name) {
+                return Err(syntax(path, line, "invalid section name"));
+            }
+            section = format!("{}.", name);
+            continue;
+        }
+
+        let (key, value) = trimmed
+            .split_once('=')
+            .ok_or_else(|| syntax(path, line, "expected `key = value`"))?;
+        let key = key.trim();
+        if !is_valid_key(key) {
+            return Err(syntax(path, line, "invalid key"));
+        }
+
+        let value = parse_value(value.trim()).map_err(|reason| syntax(path, line, reason))?;
+        let full_key = format!("{}{}", section, key);
+        if entries.contains_key(&full_key) {
+            return Err(syntax(path, line, "duplicate key"));
+        }
+        entries.insert(full_key, Entry { value, line });
+    }
+
+    Ok(entries)
+}
+
+fn is_valid_key(key: &str) -> bool {
+    !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.')
+}
+
+fn parse_value(value: &str) -> std::result::Result<String, &'static str> {
+    let quoted = match value.strip_prefix('"') {
+        Some(rest) => rest,
+        // unquoted values may carry a trailing comment
+        None => return Ok(value.split(" #").next().unwrap_or("").trim_end().to_string()),
+    };
+
+    let mut out = String::new();
+    let mut chars = quoted.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                let rest = chars.as_str().trim();
+                if rest.is_empty() || rest.starts_with('#') {
+                    return Ok(out);
+                }
+                return Err("unexpected text after closing quote");
+            }
+            '\\' => match chars.next() {
+                Some('n') => out.push('\n'),
+                Some('t') => out.push('\t'),
+                Some('"') => out.push('"'),
+                Some('\\') => out.push('\\'),
+                _ => return Err("unknown escape sequence"),
+            },
+            other => out.push(other),
+        }
+    }
+    Err("unterminated string")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process;
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("tutorial15_config_{}_{}", name, process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn parses_sections_quotes_and_comments() {
+        let text = "# top\nname = ferris\n\n[server]\nport = 8080 # inline\ngreeting = \"hi # there\"\n";
+        let entries = parse(text, Path::new("t.conf")).unwrap();
+
+        assert_eq!(entries["name"].value, "ferris");
+        assert_eq!(entries["server.port"], Entry { value: "8080".to_string(), line: 5 });
+        assert_eq!(entries["server.greeting"].value, "hi # there");
+    }
+
+    #[test]
+    fn syntax_errors_report_the_line() {
+        let err = parse("a = 1\n\nno equals here\n", Path::new("t.conf")).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "could not parse config at t.conf:3: expected `key = value`"
+        );
+
+        let err = parse("a = \"open\n", Path::new("t.conf")).unwrap_err();
+        assert!(matches!(err, Error::Parse { line: Some(1), .. }));
+
+        let err = parse("a = 1\na = 2\n", Path::new("t.conf")).unwrap_err();
+        assert!(err.to_string().ends_with("t.conf:2: duplicate key"));
+    }
+
+    #[test]
+    fn first_run_creates_the_default_file() {
+        let dir = scratch_dir("first_run");
+        let loader = ConfigLoader::new("tutorial15", "hello.txt")
+            .search_dirs(vec![dir.clone()])
+            .default_contents("username = ferris\n")
+            .require("username");
+
+        let config = loader.load().unwrap();
+        assert!(config.was_created());
+        assert_eq!(config.path(), dir.join("hello.txt"));
+        assert_eq!(config.get("username"), Some("ferris"));
+
+        let again = loader.load().unwrap();
+        assert!(!again.was_created());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn explicit_path_wins_and_later_dirs_are_searched() {
+        let dir = scratch_dir("search");
+        let first = dir.join("first");
+        let second = dir.join("second");
+        fs::create_dir_all(&second).unwrap();
+        fs::write(second.join("app.conf"), "from = second\n").unwrap();
+        fs::write(dir.join("explicit.conf"), "from = explicit\n").unwrap();
+
+        let loader = ConfigLoader::new("app", "app.conf").search_dirs(vec![first, second]);
+        assert_eq!(loader.load().unwrap().get("from"), Some("second"));
+
+        let loader = loader.explicit_path(dir.join("explicit.conf"));
+        assert_eq!(loader.load().unwrap().get("from"), Some("explicit"));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn missing_explicit_path_is_an_error_not_a_first_run() {
+        let dir = scratch_dir("explicit_missing");
+        let typo = dir.join("tpyo.conf");
+        let loader = ConfigLoader::new("app", "app.conf")
+            .search_dirs(vec![dir.clone()])
+            .default_contents("from = default\n")
+            .explicit_path(&typo);
+        assert_eq!(loader.candidates(), vec![typo.clone()]);
+
+        let err = loader.load().unwrap_err();
+        assert_eq!(err.io_kind(), Some(ErrorKind::NotFound));
+        assert!(err.to_string().starts_with(&format!("failed to read `{}`", typo.display())));
+        assert!(!typo.exists());
+        assert!(!dir.join("app.conf").exists());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn missing_required_key_and_bad_values_are_errors() {
+        let dir = scratch_dir("validate");
+        fs::write(dir.join("app.conf"), "port = eighty\n").unwrap();
+
+        let loader = ConfigLoader::new("app", "app.conf").search_dirs(vec![dir.clone()]);
+        let err = loader.require("host").load().unwrap_err();
+        assert!(matches!(&err, Error::Validation { field, .. } if field == "host"));
+
+        let config = ConfigLoader::new("app", "app.conf")
+            .search_dirs(vec![dir.clone()])
+            .load()
+            .unwrap();
+        let err = config.get_parsed::<u16>("port").unwrap_err();
+        assert!(matches!(err, Error::Parse { line: Some(1), .. }));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
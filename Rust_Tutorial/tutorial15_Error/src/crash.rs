@@ -0,0 +1,298 @@
+use std::any::Any;
+use std::backtrace::Backtrace;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::error::Error as StdError;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::panic::{self, PanicHookInfo, UnwindSafe};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, Once};
+use std::thread;
+
+use crate::error::{Error, IoContext, Result};
+
+/* Crash reporting for panics like the a() -> b() -> c(22) chain */
+
+const LOG_CAPACITY: usize = 32;
+
+static RECENT_LOG: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+static REPORT_DIR: Mutex<Option<PathBuf>> = Mutex::new(None);
+static REPORT_COUNT: AtomicUsize = AtomicUsize::new(0);
+static INSTALL: Once = Once::new();
+
+thread_local! {
+    // Filled in by the hook so `isolate` can say where the panic happened
+    static LAST_PANIC: RefCell<Option<(Option<String>, Option<PathBuf>)>> = const { RefCell::new(None) };
+}
+
+/// Remembers a line for the next crash report, only the last few are kept
+pub fn log(line: impl Into<String>) {
+    let mut recent = RECENT_LOG.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    if recent.len() == LOG_CAPACITY {
+        recent.pop_front();
+    }
+    recent.push_back(line.into());
+}
+
+fn recent_log() -> Vec<String> {
+    let recent = RECENT_LOG.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    recent.iter().cloned().collect()
+}
+
+/// Installs the panic hook. Every panic writes a report into `dir`,
+/// then the previous hook runs as usual. Calling it again only changes `dir`
+pub fn install(dir: impl Into<PathBuf>) {
+    *REPORT_DIR.lock().unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(dir.into());
+
+    INSTALL.call_once(|| {
+        let previous = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            let report = CrashReport::capture(info);
+            let path = report_dir().and_then(|dir| report.write_to(&dir).ok());
+            LAST_PANIC.with(|last| *last.borrow_mut() = Some((report.location.clone(), path)));
+            previous(info);
+        }));
+    });
+}
+
+fn report_dir() -> Option<PathBuf> {
+    REPORT_DIR.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).clone()
+}
+
+/* Crash report */
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CrashReport {
+    pub message: String,
+    pub location: Option<String>,
+    pub thread: String,
+    pub recent_log: Vec<String>,
+    pub backtrace: String,
+}
+
+impl CrashReport {
+    fn capture(info: &PanicHookInfo<'_>) -> CrashReport {
+        CrashReport {
+            message: payload_message(info.payload()),
+            location: info
+                .location()
+                .map(|l| format!("{}:{}:{}", l.file(), l.line(), l.column())),
+            thread: thread::current().name().unwrap_or("<unnamed>").to_string(),
+            recent_log: recent_log(),
+            backtrace: Backtrace::force_capture().to_string(),
+        }
+    }
+
+    /// Plain text: a few `key: value` headers, then the log and backtrace sections
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        text.push_str(&format!("message: {}\n", escape(&self.message)));
+        text.push_str(&format!("location: {}\n", self.location.as_deref().unwrap_or("<unknown>")));
+        text.push_str(&format!("thread: {}\n", self.thread));
+        text.push_str("--- log ---\n");
+        for line in &self.recent_log {
+            text.push_str(&escape(line));
+            text.push('\n');
+        }
+        text.push_str("--- backtrace ---\n");
+        text.push_str(&self.backtrace);
+        text
+    }
+
+    pub fn parse(text: &str) -> Option<CrashReport> {
+        let mut lines = text.lines();
+        let message = unescape(lines.next()?.strip_prefix("message: ")?);
+        let location = match lines.next()?.strip_prefix("location: ")? {
+            "<unknown>" => None,
+            location => Some(location.to_string()),
+        };
+        let thread = lines.next()?.strip_prefix("thread: ")?.to_string();
+        if lines.next()? != "--- log ---" {
+            return None;
+        }
+
+        let mut recent_log = Vec::new();
+        for line in lines.by_ref() {
+            if line == "--- backtrace ---" {
+                let backtrace = lines.collect::<Vec<_>>().join("\n");
+                return Some(CrashReport { message, location, thread, recent_log, backtrace });
+            }
+            recent_log.push(unescape(line));
+        }
+        None
+    }
+
+    pub fn read(path: &Path) -> Result<CrashReport> {
+        let text = fs::read_to_string(path).io_context("read", path)?;
+        CrashReport::parse(&text).ok_or_else(|| Error::parse("crash report").at(path, 1))
+    }
+
+    fn write_to(&self, dir: &Path) -> io::Result<PathBuf> {
+        fs::create_dir_all(dir)?;
+        let n = REPORT_COUNT.fetch_add(1, Ordering::SeqCst);
+        let path = dir.join(format!("crash-{}-{}.txt", process::id(), n));
+        fs::write(&path, self.to_text())?;
+        Ok(path)
+    }
+}
+
+/* Reports are line based, so newlines inside a message are escaped. So is a
+leading `-`, which keeps a logged `--- backtrace ---` from ending the log */
+fn escape(s: &str) -> String {
+    let escaped = s.replace('\\', "\\\\").replace('\n', "\\n");
+    if escaped.starts_with('-') {
+        format!("\\{}", escaped)
+    } else {
+        escaped
+    }
+}
+
+fn unescape(s: &str) -> String {
+    let mut out = String::new();
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        match (c, chars.clone().next()) {
+            ('\\', Some('n')) => {
+                out.push('\n');
+                chars.next();
+            }
+            ('\\', Some('\\')) => {
+                out.push('\\');
+                chars.next();
+            }
+            ('\\', Some('-')) => {
+                out.push('-');
+                chars.next();
+            }
+            (c, _) => out.push(c),
+        }
+    }
+    out
+}
+
+fn payload_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(s) = payload.downcast_ref::<&str>() {
+        s.to_string()
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        s.clone()
+    } else {
+        "<non-string panic payload>".to_string()
+    }
+}
+
+/* Isolation: turn a panic into an Err */
+
+#[derive(Debug)]
+pub struct Panicked {
+    pub message: String,
+    pub location: Option<String>,
+    /// Only set when the hook from `install` is active
+    pub report: Option<PathBuf>,
+}
+
+impl fmt::Display for Panicked {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "task panicked: {}", self.message)?;
+        if let Some(location) = &self.location {
+            write!(f, " at {}", location)?;
+        }
+        Ok(())
+    }
+}
+
+impl StdError for Panicked {}
+
+/// Runs `task`, catching a panic instead of letting it unwind further
+pub fn isolate<T, F>(task: F) -> std::result::Result<T, Panicked>
+where
+    F: FnOnce() -> T + UnwindSafe,
+{
+    LAST_PANIC.with(|last| last.borrow_mut().take());
+
+    panic::catch_unwind(task).map_err(|payload| {
+        let (location, report) = LAST_PANIC
+            .with(|last| last.borrow_mut().take())
+            .unwrap_or((None, None));
+        Panicked {
+            message: payload_message(&*payload),
+            location,
+            report,
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn report_round_trips_through_text() {
+        let report = CrashReport {
+            message: "two\nlines \\ here".to_string(),
+            location: Some("src/main.rs:3:5".to_string()),
+            thread: "worker".to_string(),
+            recent_log: vec!["a() called".to_string(), "b() called".to_string()],
+            backtrace: "   0: frame\n   1: frame".to_string(),
+        };
+
+        assert_eq!(CrashReport::parse(&report.to_text()), Some(report));
+        assert_eq!(CrashReport::parse("not a report"), None);
+    }
+
+    #[test]
+    fn logged_separators_stay_in_the_log() {
+        let report = CrashReport {
+            message: "-".to_string(),
+            location: None,
+            thread: "main".to_string(),
+            recent_log: vec![
+                "--- backtrace ---".to_string(),
+                "--- log ---".to_string(),
+                "\\-".to_string(),
+                "a - b".to_string(),
+            ],
+            backtrace: "   0: frame".to_string(),
+        };
+
+        let text = report.to_text();
+        assert_eq!(text.matches("\n--- backtrace ---\n").count(), 1);
+        assert_eq!(CrashReport::parse(&text), Some(report));
+    }
+
+    #[test]
+    fn isolate_returns_ok_when_nothing_panics() {
+        assert_eq!(isolate(|| 2 + 2).unwrap(), 4);
+    }
+
+    #[test]
+    fn hook_writes_a_report_and_isolate_points_at_it() {
+        let dir = env::temp_dir().join(format!("tutorial15_crash_{}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        install(&dir);
+        log("before the crash");
+
+        let err = thread::Builder::new()
+            .name("crash-test".to_string())
+            .spawn(|| isolate(|| -> u32 { panic!("boom {}", 42) }).unwrap_err())
+            .unwrap()
+            .join()
+            .unwrap();
+
+        assert_eq!(err.message, "boom 42");
+        assert!(err.location.as_deref().unwrap().contains("crash.rs"));
+
+        let report = CrashReport::read(err.report.as_deref().unwrap()).unwrap();
+        assert_eq!(report.message, "boom 42");
+        assert_eq!(report.thread, "crash-test");
+        assert_eq!(report.location, err.location);
+        assert!(report.recent_log.iter().any(|line| line == "before the crash"));
+        assert!(!report.backtrace.is_empty());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,247 @@
+use std::error::Error as StdError;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/* Crate level Error type */
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+type BoxedSource = Box<dyn StdError + Send + Sync + 'static>;
+
+#[derive(Debug)]
+pub enum Error {
+    /// An I/O operation (`op`, e.g. "open" or "read") failed on `path`
+    Io {
+        op: &'static str,
+        path: PathBuf,
+        source: io::Error,
+    },
+    /// Some input could not be parsed, optionally pointing at a file and line
+    Parse {
+        what: String,
+        path: Option<PathBuf>,
+        line: Option<usize>,
+        source: Option<BoxedSource>,
+    },
+    /// The input parsed fine but a value is not acceptable
+    Validation { field: String, reason: String },
+    /// Extra context attached with `?` through the `Context` trait
+    Context { context: String, source: Box<Error> },
+}
+
+impl Error {
+    pub fn io(op: &'static str, path: impl AsRef<Path>, source: io::Error) -> Error {
+        Error::Io {
+            op,
+            path: path.as_ref().to_path_buf(),
+            source,
+        }
+    }
+
+    pub fn parse(what: impl Into<String>) -> Error {
+        Error::Parse {
+            what: what.into(),
+            path: None,
+            line: None,
+            source: None,
+        }
+    }
+
+    pub fn validation(field: impl Into<String>, reason: impl Into<String>) -> Error {
+        Error::Validation {
+            field: field.into(),
+            reason: reason.into(),
+        }
+    }
+
+    /* Builders for the optional parts of a Parse error */
+    pub fn at(mut self, at_path: impl AsRef<Path>, at_line: usize) -> Error {
+        if let Error::Parse { path, line, .. } = &mut self {
+            *path = Some(at_path.as_ref().to_path_buf());
+            *line = Some(at_line);
+        }
+        self
+    }
+
+    pub fn caused_by<E>(mut self, cause: E) -> Error
+    where
+        E: StdError + Send + Sync + 'static,
+    {
+        if let Error::Parse { source, .. } = &mut self {
+            *source = Some(Box::new(cause));
+        }
+        self
+    }
+
+    /// The innermost error of this crate, skipping every `Context` layer
+    pub fn root(&self) -> &Error {
+        match self {
+            Error::Context { source, .. } => source.root(),
+            other => other,
+        }
+    }
+
+    /// The `io::ErrorKind` behind this error, if it came from I/O
+    pub fn io_kind(&self) -> Option<io::ErrorKind> {
+        match self.root() {
+            Error::Io { source, .. } => Some(source.kind()),
+            _ => None,
+        }
+    }
+
+    /* Writes only this layer's message, the chain is handled by Display */
+    fn describe(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io { op, path, .. } => write!(f, "failed to {} `{}`", op, path.display()),
+            Error::Parse { what, path, line, .. } => {
+                write!(f, "could not parse {}", what)?;
+                match (path, line) {
+                    (Some(path), Some(line)) => write!(f, " at {}:{}", path.display(), line),
+                    (Some(path), None) => write!(f, " in {}", path.display()),
+                    (None, Some(line)) => write!(f, " on line {}", line),
+                    (None, None) => Ok(()),
+                }
+            }
+            Error::Validation { field, reason } => write!(f, "invalid `{}`: {}", field, reason),
+            Error::Context { context, .. } => write!(f, "{}", context),
+        }
+    }
+}
+
+/* Display prints the whole chain, e.g. "loading config: failed to open `a.txt`: No such file" */
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.describe(f)?;
+
+        let mut cause = self.source();
+        while let Some(err) = cause {
+            write!(f, ": ")?;
+            match err.downcast_ref::<Error>() {
+                Some(ours) => ours.describe(f)?,
+                None => write!(f, "{}", err)?,
+            }
+            cause = err.source();
+        }
+        Ok(())
+    }
+}
+
+impl StdError for Error {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            Error::Io { source, .. } => Some(source),
+            Error::Parse { source, .. } => source.as_ref().map(|s| &**s as &(dyn StdError + 'static)),
+            Error::Validation { .. } => None,
+            Error::Context { source, .. } => Some(&**source),
+        }
+    }
+}
+
+/* Context extension trait, a typed version of anyhow::Context */
+pub trait Context<T> {
+    fn context<C: Into<String>>(self, context: C) -> Result<T>;
+
+    fn with_context<C, F>(self, f: F) -> Result<T>
+    where
+        C: Into<String>,
+        F: FnOnce() -> C;
+}
+
+impl<T, E> Context<T> for std::result::Result<T, E>
+where
+    E: Into<Error>,
+{
+    fn context<C: Into<String>>(self, context: C) -> Result<T> {
+        self.map_err(|e| Error::Context {
+            context: context.into(),
+            source: Box::new(e.into()),
+        })
+    }
+
+    fn with_context<C, F>(self, f: F) -> Result<T>
+    where
+        C: Into<String>,
+        F: FnOnce() -> C,
+    {
+        self.map_err(|e| Error::Context {
+            context: f().into(),
+            source: Box::new(e.into()),
+        })
+    }
+}
+
+/* io::Error has no idea which file it was about, so it gets its own extension */
+pub trait IoContext<T> {
+    fn io_context(self, op: &'static str, path: impl AsRef<Path>) -> Result<T>;
+}
+
+impl<T> IoContext<T> for io::Result<T> {
+    fn io_context(self, op: &'static str, path: impl AsRef<Path>) -> Result<T> {
+        self.map_err(|e| Error::io(op, path, e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn read_missing() -> Result<String> {
+        fs::read_to_string("does/not/exist.txt").io_context("read", "does/not/exist.txt")
+    }
+
+    #[test]
+    fn io_error_keeps_operation_path_and_source() {
+        let err = read_missing().unwrap_err();
+
+        assert_eq!(err.io_kind(), Some(io::ErrorKind::NotFound));
+        assert!(err.to_string().starts_with("failed to read `does/not/exist.txt`: "));
+        assert!(err.source().unwrap().downcast_ref::<io::Error>().is_some());
+    }
+
+    #[test]
+    fn context_wraps_and_display_prints_the_chain() {
+        let err = read_missing()
+            .context("loading username")
+            .with_context(|| format!("starting user {}", 7))
+            .unwrap_err();
+
+        let shown = err.to_string();
+        assert!(shown.starts_with(
+            "starting user 7: loading username: failed to read `does/not/exist.txt`: "
+        ));
+        // every layer appears exactly once
+        assert_eq!(shown.matches("loading username").count(), 1);
+
+        let mut depth = 0;
+        let mut cause = err.source();
+        while let Some(e) = cause {
+            depth += 1;
+            cause = e.source();
+        }
+        assert_eq!(depth, 3);
+        assert!(matches!(err.root(), Error::Io { op: "read", .. }));
+    }
+
+    #[test]
+    fn parse_error_with_location_and_cause() {
+        let cause = "abc".parse::<u32>().unwrap_err();
+        let err = Error::parse("port").at("app.conf", 3).caused_by(cause);
+
+        assert_eq!(
+            err.to_string(),
+            "could not parse port at app.conf:3: invalid digit found in string"
+        );
+        assert!(err.source().unwrap().is::<std::num::ParseIntError>());
+    }
+
+    #[test]
+    fn validation_has_no_source() {
+        let err = Error::validation("name", "must not be empty");
+
+        assert_eq!(err.to_string(), "invalid `name`: must not be empty");
+        assert!(err.source().is_none());
+        assert_eq!(err.io_kind(), None);
+    }
+}
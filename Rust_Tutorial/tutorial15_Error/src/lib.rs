@@ -0,0 +1,6 @@
+pub mod config;
+pub mod crash;
+pub mod error;
+pub mod retry;
+
+pub use error::{Context, Error, IoContext, Result};
@@ -1,8 +1,10 @@
 use std::fs;
-use std::{fs::File, io::ErrorKind};
-use std::io::{self, Read};
-use std::error::Error;
-fn main() -> Result<(), Box<dyn Error>> {
+use std::time::Duration;
+use tutorial15_error::config::ConfigLoader;
+use tutorial15_error::crash;
+use tutorial15_error::retry::{self, Backoff, RetryPolicy};
+use tutorial15_error::{Context, IoContext};
+fn main() -> tutorial15_error::Result<()> {
     println!("Hello, world!");
     /*
     Panic Macro
@@ -12,30 +14,25 @@ fn main() -> Result<(), Box<dyn Error>> {
     // a();
 
     /*
-    Result Enum
+    Crash reports: the hook writes a report file, isolate turns the panic into an Err.
+    Only with `cargo run -- --crash`, so a normal run doesn't panic or leave files behind
      */
-    let greeting_file_result = File::open("hello.txt");
+    if std::env::args().any(|arg| arg == "--crash") {
+        crash::install(std::env::temp_dir().join("tutorial15_crashes"));
+        if let Err(panicked) = crash::isolate(a) {
+            println!("{} (report: {:?})", panicked, panicked.report);
+        }
+    }
 
-    // let greeting_file = match greeting_file_result {
-    //     Ok(file) => file,
-    //     Err(error) => panic!("Problem opening the file: {:?}", error),
-    // };
-
-    let greeting_file = match greeting_file_result {
-        Ok(file) => file,
-        Err(error) => match error.kind() {
-            ErrorKind::NotFound => match File::create("hello.txt") {
-                Ok(fc) => fc,
-                Err(e) => panic!("Problem creating the file: {:?}", e),
-            },
-            other_error => {
-                panic!("Problem opening the file: {:?}", other_error);
-            }
-        },
-    };
-
-    let f = File::open("hello.txt").unwrap();
-    let f = File::open("hello.txt").expect("Failed to open the file");
+    /*
+    Config loader: looks in the cwd, then the XDG config dir, creates hello.txt on first run
+     */
+    let config = ConfigLoader::new("tutorial15", "hello.txt")
+        .default_contents("username = ferris\n")
+        .require("username")
+        .load()
+        .context("loading the config")?;
+    println!("username = {:?} (from {})", config.get("username"), config.path().display());
 
 
 
@@ -43,7 +40,8 @@ fn main() -> Result<(), Box<dyn Error>> {
     Propagating errors
      */
     
-    let propagated_error = read_username_from_file()?;
+    let propagated_error = read_username_from_file().context("reading the username")?;
+    println!("hello.txt: {:?}", propagated_error);
     Ok(())
   
 }
@@ -82,18 +80,37 @@ fn main() -> Result<(), Box<dyn Error>> {
 //     Ok(username)
 // }
 
-fn read_username_from_file() -> Result<String, io::Error> {
-    fs::read_to_string("hello.txt")
+// fn read_username_from_file() -> Result<String, io::Error> {
+//     fs::read_to_string("hello.txt")
+// }
+
+/* Typed errors that remember which file and operation failed */
+// fn read_username_from_file() -> tutorial15_error::Result<String> {
+//     fs::read_to_string("hello.txt").io_context("read", "hello.txt")
+// }
+
+/* Retrying reads that fail for a passing reason (Interrupted, WouldBlock) */
+fn read_username_from_file() -> tutorial15_error::Result<String> {
+    let policy = RetryPolicy::new(Backoff::jittered(Duration::from_millis(10), Duration::from_millis(200)))
+        .max_attempts(4)
+        .budget(Duration::from_secs(1));
+
+    policy
+        .run(|_attempt| fs::read_to_string("hello.txt"), retry::is_retryable_io)
+        .io_context("read", "hello.txt")
 }
 
 fn a(){
+    crash::log("a() called");
     b();
 }
 
 fn b(){
+    crash::log("b() called");
     c(22);
 }
 
 fn c(i: i32){
+    crash::log(format!("c({}) called", i));
     panic!("crash and burn");
 }
@@ -0,0 +1,350 @@
+use std::io::{self, ErrorKind};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/* Retry with backoff for fallible I/O */
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Backoff {
+    /// Always wait the same amount
+    Fixed(Duration),
+    /// `initial * factor^n`, capped at `max`
+    Exponential {
+        initial: Duration,
+        factor: u32,
+        max: Duration,
+    },
+    /// Exponential, but the actual wait is picked at random between zero and that value
+    Jittered {
+        initial: Duration,
+        factor: u32,
+        max: Duration,
+    },
+}
+
+impl Backoff {
+    pub fn exponential(initial: Duration, max: Duration) -> Backoff {
+        Backoff::Exponential { initial, factor: 2, max }
+    }
+
+    pub fn jittered(initial: Duration, max: Duration) -> Backoff {
+        Backoff::Jittered { initial, factor: 2, max }
+    }
+
+    /// The wait after the `retry`-th failed attempt, starting at 1
+    pub fn delay(&self, retry: u32, rng: &mut Rng) -> Duration {
+        match *self {
+            Backoff::Fixed(delay) => delay,
+            Backoff::Exponential { initial, factor, max } => grow(initial, factor, max, retry),
+            Backoff::Jittered { initial, factor, max } => {
+                let ceiling = grow(initial, factor, max, retry).as_nanos();
+                // a ceiling past u64::MAX nanos (584 years) allows any u64
+                let nanos = match u64::try_from(ceiling).ok().and_then(|c| c.checked_add(1)) {
+                    Some(range) => rng.next_u64() % range,
+                    None => rng.next_u64(),
+                };
+                Duration::from_nanos(nanos)
+            }
+        }
+    }
+}
+
+fn grow(initial: Duration, factor: u32, max: Duration, retry: u32) -> Duration {
+    let scale = factor.checked_pow(retry.saturating_sub(1)).unwrap_or(u32::MAX);
+    initial.checked_mul(scale).unwrap_or(max).min(max)
+}
+
+/* Small xorshift generator so jitter can be seeded in tests */
+#[derive(Debug, Clone)]
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn seeded(seed: u64) -> Rng {
+        Rng(seed.max(1))
+    }
+
+    pub fn from_time() -> Rng {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or(0);
+        Rng::seeded(nanos)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+}
+
+/* Injectable time so tests never actually sleep */
+
+pub trait Clock {
+    /// Time passed since some fixed starting point
+    fn now(&self) -> Duration;
+}
+
+pub trait Sleeper {
+    fn sleep(&self, duration: Duration);
+}
+
+pub struct SystemClock(Instant);
+
+impl SystemClock {
+    pub fn new() -> SystemClock {
+        SystemClock(Instant::now())
+    }
+}
+
+impl Default for SystemClock {
+    fn default() -> SystemClock {
+        SystemClock::new()
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> Duration {
+        self.0.elapsed()
+    }
+}
+
+pub struct ThreadSleeper;
+
+impl Sleeper for ThreadSleeper {
+    fn sleep(&self, duration: Duration) {
+        thread::sleep(duration);
+    }
+}
+
+/// `Interrupted`, `WouldBlock` and `TimedOut` are worth another try, everything else
+/// (`NotFound`, `PermissionDenied`, ...) will fail the same way again
+pub fn is_retryable(kind: ErrorKind) -> bool {
+    matches!(
+        kind,
+        ErrorKind::Interrupted | ErrorKind::WouldBlock | ErrorKind::TimedOut
+    )
+}
+
+pub fn is_retryable_io(error: &io::Error) -> bool {
+    is_retryable(error.kind())
+}
+
+/* Retry policy */
+
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    backoff: Backoff,
+    max_attempts: u32,
+    budget: Option<Duration>,
+    seed: Option<u64>,
+}
+
+impl RetryPolicy {
+    /// Three attempts and no time budget until told otherwise
+    pub fn new(backoff: Backoff) -> RetryPolicy {
+        RetryPolicy {
+            backoff,
+            max_attempts: 3,
+            budget: None,
+            seed: None,
+        }
+    }
+
+    /// Total attempts, including the first one
+    pub fn max_attempts(mut self, attempts: u32) -> RetryPolicy {
+        self.max_attempts = attempts.max(1);
+        self
+    }
+
+    /// Gives up instead of sleeping past this much total time
+    pub fn budget(mut self, budget: Duration) -> RetryPolicy {
+        self.budget = Some(budget);
+        self
+    }
+
+    /// Fixes the jitter sequence, mostly for tests
+    pub fn seed(mut self, seed: u64) -> RetryPolicy {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Retries `op` on the real clock. `op` gets the attempt number, starting at 1
+    pub fn run<T, E, F, R>(&self, op: F, retryable: R) -> Result<T, E>
+    where
+        F: FnMut(u32) -> Result<T, E>,
+        R: Fn(&E) -> bool,
+    {
+        self.run_with(&SystemClock::new(), &ThreadSleeper, op, retryable)
+    }
+
+    /// Same as `run` with the clock and sleeper supplied by the caller.
+    /// The last error is returned once `retryable` says no or the attempts or budget run out
+    pub fn run_with<T, E, F, R>(
+        &self,
+        clock: &impl Clock,
+        sleeper: &impl Sleeper,
+        mut op: F,
+        retryable: R,
+    ) -> Result<T, E>
+    where
+        F: FnMut(u32) -> Result<T, E>,
+        R: Fn(&E) -> bool,
+    {
+        let start = clock.now();
+        let mut rng = match self.seed {
+            Some(seed) => Rng::seeded(seed),
+            None => Rng::from_time(),
+        };
+        let mut attempt = 1;
+
+        loop {
+            let error = match op(attempt) {
+                Ok(value) => return Ok(value),
+                Err(error) => error,
+            };
+            if attempt >= self.max_attempts || !retryable(&error) {
+                return Err(error);
+            }
+
+            let delay = self.backoff.delay(attempt, &mut rng);
+            if let Some(budget) = self.budget {
+                // saturating, so a Duration::MAX budget just means unbounded
+                if clock.now().saturating_sub(start).saturating_add(delay) > budget {
+                    return Err(error);
+                }
+            }
+            sleeper.sleep(delay);
+            attempt += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::{Cell, RefCell};
+
+    /* Sleeping just moves the fake clock forward */
+    #[derive(Default)]
+    struct FakeTime {
+        now: Cell<Duration>,
+        slept: RefCell<Vec<Duration>>,
+    }
+
+    impl Clock for FakeTime {
+        fn now(&self) -> Duration {
+            self.now.get()
+        }
+    }
+
+    impl Sleeper for FakeTime {
+        fn sleep(&self, duration: Duration) {
+            self.now.set(self.now.get() + duration);
+            self.slept.borrow_mut().push(duration);
+        }
+    }
+
+    fn ms(n: u64) -> Duration {
+        Duration::from_millis(n)
+    }
+
+    fn fail(kind: ErrorKind) -> io::Result<&'static str> {
+        Err(io::Error::new(kind, "test"))
+    }
+
+    #[test]
+    fn classifier_matches_the_kinds_we_expect() {
+        assert!(is_retryable(ErrorKind::Interrupted));
+        assert!(is_retryable(ErrorKind::WouldBlock));
+        assert!(!is_retryable(ErrorKind::NotFound));
+        assert!(!is_retryable(ErrorKind::PermissionDenied));
+    }
+
+    #[test]
+    fn retries_until_success_with_exponential_backoff() {
+        let time = FakeTime::default();
+        let policy = RetryPolicy::new(Backoff::exponential(ms(10), ms(25))).max_attempts(5);
+
+        let result = policy.run_with(
+            &time,
+            &time,
+            |attempt| if attempt < 4 { fail(ErrorKind::Interrupted) } else { Ok("done") },
+            is_retryable_io,
+        );
+
+        assert_eq!(result.unwrap(), "done");
+        assert_eq!(*time.slept.borrow(), vec![ms(10), ms(20), ms(25)]);
+    }
+
+    #[test]
+    fn not_found_is_not_retried() {
+        let time = FakeTime::default();
+        let calls = Cell::new(0);
+        let policy = RetryPolicy::new(Backoff::Fixed(ms(5))).max_attempts(5);
+
+        let op = |_| {
+            calls.set(calls.get() + 1);
+            fail(ErrorKind::NotFound)
+        };
+        let err = policy.run_with(&time, &time, op, is_retryable_io).unwrap_err();
+
+        assert_eq!(err.kind(), ErrorKind::NotFound);
+        assert_eq!(calls.get(), 1);
+        assert!(time.slept.borrow().is_empty());
+    }
+
+    #[test]
+    fn stops_at_max_attempts_and_at_the_budget() {
+        let time = FakeTime::default();
+        let calls = Cell::new(0);
+        let op = |_| {
+            calls.set(calls.get() + 1);
+            fail(ErrorKind::WouldBlock)
+        };
+
+        let policy = RetryPolicy::new(Backoff::Fixed(ms(5))).max_attempts(3);
+        assert!(policy.run_with(&time, &time, op, is_retryable_io).is_err());
+        assert_eq!(calls.get(), 3);
+
+        calls.set(0);
+        let policy = RetryPolicy::new(Backoff::Fixed(ms(40)))
+            .max_attempts(10)
+            .budget(ms(100));
+        assert!(policy.run_with(&time, &time, op, is_retryable_io).is_err());
+        // 40 + 40 fits the budget, a third sleep would not
+        assert_eq!(calls.get(), 3);
+    }
+
+    #[test]
+    fn huge_ceilings_and_budgets_do_not_overflow() {
+        let mut rng = Rng::seeded(3);
+        for max in [Duration::from_nanos(u64::MAX), Duration::MAX] {
+            let backoff = Backoff::jittered(max, max);
+            assert!(backoff.delay(1, &mut rng) <= max);
+        }
+
+        let time = FakeTime::default();
+        let policy = RetryPolicy::new(Backoff::Fixed(Duration::MAX))
+            .max_attempts(2)
+            .budget(Duration::MAX);
+        let result = policy.run_with(&time, &time, |_| fail(ErrorKind::Interrupted), is_retryable_io);
+        assert!(result.is_err());
+        assert_eq!(*time.slept.borrow(), vec![Duration::MAX]);
+    }
+
+    #[test]
+    fn jitter_stays_under_the_exponential_ceiling_and_is_seedable() {
+        let backoff = Backoff::jittered(ms(10), ms(1000));
+        let mut a = Rng::seeded(7);
+        let mut b = Rng::seeded(7);
+
+        for retry in 1..8 {
+            let ceiling = Backoff::exponential(ms(10), ms(1000)).delay(retry, &mut Rng::seeded(1));
+            let delay = backoff.delay(retry, &mut a);
+            assert!(delay <= ceiling);
+            assert_eq!(delay, backoff.delay(retry, &mut b));
+        }
+    }
+}
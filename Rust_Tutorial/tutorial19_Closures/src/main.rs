@@ -1,8 +1,9 @@
-use std::collections::HashMap;
 use std::thread;
-use std::hash::{Hash, Hasher};
 
 use std::time::Duration;
+use tutorial19_closures::memo::Memo;
+use tutorial19_closures::shared::SharedMemo;
+use tutorial19_closures::workout::{self, CostEstimator, Rules};
 
 fn expensive_function(num: u32) -> u32 {
     println!("calculating slowly...");
@@ -32,53 +33,54 @@ fn main() {
 
     // println!("x: {}", x);
 
-}
-
-struct Closures<T, U> where T: Fn(U) -> U, U: Copy + PartialEq + Eq + Hash{
-    calculation: T,
-    value: HashMap<U, U>,
-}
-
-impl <T,U> Closures<T, U>  where T: Fn(U) -> U, U: Copy + PartialEq + Eq + Hash{
-    fn new(calculation: T) -> Closures<T, U> {
-        Closures{
-            calculation: calculation,
-            value: HashMap::new(),
+    /*Memo: LRU + TTL cache that keeps expensive_function results between runs */
+    let path = std::env::temp_dir().join("tutorial19_memo.txt");
+    match Memo::new(expensive_function).capacity(100).ttl(Duration::from_secs(60 * 60)).persist_to(&path) {
+        Ok(mut memo) => {
+            println!("expensive_function(3) = {}", memo.get(3));
+            println!("expensive_function(3) = {}", memo.get(3));
+            println!("{:?}", memo.stats());
         }
+        Err(e) => println!("could not open the memo file {}: {}", path.display(), e),
     }
 
-    fn get_result(&mut self, arg: U) -> U {
-        match self.value.get(&arg) {
-            Some(val) => *val, 
-            None => {
-                let val = (self.calculation)(arg);
-                self.value.insert(arg, val);
-                val
-            }
+    /*SharedMemo: four threads ask for the same value, it is only calculated once */
+    let shared = SharedMemo::new(expensive_function);
+    thread::scope(|s| {
+        for _ in 0..4 {
+            s.spawn(|| println!("shared expensive_function(5) = {}", shared.get(5)));
         }
-    }
+    });
+    println!("{:?}", shared.stats());
+
 }
 
+/* problem_function as a workout planner: min is the minutes into the workout, tired is a fatigue score from 0 to 100 */
 fn problem_function(min: u32, tired: u32){
-    
-    let expensive_closure = |num| {
-        println!("calculating slowly...");
-        thread::sleep(Duration::from_secs(2));
-        num
+    let rules_path = concat!(env!("CARGO_MANIFEST_DIR"), "/workout.rules");
+    let rules = match Rules::load(rules_path) {
+        Ok(rules) => rules,
+        Err(e) => {
+            println!("using the default rules ({})", e);
+            Rules::default()
+        }
     };
 
-    let mut struct_closure = Closures::new(expensive_closure);
+    let mut estimator = CostEstimator::new(workout::slow_set_cost);
 
-    if min < 25 as u32 {
-        println!("It's too early: you should do {} situps", struct_closure.get_result(tired));
-        println!("It's too early: you should do {} pushups", struct_closure.get_result(tired));
+    let today = workout::session(&rules, min, tired);
+    if today.is_rest() {
+        println!("your too tired, take a break");
     }
-    else {
-        if tired == 0 as u32 {
-            println!("your too tired, take a break");
-        }
-        else{
-            println!("You got some energy left, cooldown time {}", struct_closure.get_result(tired));
-        }
+    for set in &today.sets {
+        println!("You should do {} {} ({:?})", set.reps, set.exercise, today.intensity);
+    }
+    println!("Today's cost: {}", estimator.session_cost(&today));
+
+    let week = workout::weekly_plan(&rules, tired);
+    for day in &week {
+        println!("{:<9} fatigue {:>3}: {:?}", day.day, day.fatigue, day.session.intensity);
     }
-}
\ No newline at end of file
+    println!("Weekly cost: {} {:?}", estimator.plan_cost(&week), estimator.stats());
+}
+
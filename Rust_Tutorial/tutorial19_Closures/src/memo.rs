@@ -0,0 +1,432 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::hash::Hash;
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/* Memo: the Closures<T, U> struct grown up */
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Stats {
+    pub hits: u64,
+    pub misses: u64,
+    /// Dropped because the cache was full
+    pub evictions: u64,
+    /// Dropped because they were older than the ttl
+    pub expirations: u64,
+    pub save_failures: u64,
+}
+
+impl Stats {
+    pub fn hit_ratio(&self) -> f64 {
+        let total = self.hits + self.misses;
+        if total == 0 {
+            0.0
+        } else {
+            self.hits as f64 / total as f64
+        }
+    }
+}
+
+struct Slot<R> {
+    value: R,
+    inserted: Duration,
+    // position in `order`, bigger means more recently used
+    tick: u64,
+}
+
+/* Keeps the file and how to write an entry, so `get` doesn't need Persist bounds */
+struct Store<A, R> {
+    path: PathBuf,
+    encode: fn(&A, &R) -> String,
+}
+
+pub struct Memo<A, R, F> {
+    calculation: F,
+    entries: HashMap<A, Slot<R>>,
+    order: BTreeMap<u64, A>,
+    tick: u64,
+    capacity: Option<usize>,
+    ttl: Option<Duration>,
+    stats: Stats,
+    clock: Box<dyn Fn() -> Duration>,
+    store: Option<Store<A, R>>,
+}
+
+impl<A, R, F> Memo<A, R, F>
+where
+    F: Fn(A) -> R,
+    A: Eq + Hash + Clone,
+    R: Clone,
+{
+    /// Unbounded, never expires, lives only in memory
+    pub fn new(calculation: F) -> Memo<A, R, F> {
+        let start = Instant::now();
+        Memo {
+            calculation,
+            entries: HashMap::new(),
+            order: BTreeMap::new(),
+            tick: 0,
+            capacity: None,
+            ttl: None,
+            stats: Stats::default(),
+            clock: Box::new(move || start.elapsed()),
+            store: None,
+        }
+    }
+
+    /// Keeps at most `capacity` results, dropping the least recently used first
+    pub fn capacity(mut self, capacity: usize) -> Memo<A, R, F> {
+        self.capacity = Some(capacity);
+        self.evict();
+        self
+    }
+
+    /// Results older than `ttl` are calculated again
+    pub fn ttl(mut self, ttl: Duration) -> Memo<A, R, F> {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    /// Replaces the clock used for the ttl, it returns the time since some fixed start
+    pub fn with_clock(mut self, clock: impl Fn() -> Duration + 'static) -> Memo<A, R, F> {
+        self.clock = Box::new(clock);
+        self
+    }
+
+    pub fn get(&mut self, arg: A) -> R {
+        let now = (self.clock)();
+
+        if let Some(slot) = self.entries.get_mut(&arg) {
+            let expired = self
+                .ttl
+                .is_some_and(|ttl| now.saturating_sub(slot.inserted) >= ttl);
+            if !expired {
+                self.stats.hits += 1;
+                self.order.remove(&slot.tick);
+                self.tick += 1;
+                slot.tick = self.tick;
+                self.order.insert(self.tick, arg);
+                return slot.value.clone();
+            }
+            self.stats.expirations += 1;
+            self.remove(&arg);
+        }
+
+        self.stats.misses += 1;
+        let value = (self.calculation)(arg.clone());
+        self.insert(arg, value.clone(), now);
+        self.evict();
+
+        if self.store.is_some() && self.save().is_err() {
+            self.stats.save_failures += 1;
+        }
+        value
+    }
+
+    pub fn contains(&self, arg: &A) -> bool {
+        self.entries.contains_key(arg)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn stats(&self) -> Stats {
+        self.stats
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.order.clear();
+    }
+
+    /// Writes every cached result to the persistence file, a no-op without one
+    pub fn save(&self) -> io::Result<()> {
+        let store = match &self.store {
+            Some(store) => store,
+            None => return Ok(()),
+        };
+
+        let now_ms = unix_millis();
+        let now = (self.clock)();
+        let mut text = String::new();
+        // least recently used first, so loading replays the same order
+        for arg in self.order.values() {
+            let slot = &self.entries[arg];
+            let age = now.saturating_sub(slot.inserted).as_millis() as u64;
+            text.push_str(&format!(
+                "{}\t{}\n",
+                now_ms.saturating_sub(age),
+                (store.encode)(arg, &slot.value)
+            ));
+        }
+
+        let tmp = store.path.with_extension("tmp");
+        fs::write(&tmp, text)?;
+        fs::rename(&tmp, &store.path)
+    }
+
+    fn insert(&mut self, arg: A, value: R, inserted: Duration) {
+        self.tick += 1;
+        self.order.insert(self.tick, arg.clone());
+        self.entries.insert(
+            arg,
+            Slot {
+                value,
+                inserted,
+                tick: self.tick,
+            },
+        );
+    }
+
+    fn remove(&mut self, arg: &A) {
+        if let Some(slot) = self.entries.remove(arg) {
+            self.order.remove(&slot.tick);
+        }
+    }
+
+    fn evict(&mut self) {
+        let capacity = match self.capacity {
+            Some(capacity) => capacity,
+            None => return,
+        };
+        while self.entries.len() > capacity {
+            match self.order.pop_first() {
+                Some((_, oldest)) => {
+                    self.entries.remove(&oldest);
+                    self.stats.evictions += 1;
+                }
+                None => break,
+            }
+        }
+    }
+}
+
+impl<A, R, F> Memo<A, R, F>
+where
+    F: Fn(A) -> R,
+    A: Eq + Hash + Clone + Persist,
+    R: Clone + Persist,
+{
+    /// Loads earlier results from `path` (if it exists) and saves after every miss.
+    /// Call after `capacity`/`ttl`/`with_clock` so loading respects them
+    pub fn persist_to(mut self, path: impl AsRef<Path>) -> io::Result<Memo<A, R, F>> {
+        let path = path.as_ref().to_path_buf();
+
+        match fs::read_to_string(&path) {
+            Ok(text) => self.load(&text)?,
+            Err(e) if e.kind() == ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+
+        self.store = Some(Store {
+            path,
+            encode: |arg, value| format!("{}\t{}", arg.encode(), value.encode()),
+        });
+        Ok(self)
+    }
+
+    fn load(&mut self, text: &str) -> io::Result<()> {
+        let now_ms = unix_millis();
+        let now = (self.clock)();
+
+        for (index, line) in text.lines().enumerate() {
+            let bad_line = || {
+                io::Error::new(
+                    ErrorKind::InvalidData,
+                    format!("bad memo entry on line {}", index + 1),
+                )
+            };
+
+            let mut fields = line.split('\t');
+            let (stamp, arg, value) =
+                match (fields.next(), fields.next(), fields.next(), fields.next()) {
+                    (Some(stamp), Some(arg), Some(value), None) => (stamp, arg, value),
+                    _ => return Err(bad_line()),
+                };
+            let stamp: u64 = stamp.parse().map_err(|_| bad_line())?;
+            let arg = A::decode(arg).ok_or_else(bad_line)?;
+            let value = R::decode(value).ok_or_else(bad_line)?;
+
+            let age = Duration::from_millis(now_ms.saturating_sub(stamp));
+            if self.ttl.is_some_and(|ttl| age >= ttl) {
+                continue;
+            }
+            self.remove(&arg);
+            self.insert(arg, value, now.saturating_sub(age));
+        }
+
+        self.evict();
+        Ok(())
+    }
+}
+
+fn unix_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/* Types that can be written to the memo file, one tab separated line per entry */
+
+pub trait Persist: Sized {
+    fn encode(&self) -> String;
+    fn decode(text: &str) -> Option<Self>;
+}
+
+macro_rules! persist_with_from_str {
+    ($($t:ty),*) => {
+        $(
+            impl Persist for $t {
+                fn encode(&self) -> String {
+                    self.to_string()
+                }
+
+                fn decode(text: &str) -> Option<$t> {
+                    text.parse().ok()
+                }
+            }
+        )*
+    };
+}
+
+persist_with_from_str!(
+    u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize, bool, f32, f64
+);
+
+impl Persist for String {
+    fn encode(&self) -> String {
+        self.replace('\\', "\\\\")
+            .replace('\t', "\\t")
+            .replace('\n', "\\n")
+    }
+
+    fn decode(text: &str) -> Option<String> {
+        let mut out = String::new();
+        let mut chars = text.chars();
+        while let Some(c) = chars.next() {
+            if c != '\\' {
+                out.push(c);
+                continue;
+            }
+            match chars.next()? {
+                '\\' => out.push('\\'),
+                't' => out.push('\t'),
+                'n' => out.push('\n'),
+                _ => return None,
+            }
+        }
+        Some(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use std::env;
+    use std::process;
+    use std::rc::Rc;
+
+    fn counting() -> (Rc<Cell<u32>>, impl Fn(u32) -> String) {
+        let calls = Rc::new(Cell::new(0));
+        let counter = Rc::clone(&calls);
+        (calls, move |n: u32| {
+            counter.set(counter.get() + 1);
+            format!("#{}", n)
+        })
+    }
+
+    #[test]
+    fn different_argument_and_result_types() {
+        let (calls, calculation) = counting();
+        let mut memo = Memo::new(calculation);
+
+        assert_eq!(memo.get(1), "#1");
+        assert_eq!(memo.get(1), "#1");
+        assert_eq!(memo.get(2), "#2");
+
+        assert_eq!(calls.get(), 2);
+        assert_eq!(
+            memo.stats(),
+            Stats {
+                hits: 1,
+                misses: 2,
+                ..Stats::default()
+            }
+        );
+        assert!((memo.stats().hit_ratio() - 1.0 / 3.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn least_recently_used_is_evicted_first() {
+        let (_, calculation) = counting();
+        let mut memo = Memo::new(calculation).capacity(2);
+
+        memo.get(1);
+        memo.get(2);
+        memo.get(1); // 2 is now the oldest
+        memo.get(3);
+
+        assert!(memo.contains(&1));
+        assert!(!memo.contains(&2));
+        assert!(memo.contains(&3));
+        assert_eq!(memo.stats().evictions, 1);
+    }
+
+    #[test]
+    fn entries_expire_after_the_ttl() {
+        let now = Rc::new(Cell::new(Duration::ZERO));
+        let clock = Rc::clone(&now);
+        let (calls, calculation) = counting();
+        let mut memo = Memo::new(calculation)
+            .ttl(Duration::from_secs(10))
+            .with_clock(move || clock.get());
+
+        memo.get(7);
+        now.set(Duration::from_secs(9));
+        memo.get(7);
+        assert_eq!(calls.get(), 1);
+
+        now.set(Duration::from_secs(10));
+        memo.get(7);
+        assert_eq!(calls.get(), 2);
+        assert_eq!(memo.stats().expirations, 1);
+    }
+
+    #[test]
+    fn results_survive_a_restart() {
+        let path = env::temp_dir().join(format!("tutorial19_memo_{}.txt", process::id()));
+        let _ = fs::remove_file(&path);
+
+        let (calls, calculation) = counting();
+        let mut memo = Memo::new(calculation).persist_to(&path).unwrap();
+        memo.get(4);
+        memo.get(5);
+        drop(memo);
+
+        // a fresh memo, as if the program started again
+        let (calls_after, calculation) = counting();
+        let mut memo = Memo::new(calculation).persist_to(&path).unwrap();
+        assert_eq!(memo.get(4), "#4");
+        assert_eq!(memo.get(5), "#5");
+        assert_eq!(calls.get(), 2);
+        assert_eq!(calls_after.get(), 0);
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn strings_with_tabs_and_newlines_round_trip() {
+        let text = "a\tb\nc\\d".to_string();
+        assert_eq!(String::decode(&text.encode()), Some(text));
+        assert_eq!(u32::decode("nope"), None);
+    }
+}
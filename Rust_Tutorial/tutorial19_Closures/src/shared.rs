@@ -0,0 +1,279 @@
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::hash::{BuildHasher, Hash};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+
+/* SharedMemo: a memo that can be used from many threads through &self */
+
+const DEFAULT_SHARDS: usize = 16;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SharedStats {
+    pub hits: u64,
+    /// Times the calculation actually ran
+    pub misses: u64,
+    /// Callers that waited for another thread's calculation instead of running it
+    pub coalesced: u64,
+}
+
+enum Flight<R> {
+    Running,
+    Done(R),
+    // the calculation panicked, waiters go back and try again
+    Failed,
+}
+
+/* One in-progress calculation that other callers can wait on */
+struct InFlight<R> {
+    state: Mutex<Flight<R>>,
+    finished: Condvar,
+}
+
+impl<R: Clone> InFlight<R> {
+    fn finish(&self, outcome: Flight<R>) {
+        *lock(&self.state) = outcome;
+        self.finished.notify_all();
+    }
+
+    fn wait(&self) -> Option<R> {
+        let mut state = lock(&self.state);
+        loop {
+            match &*state {
+                Flight::Running => {
+                    state = self
+                        .finished
+                        .wait(state)
+                        .unwrap_or_else(|poisoned| poisoned.into_inner())
+                }
+                Flight::Done(value) => return Some(value.clone()),
+                Flight::Failed => return None,
+            }
+        }
+    }
+}
+
+enum Entry<R> {
+    Ready(R),
+    Pending(Arc<InFlight<R>>),
+}
+
+type Shard<A, R> = Mutex<HashMap<A, Entry<R>>>;
+
+pub struct SharedMemo<A, R, F> {
+    calculation: F,
+    shards: Vec<Shard<A, R>>,
+    hasher: RandomState,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    coalesced: AtomicU64,
+}
+
+impl<A, R, F> SharedMemo<A, R, F>
+where
+    F: Fn(A) -> R + Send + Sync,
+    A: Eq + Hash + Clone + Send,
+    R: Clone + Send,
+{
+    pub fn new(calculation: F) -> SharedMemo<A, R, F> {
+        SharedMemo::with_shards(calculation, DEFAULT_SHARDS)
+    }
+
+    /// More shards means less contention between unrelated keys
+    pub fn with_shards(calculation: F, shards: usize) -> SharedMemo<A, R, F> {
+        SharedMemo {
+            calculation,
+            shards: (0..shards.max(1))
+                .map(|_| Mutex::new(HashMap::new()))
+                .collect(),
+            hasher: RandomState::new(),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            coalesced: AtomicU64::new(0),
+        }
+    }
+
+    /// Returns the cached result, or calculates it. If another thread is already
+    /// calculating `arg`, waits for that result instead of calculating it twice
+    pub fn get(&self, arg: A) -> R {
+        let shard = &self.shards[self.hasher.hash_one(&arg) as usize % self.shards.len()];
+
+        loop {
+            let flight = {
+                let mut map = lock(shard);
+                match map.get(&arg) {
+                    Some(Entry::Ready(value)) => {
+                        self.hits.fetch_add(1, Ordering::Relaxed);
+                        return value.clone();
+                    }
+                    Some(Entry::Pending(flight)) => Arc::clone(flight),
+                    None => {
+                        let flight = Arc::new(InFlight {
+                            state: Mutex::new(Flight::Running),
+                            finished: Condvar::new(),
+                        });
+                        map.insert(arg.clone(), Entry::Pending(Arc::clone(&flight)));
+                        drop(map);
+                        return self.calculate(shard, arg, flight);
+                    }
+                }
+            };
+
+            // the shard lock is released while waiting, so other keys are not blocked
+            if let Some(value) = flight.wait() {
+                self.coalesced.fetch_add(1, Ordering::Relaxed);
+                return value;
+            }
+        }
+    }
+
+    fn calculate(&self, shard: &Shard<A, R>, arg: A, flight: Arc<InFlight<R>>) -> R {
+        self.misses.fetch_add(1, Ordering::Relaxed);
+
+        // Cleans up if the calculation panics so waiters don't hang forever
+        struct Abandon<'a, A: Eq + Hash, R: Clone> {
+            shard: &'a Shard<A, R>,
+            arg: Option<A>,
+            flight: &'a InFlight<R>,
+        }
+
+        impl<A: Eq + Hash, R: Clone> Drop for Abandon<'_, A, R> {
+            fn drop(&mut self) {
+                if let Some(arg) = self.arg.take() {
+                    lock(self.shard).remove(&arg);
+                    self.flight.finish(Flight::Failed);
+                }
+            }
+        }
+
+        let mut guard = Abandon {
+            shard,
+            arg: Some(arg.clone()),
+            flight: &flight,
+        };
+        let value = (self.calculation)(arg);
+        let arg = guard.arg.take().expect("only taken here or in drop");
+
+        lock(shard).insert(arg, Entry::Ready(value.clone()));
+        flight.finish(Flight::Done(value.clone()));
+        value
+    }
+
+    pub fn len(&self) -> usize {
+        self.shards
+            .iter()
+            .map(|shard| {
+                lock(shard)
+                    .values()
+                    .filter(|entry| matches!(entry, Entry::Ready(_)))
+                    .count()
+            })
+            .sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn stats(&self) -> SharedStats {
+        SharedStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            coalesced: self.coalesced.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/* A panic inside the calculation must not poison the memo for everyone else */
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::Barrier;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn concurrent_callers_share_one_calculation() {
+        let calls = AtomicUsize::new(0);
+        let memo = SharedMemo::new(|num: u32| {
+            calls.fetch_add(1, Ordering::SeqCst);
+            thread::sleep(Duration::from_millis(50));
+            num * 2
+        });
+        let barrier = Barrier::new(8);
+
+        let results: Vec<u32> = thread::scope(|s| {
+            let handles: Vec<_> = (0..8)
+                .map(|_| {
+                    s.spawn(|| {
+                        barrier.wait();
+                        memo.get(21)
+                    })
+                })
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+
+        assert_eq!(results, vec![42; 8]);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        let stats = memo.stats();
+        assert_eq!(stats.misses, 1);
+        assert_eq!(stats.hits + stats.coalesced, 7);
+    }
+
+    #[test]
+    fn unrelated_keys_calculate_at_the_same_time() {
+        // each calculation waits until both are running, which only happens
+        // if neither blocks the other
+        let running = AtomicUsize::new(0);
+        let memo = SharedMemo::with_shards(
+            |num: u32| {
+                running.fetch_add(1, Ordering::SeqCst);
+                let start = Instant::now();
+                while running.load(Ordering::SeqCst) < 2 {
+                    if start.elapsed() > Duration::from_secs(5) {
+                        return false;
+                    }
+                    thread::yield_now();
+                }
+                num > 0
+            },
+            1, // even sharing a shard, the lock is not held while calculating
+        );
+
+        let (a, b) = thread::scope(|s| {
+            let a = s.spawn(|| memo.get(1));
+            let b = s.spawn(|| memo.get(2));
+            (a.join().unwrap(), b.join().unwrap())
+        });
+
+        assert!(a && b);
+        assert_eq!(memo.len(), 2);
+    }
+
+    #[test]
+    fn a_panicking_calculation_can_be_retried() {
+        let first = AtomicUsize::new(0);
+        let memo = SharedMemo::new(|num: u32| {
+            if first.fetch_add(1, Ordering::SeqCst) == 0 {
+                panic!("first try fails");
+            }
+            num
+        });
+
+        let crashed = thread::scope(|s| s.spawn(|| memo.get(3)).join());
+        assert!(crashed.is_err());
+        assert!(memo.is_empty());
+
+        assert_eq!(memo.get(3), 3);
+        assert_eq!(memo.get(3), 3);
+        assert_eq!(memo.stats().hits, 1);
+    }
+}
@@ -0,0 +1,527 @@
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
+
+use crate::memo::{Memo, Stats};
+
+/* Workout planner behind problem_function */
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Strength,
+    Cardio,
+    Mobility,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Exercise {
+    pub name: String,
+    pub kind: Kind,
+    /// Reps (or minutes for cardio) at moderate intensity
+    pub base_reps: u32,
+    /// How hard one rep is, used by the cost estimator
+    pub effort: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Intensity {
+    Rest,
+    Light,
+    Moderate,
+    Hard,
+}
+
+impl Intensity {
+    /// Percentage of `base_reps` to do
+    pub fn percent(&self) -> u32 {
+        match self {
+            Intensity::Rest => 0,
+            Intensity::Light => 50,
+            Intensity::Moderate => 100,
+            Intensity::Hard => 150,
+        }
+    }
+
+    /// `amount` at this intensity, saturating instead of overflowing for huge
+    /// values from a rules file
+    pub fn scale(&self, amount: u32) -> u32 {
+        let scaled = u64::from(amount) * u64::from(self.percent()) / 100;
+        u32::try_from(scaled).unwrap_or(u32::MAX)
+    }
+}
+
+/* Rules, the old magic numbers (min < 25, tired == 0) live here now */
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rules {
+    pub exercises: Vec<Exercise>,
+    /// Fatigue at or above this means rest
+    pub rest_at: u32,
+    /// Fatigue at or above this means a light session
+    pub light_at: u32,
+    /// Fatigue at or above this means a moderate session, below it is hard
+    pub moderate_at: u32,
+    /// Before this many minutes we do strength work, after it only a cooldown
+    pub warmup_minutes: u32,
+    /// Fatigue gained by a moderate session, scaled by intensity
+    pub gain: u32,
+    /// Fatigue lost on a rest day
+    pub recovery: u32,
+    pub rest_days: u32,
+    pub max_streak: u32,
+}
+
+impl Default for Rules {
+    fn default() -> Rules {
+        Rules {
+            exercises: vec![
+                exercise("situps", Kind::Strength, 20, 1),
+                exercise("pushups", Kind::Strength, 15, 2),
+                exercise("stretch", Kind::Mobility, 10, 1),
+            ],
+            rest_at: 90,
+            light_at: 60,
+            moderate_at: 30,
+            warmup_minutes: 25,
+            gain: 20,
+            recovery: 30,
+            rest_days: 2,
+            max_streak: 3,
+        }
+    }
+}
+
+fn exercise(name: &str, kind: Kind, base_reps: u32, effort: u32) -> Exercise {
+    Exercise {
+        name: name.to_string(),
+        kind,
+        base_reps,
+        effort,
+    }
+}
+
+#[derive(Debug)]
+pub enum RulesError {
+    Io(io::Error),
+    Syntax {
+        line: usize,
+        message: String,
+    },
+    /// Every line parsed, but the rules contradict each other
+    Invalid(String),
+}
+
+impl fmt::Display for RulesError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RulesError::Io(e) => write!(f, "could not read the rules: {}", e),
+            RulesError::Syntax { line, message } => write!(f, "line {}: {}", line, message),
+            RulesError::Invalid(message) => write!(f, "invalid rules: {}", message),
+        }
+    }
+}
+
+impl Error for RulesError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            RulesError::Io(e) => Some(e),
+            RulesError::Syntax { .. } | RulesError::Invalid(_) => None,
+        }
+    }
+}
+
+impl Rules {
+    pub fn load(path: impl AsRef<Path>) -> Result<Rules, RulesError> {
+        let text = fs::read_to_string(path).map_err(RulesError::Io)?;
+        Rules::parse(&text)
+    }
+
+    /// `[rules]` holds `key = number` lines, `[exercises]` holds
+    /// `name = kind, base_reps, effort` lines. Missing rules keep their default
+    pub fn parse(text: &str) -> Result<Rules, RulesError> {
+        let mut rules = Rules::default();
+        let mut exercises = Vec::new();
+        let mut section = "";
+
+        for (index, raw) in text.lines().enumerate() {
+            let line = index + 1;
+            let syntax = |message: &str| RulesError::Syntax {
+                line,
+                message: message.to_string(),
+            };
+
+            let trimmed = raw.split('#').next().unwrap_or("").trim();
+            if trimmed.is_empty() {
+                continue;
+            }
+            if let Some(name) = trimmed.strip_prefix('[').and_then(|s| s.strip_suffix(']')) {
+                section = match name.trim() {
+                    "rules" => "rules",
+                    "exercises" => "exercises",
+                    _ => return Err(syntax("unknown section")),
+                };
+                continue;
+            }
+
+            let (key, value) = trimmed
+                .split_once('=')
+                .ok_or_else(|| syntax("expected `key = value`"))?;
+            let (key, value) = (key.trim(), value.trim());
+
+            match section {
+                "rules" => {
+                    let number: u32 = value.parse().map_err(|_| syntax("expected a number"))?;
+                    let field = match key {
+                        "rest_at" => &mut rules.rest_at,
+                        "light_at" => &mut rules.light_at,
+                        "moderate_at" => &mut rules.moderate_at,
+                        "warmup_minutes" => &mut rules.warmup_minutes,
+                        "gain" => &mut rules.gain,
+                        "recovery" => &mut rules.recovery,
+                        "rest_days" => &mut rules.rest_days,
+                        "max_streak" => &mut rules.max_streak,
+                        _ => return Err(syntax("unknown rule")),
+                    };
+                    *field = number;
+                }
+                "exercises" => {
+                    let parts: Vec<&str> = value.split(',').map(str::trim).collect();
+                    let (kind, reps, effort) = match parts[..] {
+                        [kind, reps, effort] => (kind, reps, effort),
+                        _ => return Err(syntax("expected `kind, base_reps, effort`")),
+                    };
+                    let kind = match kind {
+                        "strength" => Kind::Strength,
+                        "cardio" => Kind::Cardio,
+                        "mobility" => Kind::Mobility,
+                        _ => return Err(syntax("kind must be strength, cardio or mobility")),
+                    };
+                    let base_reps = reps
+                        .parse()
+                        .map_err(|_| syntax("base_reps must be a number"))?;
+                    let effort = effort
+                        .parse()
+                        .map_err(|_| syntax("effort must be a number"))?;
+                    exercises.push(exercise(key, kind, base_reps, effort));
+                }
+                _ => return Err(syntax("value outside of a section")),
+            }
+        }
+
+        if !exercises.is_empty() {
+            rules.exercises = exercises;
+        }
+        if !(rules.moderate_at <= rules.light_at && rules.light_at <= rules.rest_at) {
+            return Err(RulesError::Invalid(
+                "thresholds must satisfy moderate_at <= light_at <= rest_at".to_string(),
+            ));
+        }
+        if rules.rest_days > 7 || rules.max_streak == 0 {
+            return Err(RulesError::Invalid(
+                "rest_days must be at most 7 and max_streak at least 1".to_string(),
+            ));
+        }
+        Ok(rules)
+    }
+
+    /// Fatigue goes from 0 (fresh) to 100 (exhausted)
+    pub fn intensity(&self, fatigue: u32) -> Intensity {
+        if fatigue >= self.rest_at {
+            Intensity::Rest
+        } else if fatigue >= self.light_at {
+            Intensity::Light
+        } else if fatigue >= self.moderate_at {
+            Intensity::Moderate
+        } else {
+            Intensity::Hard
+        }
+    }
+}
+
+/* Sessions and the weekly plan */
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Set {
+    pub exercise: String,
+    pub reps: u32,
+    pub effort: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Session {
+    pub intensity: Intensity,
+    pub sets: Vec<Set>,
+}
+
+impl Session {
+    pub fn is_rest(&self) -> bool {
+        self.sets.is_empty()
+    }
+}
+
+/// One session. Early on (`minutes < warmup_minutes`) it is strength and cardio work,
+/// later it is just a mobility cooldown
+pub fn session(rules: &Rules, minutes: u32, fatigue: u32) -> Session {
+    session_at(rules, minutes, rules.intensity(fatigue))
+}
+
+fn session_at(rules: &Rules, minutes: u32, intensity: Intensity) -> Session {
+    let early = minutes < rules.warmup_minutes;
+    let sets = rules
+        .exercises
+        .iter()
+        .filter(|e| (e.kind == Kind::Mobility) != early)
+        .map(|e| Set {
+            exercise: e.name.clone(),
+            reps: intensity.scale(e.base_reps),
+            effort: e.effort,
+        })
+        .filter(|set| set.reps > 0)
+        .collect();
+
+    Session { intensity, sets }
+}
+
+pub const WEEKDAYS: [&str; 7] = [
+    "Monday",
+    "Tuesday",
+    "Wednesday",
+    "Thursday",
+    "Friday",
+    "Saturday",
+    "Sunday",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DayPlan {
+    pub day: &'static str,
+    /// Fatigue at the start of the day
+    pub fatigue: u32,
+    pub session: Session,
+}
+
+/// Seven days starting Monday. Rest days are forced when fatigue says so, when the
+/// training streak hits `max_streak`, or when the week would otherwise end with
+/// fewer than `rest_days` rests
+pub fn weekly_plan(rules: &Rules, start_fatigue: u32) -> Vec<DayPlan> {
+    let mut fatigue = start_fatigue.min(100);
+    let mut streak = 0;
+    let mut rests = 0;
+    let mut plan = Vec::new();
+
+    for (index, day) in WEEKDAYS.iter().enumerate() {
+        let days_left = 7 - index as u32;
+        let rests_needed = rules.rest_days.saturating_sub(rests);
+        let intensity = rules.intensity(fatigue);
+
+        let rest =
+            intensity == Intensity::Rest || streak >= rules.max_streak || rests_needed >= days_left;
+
+        let session = if rest {
+            Session {
+                intensity: Intensity::Rest,
+                sets: Vec::new(),
+            }
+        } else {
+            session_at(rules, 0, intensity)
+        };
+        plan.push(DayPlan {
+            day,
+            fatigue,
+            session,
+        });
+
+        if rest {
+            rests += 1;
+            streak = 0;
+            fatigue = fatigue.saturating_sub(rules.recovery);
+        } else {
+            streak += 1;
+            fatigue = fatigue.saturating_add(intensity.scale(rules.gain)).min(100);
+        }
+    }
+    plan
+}
+
+/* Plan cost estimator, the memoized expensive calculation from problem_function */
+
+type CostKey = (u32, u32);
+
+pub struct CostEstimator<F> {
+    memo: Memo<CostKey, u32, F>,
+}
+
+impl<F> CostEstimator<F>
+where
+    F: Fn(CostKey) -> u32,
+{
+    /// `calculation` gets `(effort, reps)` and returns the cost of that set
+    pub fn new(calculation: F) -> CostEstimator<F> {
+        CostEstimator {
+            memo: Memo::new(calculation),
+        }
+    }
+
+    pub fn session_cost(&mut self, session: &Session) -> u32 {
+        session
+            .sets
+            .iter()
+            .map(|set| self.memo.get((set.effort, set.reps)))
+            .fold(0, u32::saturating_add)
+    }
+
+    pub fn plan_cost(&mut self, plan: &[DayPlan]) -> u32 {
+        plan.iter()
+            .map(|day| self.session_cost(&day.session))
+            .fold(0, u32::saturating_add)
+    }
+
+    pub fn stats(&self) -> Stats {
+        self.memo.stats()
+    }
+}
+
+/// The slow calculation from the original example
+pub fn slow_set_cost((effort, reps): CostKey) -> u32 {
+    println!("calculating slowly...");
+    thread::sleep(Duration::from_secs(2));
+    effort.saturating_mul(reps)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    #[test]
+    fn intensity_follows_fatigue() {
+        let rules = Rules::default();
+
+        assert_eq!(rules.intensity(0), Intensity::Hard);
+        assert_eq!(rules.intensity(30), Intensity::Moderate);
+        assert_eq!(rules.intensity(60), Intensity::Light);
+        assert_eq!(rules.intensity(95), Intensity::Rest);
+    }
+
+    #[test]
+    fn early_sessions_are_strength_and_later_ones_cooldown() {
+        let rules = Rules::default();
+
+        let early = session(&rules, 10, 40);
+        let names: Vec<_> = early.sets.iter().map(|s| s.exercise.as_str()).collect();
+        assert_eq!(names, vec!["situps", "pushups"]);
+        assert_eq!(early.sets[0].reps, 20);
+
+        let late = session(&rules, 40, 70);
+        assert_eq!(late.intensity, Intensity::Light);
+        assert_eq!(late.sets[0].exercise, "stretch");
+        assert_eq!(late.sets[0].reps, 5);
+
+        assert!(session(&rules, 10, 100).is_rest());
+    }
+
+    #[test]
+    fn rules_parse_from_text() {
+        let text = "\
+# thresholds
+[rules]
+rest_at = 80
+warmup_minutes = 15
+
+[exercises]
+burpees = strength, 10, 4
+jog = cardio, 20, 2
+";
+        let rules = Rules::parse(text).unwrap();
+        assert_eq!(rules.rest_at, 80);
+        assert_eq!(rules.warmup_minutes, 15);
+        assert_eq!(rules.light_at, Rules::default().light_at);
+        assert_eq!(rules.exercises.len(), 2);
+        assert_eq!(rules.exercises[1].kind, Kind::Cardio);
+
+        let err = Rules::parse("[rules]\nrest_at = lots\n").unwrap_err();
+        assert!(matches!(err, RulesError::Syntax { line: 2, .. }));
+        assert!(Rules::parse("[exercises]\nrow = rowing, 1, 1\n").is_err());
+        assert!(matches!(
+            Rules::parse("[rules]\nrest_at = 10\n"),
+            Err(RulesError::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn weekly_plan_enforces_rest_days() {
+        let rules = Rules::default();
+
+        for start in [0, 20, 50, 95] {
+            let plan = weekly_plan(&rules, start);
+            assert_eq!(plan.len(), 7);
+
+            let rests = plan.iter().filter(|d| d.session.is_rest()).count() as u32;
+            assert!(rests >= rules.rest_days, "start {}: {} rests", start, rests);
+
+            let mut streak = 0;
+            for day in &plan {
+                streak = if day.session.is_rest() { 0 } else { streak + 1 };
+                assert!(streak <= rules.max_streak);
+            }
+        }
+        assert!(weekly_plan(&rules, 95)[0].session.is_rest());
+    }
+
+    #[test]
+    fn huge_rule_values_saturate() {
+        let text = format!(
+            "[rules]\ngain = {max}\n[exercises]\nsquats = strength, {max}, {max}\n",
+            max = u32::MAX
+        );
+        let rules = Rules::parse(&text).unwrap();
+
+        let hard = session(&rules, 0, 0);
+        assert_eq!(hard.intensity, Intensity::Hard);
+        assert_eq!(hard.sets[0].reps, u32::MAX);
+        assert_eq!(session(&rules, 0, 40).sets[0].reps, u32::MAX);
+        assert_eq!(session(&rules, 0, 70).sets[0].reps, u32::MAX / 2);
+
+        let plan = weekly_plan(&rules, 0);
+        assert_eq!(plan[1].fatigue, 100);
+        assert!(plan[1].session.is_rest());
+
+        // slow_set_cost without the sleep
+        let mut estimator =
+            CostEstimator::new(|(effort, reps): CostKey| effort.saturating_mul(reps));
+        let two_sets = Session {
+            intensity: Intensity::Hard,
+            sets: vec![hard.sets[0].clone(), hard.sets[0].clone()],
+        };
+        assert_eq!(estimator.session_cost(&two_sets), u32::MAX);
+        assert_eq!(estimator.plan_cost(&plan), u32::MAX);
+    }
+
+    #[test]
+    fn cost_estimator_memoizes_repeated_sets() {
+        let calls = Cell::new(0);
+        let mut estimator = CostEstimator::new(|(effort, reps)| {
+            calls.set(calls.get() + 1);
+            effort * reps
+        });
+        let rules = Rules::default();
+        let plan = weekly_plan(&rules, 0);
+
+        let total = estimator.plan_cost(&plan);
+        let expected: u32 = plan
+            .iter()
+            .flat_map(|d| &d.session.sets)
+            .map(|s| s.effort * s.reps)
+            .sum();
+        assert_eq!(total, expected);
+
+        let distinct = calls.get();
+        assert_eq!(estimator.plan_cost(&plan), total);
+        assert_eq!(calls.get(), distinct);
+        assert!(estimator.stats().hits > 0);
+    }
+}
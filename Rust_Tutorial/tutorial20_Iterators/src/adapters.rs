@@ -0,0 +1,634 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::rc::Rc;
+
+/* Extra adapters for any Iterator, the same way map/filter/zip are provided */
+
+pub trait IterExt: Iterator + Sized {
+    /// Vecs of exactly `size` items, leftovers are kept in `remainder()`
+    fn chunks_exact(self, size: usize) -> ChunksExact<Self> {
+        assert!(size > 0, "chunk size must be at least 1");
+        ChunksExact {
+            iter: self,
+            size,
+            remainder: Vec::new(),
+        }
+    }
+
+    /// Overlapping windows of `size` owned items: [1, 2, 3] -> [1, 2], [2, 3]
+    fn windows(self, size: usize) -> Windows<Self>
+    where
+        Self::Item: Clone,
+    {
+        assert!(size > 0, "window size must be at least 1");
+        Windows {
+            iter: self,
+            size,
+            window: VecDeque::with_capacity(size),
+        }
+    }
+
+    /// Alternates between the two, then finishes whichever is longer
+    fn interleave<J>(self, other: J) -> Interleave<Self, J::IntoIter>
+    where
+        J: IntoIterator<Item = Self::Item>,
+    {
+        Interleave {
+            a: self,
+            b: other.into_iter(),
+            b_turn: false,
+        }
+    }
+
+    /// Drops items whose key is the same as the item just before
+    fn dedup_by_key<K, F>(self, key: F) -> DedupByKey<Self, K, F>
+    where
+        K: PartialEq,
+        F: FnMut(&Self::Item) -> K,
+    {
+        DedupByKey {
+            iter: self,
+            key,
+            last: None,
+        }
+    }
+
+    /// `f` pulls as many items as it wants for each output, `None` ends the iterator
+    fn batching<B, F>(self, f: F) -> Batching<Self, F>
+    where
+        F: FnMut(&mut Self) -> Option<B>,
+    {
+        Batching { iter: self, f }
+    }
+
+    /// Like `peekable`, but can look more than one item ahead
+    fn peekable_n(self) -> PeekN<Self> {
+        PeekN {
+            iter: self,
+            buffer: VecDeque::new(),
+        }
+    }
+
+    /// Skips `offset` items, then yields every `step`th one
+    fn step_by_with_offset(self, offset: usize, step: usize) -> StepByWithOffset<Self> {
+        assert!(step > 0, "step must be at least 1");
+        StepByWithOffset {
+            iter: self,
+            offset,
+            step,
+            first: true,
+        }
+    }
+
+    /// Two iterators that both see every item
+    fn tee(self) -> (Tee<Self>, Tee<Self>)
+    where
+        Self::Item: Clone,
+    {
+        let shared = Rc::new(RefCell::new(TeeShared {
+            iter: self,
+            buffer: VecDeque::new(),
+            ahead: Side::Left,
+        }));
+        (
+            Tee {
+                shared: Rc::clone(&shared),
+                side: Side::Left,
+            },
+            Tee {
+                shared,
+                side: Side::Right,
+            },
+        )
+    }
+}
+
+impl<I: Iterator> IterExt for I {}
+
+/* chunks_exact */
+
+pub struct ChunksExact<I: Iterator> {
+    iter: I,
+    size: usize,
+    remainder: Vec<I::Item>,
+}
+
+impl<I: Iterator> ChunksExact<I> {
+    /// The items that did not fill a whole chunk, known once the iterator is done
+    pub fn remainder(&self) -> &[I::Item] {
+        &self.remainder
+    }
+}
+
+impl<I: Iterator> Iterator for ChunksExact<I> {
+    type Item = Vec<I::Item>;
+
+    fn next(&mut self) -> Option<Vec<I::Item>> {
+        let chunk: Vec<_> = self.iter.by_ref().take(self.size).collect();
+        if chunk.len() == self.size {
+            Some(chunk)
+        } else {
+            self.remainder.extend(chunk);
+            None
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let (low, high) = self.iter.size_hint();
+        (low / self.size, high.map(|high| high / self.size))
+    }
+}
+
+impl<I: ExactSizeIterator> ExactSizeIterator for ChunksExact<I> {}
+
+impl<I> DoubleEndedIterator for ChunksExact<I>
+where
+    I: DoubleEndedIterator + ExactSizeIterator,
+{
+    fn next_back(&mut self) -> Option<Vec<I::Item>> {
+        // set the leftovers aside first so the back lines up with chunk boundaries
+        let extra = self.iter.len() % self.size;
+        if extra > 0 {
+            let mut tail: Vec<_> = (0..extra).filter_map(|_| self.iter.next_back()).collect();
+            tail.reverse();
+            tail.append(&mut self.remainder);
+            self.remainder = tail;
+        }
+        if self.iter.len() < self.size {
+            return None;
+        }
+
+        let mut chunk: Vec<_> = (0..self.size)
+            .filter_map(|_| self.iter.next_back())
+            .collect();
+        chunk.reverse();
+        Some(chunk)
+    }
+}
+
+/* windows */
+
+pub struct Windows<I: Iterator> {
+    iter: I,
+    size: usize,
+    window: VecDeque<I::Item>,
+}
+
+impl<I> Iterator for Windows<I>
+where
+    I: Iterator,
+    I::Item: Clone,
+{
+    type Item = Vec<I::Item>;
+
+    fn next(&mut self) -> Option<Vec<I::Item>> {
+        if self.window.len() == self.size {
+            self.window.pop_front();
+        }
+        while self.window.len() < self.size {
+            self.window.push_back(self.iter.next()?);
+        }
+        Some(self.window.iter().cloned().collect())
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let (low, high) = self.iter.size_hint();
+        // before the first window, size - 1 items only fill it up
+        let filling = (self.size - 1).saturating_sub(self.window.len());
+        (
+            low.saturating_sub(filling),
+            high.map(|high| high.saturating_sub(filling)),
+        )
+    }
+}
+
+impl<I> ExactSizeIterator for Windows<I>
+where
+    I: ExactSizeIterator,
+    I::Item: Clone,
+{
+}
+
+/* interleave */
+
+pub struct Interleave<I, J> {
+    a: I,
+    b: J,
+    b_turn: bool,
+}
+
+impl<I, J> Iterator for Interleave<I, J>
+where
+    I: Iterator,
+    J: Iterator<Item = I::Item>,
+{
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<I::Item> {
+        let b_turn = self.b_turn;
+        self.b_turn = !b_turn;
+        if b_turn {
+            self.b.next().or_else(|| self.a.next())
+        } else {
+            self.a.next().or_else(|| self.b.next())
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let (a_low, a_high) = self.a.size_hint();
+        let (b_low, b_high) = self.b.size_hint();
+        let high = match (a_high, b_high) {
+            (Some(a), Some(b)) => a.checked_add(b),
+            _ => None,
+        };
+        (a_low.saturating_add(b_low), high)
+    }
+}
+
+impl<I, J> ExactSizeIterator for Interleave<I, J>
+where
+    I: ExactSizeIterator,
+    J: ExactSizeIterator<Item = I::Item>,
+{
+}
+
+/* dedup_by_key */
+
+pub struct DedupByKey<I, K, F> {
+    iter: I,
+    key: F,
+    last: Option<K>,
+}
+
+impl<I, K, F> Iterator for DedupByKey<I, K, F>
+where
+    I: Iterator,
+    K: PartialEq,
+    F: FnMut(&I::Item) -> K,
+{
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<I::Item> {
+        for item in self.iter.by_ref() {
+            let key = (self.key)(&item);
+            if self.last.as_ref() != Some(&key) {
+                self.last = Some(key);
+                return Some(item);
+            }
+        }
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let (low, high) = self.iter.size_hint();
+        // the very first item always comes through, after that everything may be a duplicate
+        let low = if self.last.is_none() && low > 0 { 1 } else { 0 };
+        (low, high)
+    }
+}
+
+/* batching */
+
+pub struct Batching<I, F> {
+    iter: I,
+    f: F,
+}
+
+impl<B, I, F> Iterator for Batching<I, F>
+where
+    I: Iterator,
+    F: FnMut(&mut I) -> Option<B>,
+{
+    type Item = B;
+
+    fn next(&mut self) -> Option<B> {
+        (self.f)(&mut self.iter)
+    }
+}
+
+/* peek_n */
+
+pub struct PeekN<I: Iterator> {
+    iter: I,
+    buffer: VecDeque<I::Item>,
+}
+
+impl<I: Iterator> PeekN<I> {
+    /// Up to `n` upcoming items without consuming them, fewer near the end
+    pub fn peek_n(&mut self, n: usize) -> &[I::Item] {
+        while self.buffer.len() < n {
+            match self.iter.next() {
+                Some(item) => self.buffer.push_back(item),
+                None => break,
+            }
+        }
+        let available = n.min(self.buffer.len());
+        &self.buffer.make_contiguous()[..available]
+    }
+
+    pub fn peek(&mut self) -> Option<&I::Item> {
+        self.peek_n(1).first()
+    }
+}
+
+impl<I: Iterator> Iterator for PeekN<I> {
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<I::Item> {
+        self.buffer.pop_front().or_else(|| self.iter.next())
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let (low, high) = self.iter.size_hint();
+        let buffered = self.buffer.len();
+        (
+            low.saturating_add(buffered),
+            high.and_then(|high| high.checked_add(buffered)),
+        )
+    }
+}
+
+impl<I: ExactSizeIterator> ExactSizeIterator for PeekN<I> {}
+
+impl<I: DoubleEndedIterator> DoubleEndedIterator for PeekN<I> {
+    fn next_back(&mut self) -> Option<I::Item> {
+        self.iter.next_back().or_else(|| self.buffer.pop_back())
+    }
+}
+
+/* step_by_with_offset */
+
+pub struct StepByWithOffset<I> {
+    iter: I,
+    offset: usize,
+    step: usize,
+    first: bool,
+}
+
+impl<I> StepByWithOffset<I> {
+    // how many items are left, out of `remaining` in the inner iterator
+    fn count_for(&self, remaining: usize) -> usize {
+        if !self.first {
+            remaining / self.step
+        } else if remaining > self.offset {
+            1 + (remaining - self.offset - 1) / self.step
+        } else {
+            0
+        }
+    }
+}
+
+impl<I: Iterator> Iterator for StepByWithOffset<I> {
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<I::Item> {
+        if self.first {
+            self.first = false;
+            self.iter.nth(self.offset)
+        } else {
+            self.iter.nth(self.step - 1)
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let (low, high) = self.iter.size_hint();
+        (self.count_for(low), high.map(|high| self.count_for(high)))
+    }
+}
+
+impl<I: ExactSizeIterator> ExactSizeIterator for StepByWithOffset<I> {}
+
+impl<I> DoubleEndedIterator for StepByWithOffset<I>
+where
+    I: DoubleEndedIterator + ExactSizeIterator,
+{
+    fn next_back(&mut self) -> Option<I::Item> {
+        let remaining = self.iter.len();
+        let count = self.count_for(remaining);
+        if count == 0 {
+            // nothing left to yield, drain so later calls agree
+            self.iter.nth_back(remaining);
+            return None;
+        }
+
+        // index of the last item we would yield, counted from the front
+        let last = if self.first {
+            self.offset + (count - 1) * self.step
+        } else {
+            count * self.step - 1
+        };
+        self.iter.nth_back(remaining - 1 - last)
+    }
+}
+
+/* tee */
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Side {
+    Left,
+    Right,
+}
+
+struct TeeShared<I: Iterator> {
+    iter: I,
+    // items one side has seen and the other has not
+    buffer: VecDeque<I::Item>,
+    // which side the buffer is ahead for
+    ahead: Side,
+}
+
+pub struct Tee<I: Iterator> {
+    shared: Rc<RefCell<TeeShared<I>>>,
+    side: Side,
+}
+
+impl<I> Iterator for Tee<I>
+where
+    I: Iterator,
+    I::Item: Clone,
+{
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<I::Item> {
+        let mut shared = self.shared.borrow_mut();
+        if shared.ahead != self.side {
+            if let Some(item) = shared.buffer.pop_front() {
+                return Some(item);
+            }
+        }
+
+        let item = shared.iter.next()?;
+        shared.ahead = self.side;
+        shared.buffer.push_back(item.clone());
+        Some(item)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let shared = self.shared.borrow();
+        let (low, high) = shared.iter.size_hint();
+        let buffered = if shared.ahead != self.side {
+            shared.buffer.len()
+        } else {
+            0
+        };
+        (
+            low.saturating_add(buffered),
+            high.and_then(|high| high.checked_add(buffered)),
+        )
+    }
+}
+
+impl<I> ExactSizeIterator for Tee<I>
+where
+    I: ExactSizeIterator,
+    I::Item: Clone,
+{
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /* Checks that every size_hint along the way brackets what is really left */
+    fn assert_size_hints<I: Iterator>(make: impl Fn() -> I) {
+        let total = make().count();
+        let mut iter = make();
+        for left in (0..=total).rev() {
+            let (low, high) = iter.size_hint();
+            assert!(low <= left, "lower bound {} but {} left", low, left);
+            assert!(
+                high.is_none_or(|high| high >= left),
+                "upper bound {:?} but {} left",
+                high,
+                left
+            );
+            iter.next();
+        }
+    }
+
+    fn exact_lengths<I: ExactSizeIterator>(mut iter: I) {
+        let mut expected = iter.len();
+        while iter.next().is_some() {
+            expected -= 1;
+            assert_eq!(iter.len(), expected);
+        }
+        assert_eq!(expected, 0);
+    }
+
+    #[test]
+    fn chunks_exact_keeps_the_remainder() {
+        let mut chunks = (1..8).chunks_exact(3);
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks.next(), Some(vec![1, 2, 3]));
+        assert_eq!(chunks.next(), Some(vec![4, 5, 6]));
+        assert_eq!(chunks.next(), None);
+        assert_eq!(chunks.remainder(), &[7]);
+
+        let mut back = (1..8).chunks_exact(3);
+        assert_eq!(back.next_back(), Some(vec![4, 5, 6]));
+        assert_eq!(back.remainder(), &[7]);
+        assert_eq!(back.next(), Some(vec![1, 2, 3]));
+        assert_eq!(back.next_back(), None);
+
+        exact_lengths((0..10).chunks_exact(4));
+    }
+
+    #[test]
+    fn windows_slide_over_owned_items() {
+        let words = vec!["a".to_string(), "b".to_string(), "c".to_string()];
+        let windows: Vec<_> = words.into_iter().windows(2).collect();
+        assert_eq!(windows, vec![vec!["a", "b"], vec!["b", "c"]]);
+
+        assert_eq!((0..2).windows(3).count(), 0);
+        assert_eq!((0..5).windows(3).len(), 3);
+        exact_lengths((0..6).windows(2));
+    }
+
+    #[test]
+    fn interleave_finishes_the_longer_side() {
+        let mixed: Vec<_> = vec![1, 3, 5, 7]
+            .into_iter()
+            .interleave(vec![2, 4])
+            .collect();
+        assert_eq!(mixed, vec![1, 2, 3, 4, 5, 7]);
+        exact_lengths((0..3).interleave(10..15));
+        assert_size_hints(|| (0..3).filter(|x| x % 2 == 0).interleave(10..15));
+    }
+
+    #[test]
+    fn dedup_by_key_only_drops_neighbours() {
+        let words = ["apple", "avocado", "banana", "blueberry", "apricot"];
+        let firsts: Vec<_> = words
+            .iter()
+            .dedup_by_key(|w| w.chars().next())
+            .copied()
+            .collect();
+        assert_eq!(firsts, vec!["apple", "banana", "apricot"]);
+        assert_size_hints(|| [1, 1, 1, 2, 2, 3].iter().dedup_by_key(|x| **x));
+    }
+
+    #[test]
+    fn batching_groups_pairs() {
+        let pairs: Vec<_> = (1..=5)
+            .batching(|it| Some((it.next()?, it.next()?)))
+            .collect();
+        assert_eq!(pairs, vec![(1, 2), (3, 4)]);
+        assert_size_hints(|| (1..=5).batching(|it| it.next()));
+    }
+
+    #[test]
+    fn peek_n_looks_ahead_without_consuming() {
+        let mut it = (1..5).peekable_n();
+        assert_eq!(it.peek_n(3), &[1, 2, 3]);
+        assert_eq!(it.len(), 4);
+        assert_eq!(it.next(), Some(1));
+        assert_eq!(it.peek_n(10), &[2, 3, 4]);
+        assert_eq!(it.next_back(), Some(4));
+        assert_eq!(it.collect::<Vec<_>>(), vec![2, 3]);
+
+        let mut it = (1..4).peekable_n();
+        it.peek_n(3);
+        assert_eq!(it.next_back(), Some(3));
+        exact_lengths(it);
+    }
+
+    #[test]
+    fn step_by_with_offset_matches_skip_then_step_by() {
+        for offset in 0..5 {
+            for step in 1..4 {
+                let expected: Vec<_> = (0..11).skip(offset).step_by(step).collect();
+                let forward: Vec<_> = (0..11).step_by_with_offset(offset, step).collect();
+                assert_eq!(forward, expected, "offset {} step {}", offset, step);
+
+                let mut backward: Vec<_> =
+                    (0..11).step_by_with_offset(offset, step).rev().collect();
+                backward.reverse();
+                assert_eq!(backward, expected, "offset {} step {}", offset, step);
+
+                exact_lengths((0..11).step_by_with_offset(offset, step));
+            }
+        }
+
+        let mut it = (0..10).step_by_with_offset(1, 3);
+        assert_eq!(it.next(), Some(1));
+        assert_eq!(it.next_back(), Some(7));
+        assert_eq!(it.next(), Some(4));
+        assert_eq!(it.next(), None);
+    }
+
+    #[test]
+    fn tee_gives_both_sides_every_item() {
+        let (mut left, mut right) = vec![1, 2, 3].into_iter().tee();
+
+        assert_eq!(left.next(), Some(1));
+        assert_eq!(left.next(), Some(2));
+        assert_eq!(right.len(), 3);
+        assert_eq!(right.next(), Some(1));
+        assert_eq!(right.next(), Some(2));
+        assert_eq!(right.next(), Some(3));
+        assert_eq!(left.len(), 1);
+        assert_eq!(left.next(), Some(3));
+        assert_eq!(left.next(), None);
+        assert_eq!(right.next(), None);
+    }
+}
@@ -0,0 +1,173 @@
+/* Creating your own iterator: the Counter from the book, grown a configurable
+range and step so it can feed the adapters in this crate */
+
+pub struct Counter {
+    next: u32,
+    step: u32,
+    /// Values still to come, u64 so 0..=u32::MAX fits
+    left: u64,
+}
+
+impl Counter {
+    /// Counts from `count + 1` up to 5, like the book's Counter
+    pub fn new(count: u32) -> Counter {
+        Counter::with_range(count.saturating_add(1), 5, 1)
+    }
+
+    /// `start`, `start + step` and so on, never going past `end`. A step of 0
+    /// counts as 1
+    pub fn with_range(start: u32, end: u32, step: u32) -> Counter {
+        let step = step.max(1);
+        let left = if start > end {
+            0
+        } else {
+            u64::from((end - start) / step) + 1
+        };
+        Counter {
+            next: start,
+            step,
+            left,
+        }
+    }
+}
+
+impl Iterator for Counter {
+    type Item = u32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.left == 0 {
+            return None;
+        }
+        let value = self.next;
+        self.left -= 1;
+        if self.left > 0 {
+            // there is another value, so this stays at or below `end`
+            self.next += self.step;
+        }
+        Some(value)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let left = usize::try_from(self.left).unwrap_or(usize::MAX);
+        (left, Some(left))
+    }
+}
+
+impl ExactSizeIterator for Counter {}
+
+impl DoubleEndedIterator for Counter {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.left == 0 {
+            return None;
+        }
+        self.left -= 1;
+        // the last value is at most `end`, so this can't overflow
+        Some(self.next + (self.left as u32) * self.step)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adapters::IterExt;
+    use crate::parallel::IntoParIter;
+
+    #[test]
+    fn calling_next_test() {
+        let mut counter = Counter::new(0);
+
+        assert_eq!(counter.next(), Some(1));
+        assert_eq!(counter.next(), Some(2));
+        assert_eq!(counter.next(), Some(3));
+        assert_eq!(counter.next(), Some(4));
+        assert_eq!(counter.next(), Some(5));
+        assert_eq!(counter.next(), None);
+        assert_eq!(Counter::new(5).next(), None);
+    }
+
+    #[test]
+    fn using_default_iterator_trait_methods() {
+        let sum: u32 = Counter::new(0)
+            .zip(Counter::new(0).skip(1))
+            .map(|(a, b)| a * b)
+            .filter(|x| x % 3 == 0)
+            .sum();
+
+        assert_eq!(sum, 18);
+    }
+
+    #[test]
+    fn configurable_counter() {
+        let evens: Vec<u32> = Counter::with_range(0, 10, 2).collect();
+        assert_eq!(evens, vec![0, 2, 4, 6, 8, 10]);
+
+        let counter = Counter::with_range(3, 20, 5);
+        assert_eq!(counter.len(), 4);
+        assert_eq!(counter.collect::<Vec<_>>(), vec![3, 8, 13, 18]);
+
+        assert_eq!(Counter::with_range(5, 4, 1).next(), None);
+        assert_eq!(Counter::with_range(7, 7, 0).collect::<Vec<_>>(), vec![7]);
+
+        let mut counter = Counter::with_range(0, 9, 2);
+        assert_eq!(counter.next_back(), Some(8));
+        assert_eq!(counter.next(), Some(0));
+        assert_eq!(counter.rev().collect::<Vec<_>>(), vec![6, 4, 2]);
+    }
+
+    #[test]
+    fn counts_up_to_u32_max_without_overflowing() {
+        let top: Vec<u32> = Counter::with_range(u32::MAX - 1, u32::MAX, 3).collect();
+        assert_eq!(top, vec![u32::MAX - 1]);
+
+        let mut all = Counter::with_range(0, u32::MAX, 1);
+        assert_eq!(all.size_hint().1, usize::try_from(1u64 << 32).ok());
+        assert_eq!(all.next_back(), Some(u32::MAX));
+        assert_eq!(all.next(), Some(0));
+        assert_eq!(all.next_back(), Some(u32::MAX - 1));
+        assert_eq!(all.len(), u32::MAX as usize - 2);
+    }
+
+    /* The adapters from adapters.rs work on Counter like the built in ones */
+    #[test]
+    fn using_custom_adapters() {
+        let chunks: Vec<Vec<u32>> = Counter::new(0).chunks_exact(2).collect();
+        assert_eq!(chunks, vec![vec![1, 2], vec![3, 4]]);
+
+        let sum: u32 = Counter::new(0)
+            .windows(2)
+            .map(|w| w[0] * w[1])
+            .filter(|x| x % 3 == 0)
+            .sum();
+        assert_eq!(sum, 18);
+
+        let mixed: Vec<u32> = Counter::with_range(2, 6, 2)
+            .interleave(Counter::with_range(10, 30, 10))
+            .collect();
+        assert_eq!(mixed, vec![2, 10, 4, 20, 6, 30]);
+
+        let picked: Vec<u32> = Counter::with_range(1, 20, 1)
+            .step_by_with_offset(2, 5)
+            .rev()
+            .collect();
+        assert_eq!(picked, vec![18, 13, 8, 3]);
+    }
+
+    /* Same pipeline as using_default_iterator_trait_methods, split across threads */
+    #[test]
+    fn parallel_pipeline_matches_sequential() {
+        let sequential: u32 = Counter::new(0)
+            .zip(Counter::new(0).skip(1))
+            .map(|(a, b)| a * b)
+            .filter(|x| x % 3 == 0)
+            .sum();
+
+        let parallel: u32 = (1..5u32)
+            .into_par_iter()
+            .with_threads(2)
+            .map(|a| a * (a + 1))
+            .filter(|x| x % 3 == 0)
+            .sum();
+
+        assert_eq!(parallel, sequential);
+    }
+}
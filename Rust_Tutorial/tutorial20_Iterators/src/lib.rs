@@ -0,0 +1,6 @@
+pub mod adapters;
+pub mod counter;
+pub mod parallel;
+pub mod sources;
+pub mod scheduler;
+pub mod units;
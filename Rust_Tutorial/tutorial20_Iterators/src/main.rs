@@ -1,4 +1,4 @@
-use std::iter::Iterator;
+use tutorial20_iterators::counter::Counter;
 
 fn main() {
     println!("Hello, world!");
@@ -10,18 +10,36 @@ fn main() {
     for val in v1_iter {
         println!("Got: {}", val);
     }
+
+    /*Creating your own iterator, Counter lives in src/counter.rs */
+    for even in Counter::with_range(0, 10, 2) {
+        println!("Even: {}", even);
+    }
     
     
-    /*type keyword */
-    type Kilometers = i32;
-    let x: i32 = 5;
-    let y: Kilometers = 5;
+    /*type keyword: `type Kilometers = i32` is just another i32, the Length
+    newtype from src/units.rs is its own type */
+    use tutorial20_iterators::units::{Length, Time};
+    let x = Length::km(5.0);
+    let y: Length = "5 km".parse().unwrap();
     println!("x + y = {}", x + y);
 
-    type Thunk = Box<dyn Fn() + Send + 'static>;
+    // newtypes catch what an alias lets through, `x + 5.0` doesn't compile
+    let run: Length = "12.5 km".parse().unwrap();
+    let pace = run / Time::minutes(75.0);
+    println!("{} in 75 min is {:.1}", run, pace);
+
+    use tutorial20_iterators::scheduler::Thunk;
 
     let f: Thunk = Box::new(|| println!("hi"));
 
+    // a Thunk is exactly what the scheduler runs
+    let scheduler = tutorial20_iterators::scheduler::Scheduler::new();
+    scheduler.once("say hi", f);
+    for report in scheduler.run_pending() {
+        println!("{}: {:?}", report.name, report.outcome);
+    }
+
  
     
 }
@@ -78,56 +96,3 @@ fn mapping(){
 
     assert_eq!(v2, vec![2, 3, 4]);
 }
-
-/*Creating your own iterator */
-struct Counter{
-    count: u32,
-}
-
-impl Counter{
-    fn new(count: u32) -> Counter{
-        Counter { count }
-    }
-}
-
-impl Iterator for Counter{
-    type Item = u32;
-
-    fn next(&mut self) -> Option<Self::Item> {
-        if self.count < 5 {
-            self.count += 1;
-            Some(self.count)
-        }
-        else{
-            None
-        }
-    }
-}
-
-#[test]
-fn calling_next_test(){
-    let mut counter = Counter::new(0 as u32);
-
-    assert_eq!(counter.next(), Some(1));
-    assert_eq!(counter.next(), Some(2));
-    println!("counter {}", counter.count);
-    assert_eq!(counter.next(), Some(3));
-    assert_eq!(counter.next(), Some(4));
-    match counter.next() {
-        Some(val) => println!("counter {}", val),
-        None => println!("counter is NONE"),
-    }
-    assert_eq!(counter.next(), None);
-
-}
-
-#[test]
-fn using_defualt_iterator_trait_methods(){
-    let sum: u32 = Counter::new(0 as u32)
-        .zip(Counter::new(0 as u32).skip(1))
-        .map(|(a, b)| a * b)
-        .filter(|x| x % 3 == 0)
-        .sum();
-
-    assert_eq!(sum, 18);
-}
\ No newline at end of file
@@ -0,0 +1,426 @@
+use std::collections::VecDeque;
+use std::iter::Sum;
+use std::ops::Range;
+use std::sync::Mutex;
+use std::thread;
+
+/* A small parallel iterator on top of std::thread.
+map/filter only describe the work, nothing runs until reduce/sum/collect/count,
+which split the source into chunks, run them on a pool of scoped threads,
+then combine the chunk results in the original order */
+
+/// Something that can be cut into pieces by position, like a Vec or a range
+pub trait Source: Sized + Send {
+    type Item: Send;
+    type Iter: Iterator<Item = Self::Item>;
+
+    fn len(&self) -> usize;
+    /// First `index` items on the left, the rest on the right
+    fn split_at(self, index: usize) -> (Self, Self);
+    fn into_seq(self) -> Self::Iter;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<T: Send> Source for Vec<T> {
+    type Item = T;
+    type Iter = std::vec::IntoIter<T>;
+
+    fn len(&self) -> usize {
+        Vec::len(self)
+    }
+
+    fn split_at(mut self, index: usize) -> (Vec<T>, Vec<T>) {
+        let right = self.split_off(index);
+        (self, right)
+    }
+
+    fn into_seq(self) -> std::vec::IntoIter<T> {
+        self.into_iter()
+    }
+}
+
+impl<'a, T: Sync> Source for &'a [T] {
+    type Item = &'a T;
+    type Iter = std::slice::Iter<'a, T>;
+
+    fn len(&self) -> usize {
+        <[T]>::len(self)
+    }
+
+    fn split_at(self, index: usize) -> (&'a [T], &'a [T]) {
+        <[T]>::split_at(self, index)
+    }
+
+    fn into_seq(self) -> std::slice::Iter<'a, T> {
+        self.iter()
+    }
+}
+
+/* Lengths and split points are worked out in i128, which holds every value of
+every type below, so -100i8..100 doesn't overflow on the way */
+macro_rules! range_source {
+    ($($t:ty),*) => {
+        $(
+            impl Source for Range<$t> {
+                type Item = $t;
+                type Iter = Range<$t>;
+
+                fn len(&self) -> usize {
+                    let len = (self.end as i128 - self.start as i128).max(0);
+                    usize::try_from(len).expect("range is too long to split")
+                }
+
+                fn split_at(self, index: usize) -> (Range<$t>, Range<$t>) {
+                    let mid = <$t>::try_from(self.start as i128 + index as i128)
+                        .expect("split index is inside the range");
+                    (self.start..mid, mid..self.end)
+                }
+
+                fn into_seq(self) -> Range<$t> {
+                    self
+                }
+            }
+        )*
+    };
+}
+
+range_source!(u8, u16, u32, u64, usize, i8, i16, i32, i64, isize);
+
+/* The recorded map/filter steps, applied to each chunk's sequential iterator */
+
+pub trait Pipeline<In>: Sync {
+    type Out: Send;
+
+    fn run<I: Iterator<Item = In>>(&self, iter: I) -> impl Iterator<Item = Self::Out>;
+}
+
+pub struct Identity;
+
+impl<In: Send> Pipeline<In> for Identity {
+    type Out = In;
+
+    fn run<I: Iterator<Item = In>>(&self, iter: I) -> impl Iterator<Item = In> {
+        iter
+    }
+}
+
+pub struct MapStep<P, F> {
+    inner: P,
+    f: F,
+}
+
+impl<In, P, F, R> Pipeline<In> for MapStep<P, F>
+where
+    P: Pipeline<In>,
+    F: Fn(P::Out) -> R + Sync,
+    R: Send,
+{
+    type Out = R;
+
+    fn run<I: Iterator<Item = In>>(&self, iter: I) -> impl Iterator<Item = R> {
+        self.inner.run(iter).map(&self.f)
+    }
+}
+
+pub struct FilterStep<P, F> {
+    inner: P,
+    f: F,
+}
+
+impl<In, P, F> Pipeline<In> for FilterStep<P, F>
+where
+    P: Pipeline<In>,
+    F: Fn(&P::Out) -> bool + Sync,
+{
+    type Out = P::Out;
+
+    fn run<I: Iterator<Item = In>>(&self, iter: I) -> impl Iterator<Item = P::Out> {
+        self.inner.run(iter).filter(&self.f)
+    }
+}
+
+/* Entry point: `(0..100).into_par_iter()`, `vec.into_par_iter()`, `slice.into_par_iter()` */
+
+pub trait IntoParIter: Source {
+    fn into_par_iter(self) -> ParIter<Self, Identity> {
+        ParIter {
+            source: self,
+            pipeline: Identity,
+            threads: default_threads(),
+            min_chunk: 1,
+        }
+    }
+}
+
+impl<S: Source> IntoParIter for S {}
+
+fn default_threads() -> usize {
+    thread::available_parallelism().map_or(1, |n| n.get())
+}
+
+pub struct ParIter<S, P> {
+    source: S,
+    pipeline: P,
+    threads: usize,
+    min_chunk: usize,
+}
+
+impl<S, P> ParIter<S, P>
+where
+    S: Source,
+    P: Pipeline<S::Item>,
+{
+    /// Size of the worker pool, defaults to the number of cores
+    pub fn with_threads(mut self, threads: usize) -> ParIter<S, P> {
+        self.threads = threads.max(1);
+        self
+    }
+
+    /// Chunks are never made smaller than this, so tiny inputs don't pay for threads
+    pub fn with_min_chunk(mut self, min_chunk: usize) -> ParIter<S, P> {
+        self.min_chunk = min_chunk.max(1);
+        self
+    }
+
+    pub fn map<F, R>(self, f: F) -> ParIter<S, MapStep<P, F>>
+    where
+        F: Fn(P::Out) -> R + Sync,
+        R: Send,
+    {
+        ParIter {
+            source: self.source,
+            pipeline: MapStep {
+                inner: self.pipeline,
+                f,
+            },
+            threads: self.threads,
+            min_chunk: self.min_chunk,
+        }
+    }
+
+    pub fn filter<F>(self, f: F) -> ParIter<S, FilterStep<P, F>>
+    where
+        F: Fn(&P::Out) -> bool + Sync,
+    {
+        ParIter {
+            source: self.source,
+            pipeline: FilterStep {
+                inner: self.pipeline,
+                f,
+            },
+            threads: self.threads,
+            min_chunk: self.min_chunk,
+        }
+    }
+
+    /// Folds each chunk starting from `identity()`, then folds the chunk results
+    /// left to right. `op` must be associative, it doesn't need to be commutative
+    pub fn reduce<ID, OP>(self, identity: ID, op: OP) -> P::Out
+    where
+        ID: Fn() -> P::Out + Sync,
+        OP: Fn(P::Out, P::Out) -> P::Out + Sync,
+    {
+        let partials = self.execute(|items| items.fold(identity(), &op));
+        partials.into_iter().fold(identity(), &op)
+    }
+
+    pub fn sum<T>(self) -> T
+    where
+        T: Sum<P::Out> + Sum<T> + Send,
+    {
+        self.execute(|items| items.sum::<T>()).into_iter().sum()
+    }
+
+    pub fn count(self) -> usize {
+        self.execute(|items| items.count()).into_iter().sum()
+    }
+
+    /// Items come back in the same order the sequential version would give
+    pub fn collect_vec(self) -> Vec<P::Out> {
+        self.execute(|items| items.collect::<Vec<_>>())
+            .into_iter()
+            .flatten()
+            .collect()
+    }
+
+    /* Runs `per_chunk` on every chunk and returns the results in chunk order */
+    fn execute<R, F>(self, per_chunk: F) -> Vec<R>
+    where
+        R: Send,
+        F: for<'a> Fn(Box<dyn Iterator<Item = P::Out> + 'a>) -> R + Sync,
+    {
+        let ParIter {
+            source,
+            pipeline,
+            threads,
+            min_chunk,
+        } = self;
+
+        // a few chunks per thread so one slow chunk doesn't hold up the rest
+        let wanted = (threads * 4).min(source.len() / min_chunk).max(1);
+        let chunks = split(source, wanted);
+        let run = |chunk: S| per_chunk(Box::new(pipeline.run(chunk.into_seq())));
+
+        if threads == 1 || chunks.len() == 1 {
+            return chunks.into_iter().map(run).collect();
+        }
+
+        let count = chunks.len();
+        let jobs = Mutex::new(chunks.into_iter().enumerate().collect::<VecDeque<_>>());
+        let results = Mutex::new((0..count).map(|_| None).collect::<Vec<Option<R>>>());
+
+        thread::scope(|s| {
+            for _ in 0..threads.min(count) {
+                s.spawn(|| loop {
+                    let job = jobs.lock().unwrap().pop_front();
+                    let (index, chunk) = match job {
+                        Some(job) => job,
+                        None => break,
+                    };
+                    let result = run(chunk);
+                    results.lock().unwrap()[index] = Some(result);
+                });
+            }
+        });
+
+        results
+            .into_inner()
+            .unwrap()
+            .into_iter()
+            .map(|result| result.expect("every chunk ran"))
+            .collect()
+    }
+}
+
+/* Cuts the source into `pieces` nearly equal chunks, in order */
+fn split<S: Source>(mut source: S, pieces: usize) -> Vec<S> {
+    let len = source.len();
+    let mut chunks = Vec::with_capacity(pieces);
+
+    // split from the back so a Vec only ever moves the part being cut off
+    for piece in (1..pieces).rev() {
+        // len * piece overflows a usize for sources near usize::MAX long
+        let start = (len as u128 * piece as u128 / pieces as u128) as usize;
+        let (front, back) = source.split_at(start);
+        chunks.push(back);
+        source = front;
+    }
+    chunks.push(source);
+    chunks.reverse();
+    chunks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::panic::{self, AssertUnwindSafe};
+
+    #[test]
+    fn split_keeps_every_item_in_order() {
+        let chunks = split((0..10u32).collect::<Vec<_>>(), 3);
+        assert_eq!(chunks, vec![vec![0, 1, 2], vec![3, 4, 5], vec![6, 7, 8, 9]]);
+
+        let chunks = split(5..8usize, 5);
+        let lens: Vec<_> = chunks.iter().map(Source::len).collect();
+        assert_eq!(lens.iter().sum::<usize>(), 3);
+    }
+
+    #[test]
+    fn signed_ranges_wider_than_the_type_max() {
+        let sum: i64 = (-100i8..100).into_par_iter().map(i64::from).sum();
+        assert_eq!(sum, -100);
+        for threads in [1, 3, 8] {
+            let sum: i64 = (i8::MIN..i8::MAX)
+                .into_par_iter()
+                .with_threads(threads)
+                .map(i64::from)
+                .sum();
+            assert_eq!(sum, (i8::MIN..i8::MAX).map(i64::from).sum::<i64>());
+        }
+
+        let full = i64::MIN..i64::MAX;
+        assert_eq!(Source::len(&full), u64::MAX as usize);
+        let (left, right) = full.split_at(1 << 63);
+        assert_eq!((left, right), (i64::MIN..0, 0..i64::MAX));
+        let (start, end) = (5i32, -5);
+        assert_eq!(Source::len(&(start..end)), 0);
+    }
+
+    #[test]
+    fn full_width_ranges_split_across_threads() {
+        // far too long to run through, so each chunk stops at its first item,
+        // which is where the split put that chunk's start
+        let starts = Mutex::new(Vec::new());
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            (0..u64::MAX)
+                .into_par_iter()
+                .with_threads(2)
+                .map(|x| {
+                    starts.lock().unwrap().push(x);
+                    panic!("stopping at {}", x)
+                })
+                .count()
+        }));
+        assert!(result.is_err());
+
+        let bounds: Vec<u64> = (0..8u128)
+            .map(|piece| (u64::MAX as u128 * piece / 8) as u64)
+            .collect();
+        let starts = starts.into_inner().unwrap();
+        assert_eq!(starts.len(), 2);
+        assert!(starts.iter().all(|start| bounds.contains(start)));
+    }
+
+    #[test]
+    fn same_answers_as_the_sequential_pipeline() {
+        let sequential: u64 = (0..100_000u64)
+            .map(|x| x * x % 7919)
+            .filter(|x| x % 3 == 0)
+            .sum();
+
+        for threads in [1, 2, 3, 8] {
+            let parallel: u64 = (0..100_000u64)
+                .into_par_iter()
+                .with_threads(threads)
+                .map(|x| x * x % 7919)
+                .filter(|x| x % 3 == 0)
+                .sum();
+            assert_eq!(parallel, sequential, "{} threads", threads);
+        }
+    }
+
+    #[test]
+    fn collect_and_reduce_keep_the_order() {
+        let words: Vec<String> = (0..200).map(|n| n.to_string()).collect();
+
+        let sequential: Vec<String> = words.iter().map(|w| format!("<{}>", w)).collect();
+        let parallel = words
+            .clone()
+            .into_par_iter()
+            .with_threads(4)
+            .map(|w| format!("<{}>", w))
+            .collect_vec();
+        assert_eq!(parallel, sequential);
+
+        // string concatenation is associative but not commutative
+        let joined = words
+            .as_slice()
+            .into_par_iter()
+            .with_threads(4)
+            .map(|w| w.clone())
+            .reduce(String::new, |a, b| a + &b);
+        assert_eq!(joined, words.concat());
+    }
+
+    #[test]
+    fn count_and_empty_sources() {
+        let evens = (0..1001i64).into_par_iter().filter(|x| x % 2 == 0).count();
+        assert_eq!(evens, 501);
+
+        assert_eq!((5..5u32).into_par_iter().sum::<u32>(), 0);
+        assert!(Vec::<u8>::new().into_par_iter().collect_vec().is_empty());
+    }
+}
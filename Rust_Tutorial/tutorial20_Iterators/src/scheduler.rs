@@ -0,0 +1,438 @@
+use std::any::Any;
+use std::collections::VecDeque;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/* Job scheduler that finally runs the Thunk type alias from main */
+
+pub type Thunk = Box<dyn Fn() + Send + 'static>;
+
+/* Injectable time, so tests can move the clock by hand */
+
+pub trait Clock: Send + Sync {
+    /// Time since some fixed starting point
+    fn now(&self) -> Duration;
+}
+
+pub struct SystemClock(Instant);
+
+impl SystemClock {
+    pub fn new() -> SystemClock {
+        SystemClock(Instant::now())
+    }
+}
+
+impl Default for SystemClock {
+    fn default() -> SystemClock {
+        SystemClock::new()
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> Duration {
+        self.0.elapsed()
+    }
+}
+
+/// Only moves when told to
+#[derive(Default)]
+pub struct ManualClock(AtomicU64);
+
+impl ManualClock {
+    pub fn new() -> ManualClock {
+        ManualClock::default()
+    }
+
+    pub fn advance(&self, by: Duration) {
+        self.0.fetch_add(by.as_nanos() as u64, Ordering::SeqCst);
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Duration {
+        Duration::from_nanos(self.0.load(Ordering::SeqCst))
+    }
+}
+
+/* Jobs */
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    Finished,
+    Panicked(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Report {
+    pub name: String,
+    /// When the job was due, on the scheduler's clock
+    pub due: Duration,
+    pub outcome: Outcome,
+}
+
+/// Cancels the job it came from. Cancelling a job that is running lets that run
+/// finish, but it is never started again
+#[derive(Clone)]
+pub struct JobHandle {
+    name: Arc<str>,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl JobHandle {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+}
+
+/* A delay too long to add to the clock makes a job due at NEVER, which no clock
+reaches. It stays pending until cancelled */
+const NEVER: Duration = Duration::MAX;
+
+struct Job {
+    id: u64,
+    name: Arc<str>,
+    thunk: Thunk,
+    due: Duration,
+    period: Option<Duration>,
+    cancelled: Arc<AtomicBool>,
+}
+
+pub struct Scheduler {
+    clock: Arc<dyn Clock>,
+    jobs: Mutex<Vec<Job>>,
+    next_id: AtomicU64,
+    max_concurrency: usize,
+}
+
+impl Scheduler {
+    /// Runs on the real clock, one job at a time
+    pub fn new() -> Scheduler {
+        Scheduler::with_clock(Arc::new(SystemClock::new()))
+    }
+
+    pub fn with_clock(clock: Arc<dyn Clock>) -> Scheduler {
+        Scheduler {
+            clock,
+            jobs: Mutex::new(Vec::new()),
+            next_id: AtomicU64::new(0),
+            max_concurrency: 1,
+        }
+    }
+
+    /// How many due jobs may run at the same time
+    pub fn max_concurrency(mut self, jobs: usize) -> Scheduler {
+        self.max_concurrency = jobs.max(1);
+        self
+    }
+
+    /// Runs on the next `run_pending`
+    pub fn once(&self, name: &str, thunk: Thunk) -> JobHandle {
+        self.add(name, thunk, Duration::ZERO, None)
+    }
+
+    pub fn after(&self, name: &str, delay: Duration, thunk: Thunk) -> JobHandle {
+        self.add(name, thunk, delay, None)
+    }
+
+    /// Fixed rate: due every `period` from now, measured from when each run was
+    /// due rather than when it finished. Runs missed while nobody called
+    /// `run_pending` are skipped instead of run back to back
+    pub fn every(&self, name: &str, period: Duration, thunk: Thunk) -> JobHandle {
+        assert!(!period.is_zero(), "period must be longer than zero");
+        self.add(name, thunk, period, Some(period))
+    }
+
+    fn add(
+        &self,
+        name: &str,
+        thunk: Thunk,
+        delay: Duration,
+        period: Option<Duration>,
+    ) -> JobHandle {
+        let handle = JobHandle {
+            name: Arc::from(name),
+            cancelled: Arc::new(AtomicBool::new(false)),
+        };
+        let job = Job {
+            id: self.next_id.fetch_add(1, Ordering::SeqCst),
+            name: Arc::clone(&handle.name),
+            thunk,
+            due: self.clock.now().checked_add(delay).unwrap_or(NEVER),
+            period,
+            cancelled: Arc::clone(&handle.cancelled),
+        };
+        self.jobs.lock().unwrap().push(job);
+        handle
+    }
+
+    /// Names of the jobs still waiting to run
+    pub fn pending(&self) -> Vec<String> {
+        let mut jobs = self.jobs.lock().unwrap();
+        jobs.retain(|job| !job.cancelled.load(Ordering::SeqCst));
+        jobs.iter().map(|job| job.name.to_string()).collect()
+    }
+
+    /// When the earliest job is due, `None` when nothing is scheduled or
+    /// nothing will ever be due
+    pub fn next_due(&self) -> Option<Duration> {
+        let jobs = self.jobs.lock().unwrap();
+        jobs.iter()
+            .filter(|job| !job.cancelled.load(Ordering::SeqCst))
+            .map(|job| job.due)
+            .filter(|&due| due != NEVER)
+            .min()
+    }
+
+    /// Runs every job that is due now, in due order, on up to `max_concurrency`
+    /// threads. A panicking job is reported and doesn't affect the others
+    pub fn run_pending(&self) -> Vec<Report> {
+        let now = self.clock.now();
+
+        let mut due: Vec<Job> = {
+            let mut jobs = self.jobs.lock().unwrap();
+            jobs.retain(|job| !job.cancelled.load(Ordering::SeqCst));
+            let (due, later) = jobs.drain(..).partition(|job| job.due <= now);
+            *jobs = later;
+            due
+        };
+        due.sort_by_key(|job| (job.due, job.id));
+
+        let count = due.len();
+        let queue = Mutex::new(due.into_iter().enumerate().collect::<VecDeque<_>>());
+        let finished = Mutex::new(Vec::with_capacity(count));
+
+        thread::scope(|s| {
+            for _ in 0..self.max_concurrency.min(count) {
+                s.spawn(|| loop {
+                    let next = queue.lock().unwrap().pop_front();
+                    let (order, job) = match next {
+                        Some(next) => next,
+                        None => break,
+                    };
+                    let outcome = match panic::catch_unwind(AssertUnwindSafe(|| (job.thunk)())) {
+                        Ok(()) => Outcome::Finished,
+                        Err(payload) => Outcome::Panicked(panic_message(&*payload)),
+                    };
+                    finished.lock().unwrap().push((order, job, outcome));
+                });
+            }
+        });
+
+        let mut finished = finished.into_inner().unwrap();
+        finished.sort_by_key(|(order, _, _)| *order);
+
+        let mut reports = Vec::with_capacity(count);
+        let mut jobs = self.jobs.lock().unwrap();
+        for (_, mut job, outcome) in finished {
+            reports.push(Report {
+                name: job.name.to_string(),
+                due: job.due,
+                outcome,
+            });
+
+            if let Some(period) = job.period {
+                if !job.cancelled.load(Ordering::SeqCst) {
+                    // more than u32::MAX periods behind takes more than one step
+                    while job.due <= now {
+                        let behind = (now - job.due).as_nanos() / period.as_nanos();
+                        let skip = u32::try_from(behind + 1).unwrap_or(u32::MAX);
+                        job.due = period
+                            .checked_mul(skip)
+                            .and_then(|step| job.due.checked_add(step))
+                            .unwrap_or(NEVER);
+                    }
+                    jobs.push(job);
+                }
+            }
+        }
+        reports
+    }
+
+    /// Keeps running jobs until `deadline` on the scheduler's clock, calling `sleep`
+    /// to wait for the next one
+    pub fn run_until(&self, deadline: Duration, sleep: impl Fn(Duration)) -> Vec<Report> {
+        let mut reports = Vec::new();
+        loop {
+            reports.extend(self.run_pending());
+            let now = self.clock.now();
+            match self.next_due() {
+                Some(due) if due <= deadline => sleep(due.saturating_sub(now)),
+                _ => return reports,
+            }
+        }
+    }
+}
+
+impl Default for Scheduler {
+    fn default() -> Scheduler {
+        Scheduler::new()
+    }
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(s) = payload.downcast_ref::<&str>() {
+        s.to_string()
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        s.clone()
+    } else {
+        "<non-string panic payload>".to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+
+    fn counter() -> (Arc<AtomicUsize>, Thunk) {
+        let count = Arc::new(AtomicUsize::new(0));
+        let inner = Arc::clone(&count);
+        (
+            count,
+            Box::new(move || {
+                inner.fetch_add(1, Ordering::SeqCst);
+            }),
+        )
+    }
+
+    fn setup() -> (Arc<ManualClock>, Scheduler) {
+        let clock = Arc::new(ManualClock::new());
+        let scheduler = Scheduler::with_clock(clock.clone());
+        (clock, scheduler)
+    }
+
+    #[test]
+    fn one_shot_and_delayed_jobs_run_once() {
+        let (clock, scheduler) = setup();
+        let (now_count, now_job) = counter();
+        let (later_count, later_job) = counter();
+
+        scheduler.once("now", now_job);
+        scheduler.after("later", Duration::from_secs(5), later_job);
+
+        let reports = scheduler.run_pending();
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].name, "now");
+        assert_eq!(scheduler.pending(), vec!["later"]);
+
+        clock.advance(Duration::from_secs(5));
+        scheduler.run_pending();
+        scheduler.run_pending();
+        assert_eq!(now_count.load(Ordering::SeqCst), 1);
+        assert_eq!(later_count.load(Ordering::SeqCst), 1);
+        assert_eq!(scheduler.next_due(), None);
+    }
+
+    #[test]
+    fn periodic_jobs_keep_a_fixed_rate_and_can_be_cancelled() {
+        let (clock, scheduler) = setup();
+        let (count, job) = counter();
+        let handle = scheduler.every("tick", Duration::from_secs(10), job);
+
+        let reports = scheduler.run_until(Duration::from_secs(35), |d| clock.advance(d));
+        let dues: Vec<_> = reports.iter().map(|r| r.due.as_secs()).collect();
+        assert_eq!(dues, vec![10, 20, 30]);
+
+        // at 57s the 40s run is late, 50s is skipped and 60s is next
+        clock.advance(Duration::from_secs(27));
+        assert_eq!(scheduler.run_pending().len(), 1);
+        assert_eq!(scheduler.next_due(), Some(Duration::from_secs(60)));
+
+        handle.cancel();
+        clock.advance(Duration::from_secs(100));
+        assert!(scheduler.run_pending().is_empty());
+        assert_eq!(count.load(Ordering::SeqCst), 4);
+    }
+
+    #[test]
+    fn catching_up_past_u32_max_periods_lands_after_now() {
+        let (clock, scheduler) = setup();
+        let (count, job) = counter();
+        scheduler.every("fast", Duration::from_nanos(1), job);
+
+        // 10^10 periods behind, too many to count in a u32
+        clock.advance(Duration::from_secs(10));
+        assert_eq!(scheduler.run_pending().len(), 1);
+        assert_eq!(
+            scheduler.next_due(),
+            Some(Duration::from_secs(10) + Duration::from_nanos(1))
+        );
+        assert!(scheduler.run_pending().is_empty());
+        assert_eq!(count.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn delays_past_the_end_of_time_are_never_due() {
+        let (clock, scheduler) = setup();
+        let (count, job) = counter();
+        scheduler.after("far", Duration::MAX, job);
+        let (_, job) = counter();
+        let handle = scheduler.every("rare", Duration::MAX, job);
+
+        assert_eq!(scheduler.next_due(), None);
+        clock.advance(Duration::from_secs(u32::MAX as u64));
+        assert!(scheduler.run_pending().is_empty());
+        assert_eq!(scheduler.pending(), vec!["far", "rare"]);
+
+        handle.cancel();
+        assert_eq!(scheduler.pending(), vec!["far"]);
+        assert_eq!(count.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn panics_are_reported_per_job() {
+        let (_, scheduler) = setup();
+        let (count, job) = counter();
+        scheduler.once("bad", Box::new(|| panic!("job blew up")));
+        scheduler.once("good", job);
+
+        let reports = scheduler.run_pending();
+        assert_eq!(
+            reports[0].outcome,
+            Outcome::Panicked("job blew up".to_string())
+        );
+        assert_eq!(reports[1].outcome, Outcome::Finished);
+        assert_eq!(count.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn never_runs_more_than_max_concurrency_jobs() {
+        let clock = Arc::new(ManualClock::new());
+        let scheduler = Scheduler::with_clock(clock).max_concurrency(2);
+        let running = Arc::new(AtomicUsize::new(0));
+        let peak = Arc::new(AtomicUsize::new(0));
+
+        for n in 0..6 {
+            let running = Arc::clone(&running);
+            let peak = Arc::clone(&peak);
+            scheduler.once(
+                &format!("job {}", n),
+                Box::new(move || {
+                    let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                    peak.fetch_max(now, Ordering::SeqCst);
+                    thread::sleep(Duration::from_millis(20));
+                    running.fetch_sub(1, Ordering::SeqCst);
+                }),
+            );
+        }
+
+        let reports = scheduler.run_pending();
+        let names: Vec<_> = reports.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(
+            names,
+            ["job 0", "job 1", "job 2", "job 3", "job 4", "job 5"]
+        );
+        assert!(peak.load(Ordering::SeqCst) <= 2);
+    }
+}
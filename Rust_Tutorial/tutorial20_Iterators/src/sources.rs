@@ -0,0 +1,333 @@
+use std::fs::{self, File, ReadDir};
+use std::io::{self, BufRead, BufReader, ErrorKind, Read};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::time::Duration;
+
+/* Iterator sources that stream instead of starting from an in-memory vec! */
+
+/* Lines of a file */
+
+pub struct Lines<R> {
+    reader: R,
+    done: bool,
+}
+
+/// Reads `path` one line at a time, without the trailing `\n` or `\r\n`
+pub fn read_lines(path: impl AsRef<Path>) -> io::Result<Lines<BufReader<File>>> {
+    Ok(lines(BufReader::new(File::open(path)?)))
+}
+
+pub fn lines<R: BufRead>(reader: R) -> Lines<R> {
+    Lines {
+        reader,
+        done: false,
+    }
+}
+
+impl<R: BufRead> Iterator for Lines<R> {
+    type Item = io::Result<String>;
+
+    /// Unlike `BufRead::lines`, stops after handing out the first error
+    fn next(&mut self) -> Option<io::Result<String>> {
+        if self.done {
+            return None;
+        }
+
+        let mut line = String::new();
+        match self.reader.read_line(&mut line) {
+            Ok(0) => {
+                self.done = true;
+                None
+            }
+            Ok(_) => {
+                if line.ends_with('\n') {
+                    line.pop();
+                    if line.ends_with('\r') {
+                        line.pop();
+                    }
+                }
+                Some(Ok(line))
+            }
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+/* Fixed-size records */
+
+pub struct Records<R> {
+    reader: R,
+    size: usize,
+    done: bool,
+}
+
+/// Chunks of exactly `size` bytes. A short record at the end is an `UnexpectedEof` error
+pub fn records<R: Read>(reader: R, size: usize) -> Records<R> {
+    assert!(size > 0, "record size must be at least 1");
+    Records {
+        reader,
+        size,
+        done: false,
+    }
+}
+
+impl<R: Read> Iterator for Records<R> {
+    type Item = io::Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<io::Result<Vec<u8>>> {
+        if self.done {
+            return None;
+        }
+
+        let mut record = vec![0; self.size];
+        let mut filled = 0;
+        while filled < self.size {
+            match self.reader.read(&mut record[filled..]) {
+                Ok(0) => break,
+                Ok(n) => filled += n,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+
+        if filled == self.size {
+            return Some(Ok(record));
+        }
+        self.done = true;
+        if filled == 0 {
+            None
+        } else {
+            Some(Err(io::Error::new(
+                ErrorKind::UnexpectedEof,
+                format!("last record has {} of {} bytes", filled, self.size),
+            )))
+        }
+    }
+}
+
+/* Directory walking */
+
+pub struct WalkDir {
+    // one entry per directory we are inside of, deepest last
+    stack: Vec<std::vec::IntoIter<PathBuf>>,
+    pending_error: Option<io::Error>,
+}
+
+/// Every file and directory under `root` (not `root` itself), depth first,
+/// sorted by name within a directory. Symlinks are listed but not followed
+pub fn walk_dir(root: impl AsRef<Path>) -> WalkDir {
+    let mut walk = WalkDir {
+        stack: Vec::new(),
+        pending_error: None,
+    };
+    match sorted_entries(fs::read_dir(root)) {
+        Ok(entries) => walk.stack.push(entries.into_iter()),
+        Err(e) => walk.pending_error = Some(e),
+    }
+    walk
+}
+
+fn sorted_entries(dir: io::Result<ReadDir>) -> io::Result<Vec<PathBuf>> {
+    let mut entries = dir?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<io::Result<Vec<_>>>()?;
+    entries.sort();
+    Ok(entries)
+}
+
+impl Iterator for WalkDir {
+    type Item = io::Result<PathBuf>;
+
+    fn next(&mut self) -> Option<io::Result<PathBuf>> {
+        if let Some(e) = self.pending_error.take() {
+            return Some(Err(e));
+        }
+
+        loop {
+            let path = match self.stack.last_mut()?.next() {
+                Some(path) => path,
+                None => {
+                    self.stack.pop();
+                    continue;
+                }
+            };
+
+            // an unreadable sub directory is reported after the directory itself
+            let is_dir = fs::symlink_metadata(&path).map(|m| m.is_dir());
+            match is_dir {
+                Ok(true) => match sorted_entries(fs::read_dir(&path)) {
+                    Ok(entries) => self.stack.push(entries.into_iter()),
+                    Err(e) => self.pending_error = Some(e),
+                },
+                Ok(false) => {}
+                Err(e) => self.pending_error = Some(e),
+            }
+            return Some(Ok(path));
+        }
+    }
+}
+
+/* Draining a channel */
+
+pub struct Drain<'a, T> {
+    receiver: &'a Receiver<T>,
+    timeout: Duration,
+    stopped: Option<RecvTimeoutError>,
+}
+
+/// Yields messages until none arrives within `timeout` or every sender is gone
+pub fn drain<T>(receiver: &Receiver<T>, timeout: Duration) -> Drain<'_, T> {
+    Drain {
+        receiver,
+        timeout,
+        stopped: None,
+    }
+}
+
+impl<T> Drain<'_, T> {
+    /// Why the iterator ended: `Timeout` or `Disconnected`. `None` while it is still going
+    pub fn stopped(&self) -> Option<RecvTimeoutError> {
+        self.stopped
+    }
+}
+
+impl<T> Iterator for Drain<'_, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        if self.stopped.is_some() {
+            return None;
+        }
+        match self.receiver.recv_timeout(self.timeout) {
+            Ok(message) => Some(message),
+            Err(e) => {
+                self.stopped = Some(e);
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adapters::IterExt;
+    use std::env;
+    use std::io::Cursor;
+    use std::process;
+    use std::sync::mpsc;
+    use std::thread;
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("tutorial20_{}_{}", name, process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /* Reader that fails on its second read */
+    struct FailingReader {
+        reads: usize,
+    }
+
+    impl Read for FailingReader {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.reads += 1;
+            if self.reads > 1 {
+                return Err(io::Error::other("disk on fire"));
+            }
+            buf[..6].copy_from_slice(b"first\n");
+            Ok(6)
+        }
+    }
+
+    #[test]
+    fn lines_from_a_file_feed_adapter_chains() {
+        let dir = scratch_dir("lines");
+        let path = dir.join("numbers.txt");
+        fs::write(&path, "1\r\n2\n3\n4\n5").unwrap();
+
+        let sum: u32 = read_lines(&path)
+            .unwrap()
+            .map(|line| line.unwrap().parse::<u32>().unwrap())
+            .chunks_exact(2)
+            .map(|pair| pair[0] * pair[1])
+            .sum();
+        assert_eq!(sum, 2 + 3 * 4);
+
+        assert!(read_lines(dir.join("missing.txt")).is_err());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn lines_stop_after_the_first_error() {
+        let mut it = lines(BufReader::with_capacity(8, FailingReader { reads: 0 }));
+        assert_eq!(it.next().unwrap().unwrap(), "first");
+        assert!(it.next().unwrap().is_err());
+        assert!(it.next().is_none());
+    }
+
+    #[test]
+    fn records_are_exact_and_report_a_short_tail() {
+        let all: Vec<_> = records(Cursor::new(b"aabbcc".to_vec()), 2)
+            .collect::<io::Result<_>>()
+            .unwrap();
+        assert_eq!(all, vec![b"aa".to_vec(), b"bb".to_vec(), b"cc".to_vec()]);
+
+        let mut it = records(Cursor::new(b"aabbc".to_vec()), 2);
+        assert_eq!(it.nth(1).unwrap().unwrap(), b"bb");
+        let err = it.next().unwrap().unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+        assert!(it.next().is_none());
+    }
+
+    #[test]
+    fn walk_dir_is_depth_first_and_sorted() {
+        let dir = scratch_dir("walk");
+        fs::create_dir_all(dir.join("b/inner")).unwrap();
+        fs::write(dir.join("a.txt"), "").unwrap();
+        fs::write(dir.join("b/inner/deep.txt"), "").unwrap();
+        fs::write(dir.join("c.txt"), "").unwrap();
+
+        let found: Vec<_> = walk_dir(&dir)
+            .map(|p| p.unwrap().strip_prefix(&dir).unwrap().to_path_buf())
+            .collect();
+        let expected: Vec<PathBuf> = ["a.txt", "b", "b/inner", "b/inner/deep.txt", "c.txt"]
+            .iter()
+            .map(PathBuf::from)
+            .collect();
+        assert_eq!(found, expected);
+
+        let mut missing = walk_dir(dir.join("nope"));
+        assert!(missing.next().unwrap().is_err());
+        assert!(missing.next().is_none());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn drain_stops_on_timeout_or_disconnect() {
+        let (tx, rx) = mpsc::channel();
+        let sender = thread::spawn(move || {
+            for n in 1..=3 {
+                tx.send(n).unwrap();
+            }
+        });
+
+        let mut it = drain(&rx, Duration::from_secs(5));
+        assert_eq!(it.by_ref().sum::<i32>(), 6);
+        assert_eq!(it.stopped(), Some(RecvTimeoutError::Disconnected));
+        sender.join().unwrap();
+
+        let (_tx, rx) = mpsc::channel::<i32>();
+        let mut it = drain(&rx, Duration::from_millis(10));
+        assert_eq!(it.next(), None);
+        assert_eq!(it.stopped(), Some(RecvTimeoutError::Timeout));
+    }
+}
@@ -0,0 +1,380 @@
+use std::error::Error;
+use std::fmt;
+use std::iter::Sum;
+use std::ops::{Add, Div, Mul, Sub};
+use std::str::FromStr;
+
+/* Units of measure as newtypes instead of `type Kilometers = i32`. A type
+alias is just another name for i32, so kilometres and plain numbers add
+together without complaint. These don't, the compile_fail doctests on
+Length check that mixing them is a compile error */
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseQuantityError {
+    /// Not in the "<number> <unit>" shape
+    Format(String),
+    Number(String),
+    /// The unit exists, just not for this quantity, or not at all
+    Unit {
+        unit: String,
+        expected: &'static [&'static str],
+    },
+}
+
+impl fmt::Display for ParseQuantityError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseQuantityError::Format(s) => {
+                write!(f, "expected \"<number> <unit>\", got {:?}", s)
+            }
+            ParseQuantityError::Number(s) => write!(f, "{:?} is not a number", s),
+            ParseQuantityError::Unit { unit, expected } => {
+                write!(
+                    f,
+                    "unknown unit {:?}, expected one of {}",
+                    unit,
+                    expected.join(", ")
+                )
+            }
+        }
+    }
+}
+
+impl Error for ParseQuantityError {}
+
+/* Each quantity stores its value in one base unit and converts at the edges.
+quantity!(docs Type, UnitEnum, default unit, [Variant, "symbol", per base unit, constructor, getter]...) */
+macro_rules! quantity {
+    ($(#[$doc:meta])* $name:ident, $unit:ident, $default:ident,
+     $([$variant:ident, $symbol:literal, $factor:expr, $ctor:ident, $getter:ident]),+ $(,)?) => {
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub enum $unit {
+            $($variant),+
+        }
+
+        impl $unit {
+            pub fn symbol(self) -> &'static str {
+                match self {
+                    $($unit::$variant => $symbol),+
+                }
+            }
+
+            /// How many base units one of these is
+            fn factor(self) -> f64 {
+                match self {
+                    $($unit::$variant => $factor),+
+                }
+            }
+        }
+
+        impl FromStr for $unit {
+            type Err = ParseQuantityError;
+
+            fn from_str(s: &str) -> Result<$unit, ParseQuantityError> {
+                match s {
+                    $($symbol => Ok($unit::$variant),)+
+                    _ => Err(ParseQuantityError::Unit {
+                        unit: s.to_string(),
+                        expected: &[$($symbol),+],
+                    }),
+                }
+            }
+        }
+
+        $(#[$doc])*
+        #[derive(Debug, Clone, Copy, Default, PartialEq, PartialOrd)]
+        pub struct $name(f64);
+
+        impl $name {
+            pub fn new(value: f64, unit: $unit) -> $name {
+                $name(value * unit.factor())
+            }
+
+            pub fn get(self, unit: $unit) -> f64 {
+                self.0 / unit.factor()
+            }
+
+            /// Like `Display`, but in the unit you ask for
+            pub fn display_in(self, unit: $unit) -> InUnit {
+                InUnit {
+                    value: self.get(unit),
+                    symbol: unit.symbol(),
+                }
+            }
+
+            $(
+                pub fn $ctor(value: f64) -> $name {
+                    $name::new(value, $unit::$variant)
+                }
+
+                pub fn $getter(self) -> f64 {
+                    self.get($unit::$variant)
+                }
+            )+
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                fmt::Display::fmt(&self.display_in($unit::$default), f)
+            }
+        }
+
+        impl FromStr for $name {
+            type Err = ParseQuantityError;
+
+            /* "12.5 km", "12.5km", "1e3 m" and " 12.5  km " all work. The number
+            ends at the first space, or without one at the first char that can't
+            be part of an f64 */
+            fn from_str(s: &str) -> Result<$name, ParseQuantityError> {
+                let s = s.trim();
+                let split = s
+                    .find(char::is_whitespace)
+                    .or_else(|| s.find(|c: char| !(c.is_ascii_digit() || "+-.eE".contains(c))))
+                    .ok_or_else(|| ParseQuantityError::Format(s.to_string()))?;
+                let (number, unit) = s.split_at(split);
+                let number = number.trim();
+                if number.is_empty() {
+                    return Err(ParseQuantityError::Format(s.to_string()));
+                }
+                let value = number
+                    .parse::<f64>()
+                    .map_err(|_| ParseQuantityError::Number(number.to_string()))?;
+                Ok($name::new(value, unit.trim().parse()?))
+            }
+        }
+
+        impl Add for $name {
+            type Output = $name;
+            fn add(self, other: $name) -> $name {
+                $name(self.0 + other.0)
+            }
+        }
+
+        impl Sub for $name {
+            type Output = $name;
+            fn sub(self, other: $name) -> $name {
+                $name(self.0 - other.0)
+            }
+        }
+
+        /// Scaling keeps the unit
+        impl Mul<f64> for $name {
+            type Output = $name;
+            fn mul(self, by: f64) -> $name {
+                $name(self.0 * by)
+            }
+        }
+
+        impl Mul<$name> for f64 {
+            type Output = $name;
+            fn mul(self, quantity: $name) -> $name {
+                $name(self * quantity.0)
+            }
+        }
+
+        impl Div<f64> for $name {
+            type Output = $name;
+            fn div(self, by: f64) -> $name {
+                $name(self.0 / by)
+            }
+        }
+
+        /// The same quantity divided by itself has no unit left
+        impl Div for $name {
+            type Output = f64;
+            fn div(self, other: $name) -> f64 {
+                self.0 / other.0
+            }
+        }
+
+        impl Sum for $name {
+            fn sum<I: Iterator<Item = $name>>(iter: I) -> $name {
+                $name(iter.map(|q| q.0).sum())
+            }
+        }
+    };
+}
+
+quantity!(
+    /// A distance. Only other lengths add to it, not times or bare numbers:
+    ///
+    /// ```compile_fail
+    /// use tutorial20_iterators::units::{Length, Time};
+    /// let nonsense = Length::km(5.0) + Time::hours(1.0);
+    /// ```
+    ///
+    /// ```compile_fail
+    /// use tutorial20_iterators::units::Length;
+    /// let nonsense = Length::km(5.0) + 5.0;
+    /// ```
+    ///
+    /// ```
+    /// use tutorial20_iterators::units::Length;
+    /// assert_eq!(Length::km(5.0) + Length::meters(500.0), Length::km(5.5));
+    /// ```
+    Length,
+    LengthUnit,
+    Kilometers,
+    [Meters, "m", 1.0, meters, in_meters],
+    [Kilometers, "km", 1000.0, km, in_km],
+    [Miles, "mi", 1609.344, miles, in_miles],
+);
+
+quantity!(
+    /// A duration, only adds to other durations
+    Time,
+    TimeUnit,
+    Hours,
+    [Seconds, "s", 1.0, seconds, in_seconds],
+    [Minutes, "min", 60.0, minutes, in_minutes],
+    [Hours, "h", 3600.0, hours, in_hours],
+);
+
+quantity!(
+    /// A mass, only adds to other masses
+    Mass,
+    MassUnit,
+    Kilograms,
+    [Grams, "g", 0.001, grams, in_grams],
+    [Kilograms, "kg", 1.0, kg, in_kg],
+    [Pounds, "lb", 0.453_592_37, pounds, in_pounds],
+);
+
+quantity!(
+    /// A speed, what you get from `Length / Time`
+    Speed,
+    SpeedUnit,
+    KilometersPerHour,
+    [
+        MetersPerSecond,
+        "m/s",
+        1.0,
+        meters_per_second,
+        in_meters_per_second
+    ],
+    [KilometersPerHour, "km/h", 1000.0 / 3600.0, kmh, in_kmh],
+    [MilesPerHour, "mph", 1609.344 / 3600.0, mph, in_mph],
+);
+
+/// A value with the symbol it was converted to, formats as "12.5 km"
+pub struct InUnit {
+    value: f64,
+    symbol: &'static str,
+}
+
+impl fmt::Display for InUnit {
+    /// Precision applies to the number: `format!("{:.1}", x)` gives "12.5 km"
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match f.precision() {
+            Some(p) => write!(f, "{:.*} {}", p, self.value, self.symbol),
+            None => write!(f, "{} {}", self.value, self.symbol),
+        }
+    }
+}
+
+/* The only arithmetic between different quantities: distance = speed * time */
+
+impl Div<Time> for Length {
+    type Output = Speed;
+    fn div(self, time: Time) -> Speed {
+        Speed(self.0 / time.0)
+    }
+}
+
+impl Div<Speed> for Length {
+    type Output = Time;
+    fn div(self, speed: Speed) -> Time {
+        Time(self.0 / speed.0)
+    }
+}
+
+impl Mul<Time> for Speed {
+    type Output = Length;
+    fn mul(self, time: Time) -> Length {
+        Length(self.0 * time.0)
+    }
+}
+
+impl Mul<Speed> for Time {
+    type Output = Length;
+    fn mul(self, speed: Speed) -> Length {
+        Length(self.0 * speed.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    #[test]
+    fn conversions_round_trip() {
+        let marathon = Length::km(42.195);
+        assert!(close(marathon.in_meters(), 42_195.0));
+        assert_eq!((marathon.in_miles() * 1000.0).round(), 26_219.0);
+        assert!(close(Length::miles(1.0).in_km(), 1.609_344));
+        assert!(close(Time::minutes(90.0).in_hours(), 1.5));
+        assert!(close(Mass::pounds(1.0).in_grams(), 453.592_37));
+        assert!(close(Speed::mph(60.0).in_kmh(), 96.560_64));
+    }
+
+    #[test]
+    fn distance_over_time_is_speed() {
+        let speed = Length::km(10.0) / Time::minutes(30.0);
+        assert!(close(speed.in_kmh(), 20.0));
+        assert!(close((speed * Time::hours(2.0)).in_km(), 40.0));
+        assert!(close((Length::km(5.0) / speed).in_minutes(), 15.0));
+
+        let laps: Length = [Length::meters(400.0); 5].into_iter().sum();
+        assert!(close(laps / Length::km(1.0), 2.0));
+        assert!(close((2.0 * laps - Length::km(1.0)).in_meters(), 3000.0));
+    }
+
+    #[test]
+    fn parses_and_formats() {
+        assert_eq!("12.5 km".parse::<Length>().unwrap(), Length::km(12.5));
+        assert_eq!(" 400m ".parse::<Length>().unwrap(), Length::meters(400.0));
+        assert_eq!("30 km/h".parse::<Speed>().unwrap(), Speed::kmh(30.0));
+        assert_eq!("2 lb".parse::<Mass>().unwrap(), Mass::pounds(2.0));
+        assert_eq!("1e3 m".parse::<Length>().unwrap(), Length::km(1.0));
+        assert_eq!("1E-3 km".parse::<Length>().unwrap(), Length::meters(1.0));
+        assert_eq!("2.5e1km".parse::<Length>().unwrap(), Length::km(25.0));
+        assert_eq!("-3 min".parse::<Time>().unwrap(), Time::seconds(-180.0));
+
+        assert_eq!(Length::km(12.5).to_string(), "12.5 km");
+        assert_eq!(format!("{:.1}", Length::miles(3.0)), "4.8 km");
+        assert_eq!(
+            format!("{:.2}", Length::km(10.0).display_in(LengthUnit::Miles)),
+            "6.21 mi"
+        );
+        assert_eq!(Time::minutes(30.0).to_string(), "0.5 h");
+    }
+
+    #[test]
+    fn parse_errors_say_what_is_wrong() {
+        assert_eq!(
+            "12.5".parse::<Length>(),
+            Err(ParseQuantityError::Format("12.5".to_string()))
+        );
+        assert_eq!(
+            "km".parse::<Length>(),
+            Err(ParseQuantityError::Format("km".to_string()))
+        );
+        assert_eq!(
+            "1.2.3 km".parse::<Length>(),
+            Err(ParseQuantityError::Number("1.2.3".to_string()))
+        );
+        assert_eq!(
+            "1e km".parse::<Length>(),
+            Err(ParseQuantityError::Number("1e".to_string()))
+        );
+        let err = "3 kg".parse::<Length>().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "unknown unit \"kg\", expected one of m, km, mi"
+        );
+    }
+}
@@ -0,0 +1,50 @@
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use tutorial22_box_pointers::arena_list::ArenaList;
+use tutorial22_box_pointers::list::List;
+
+/* Box list against arena list, `cargo bench -p tutorial22_Box_Pointers`.
+Prints the best of a few runs, which is steadier than the mean */
+
+const N: u32 = 100_000;
+
+fn bench<R>(name: &str, mut f: impl FnMut() -> R) {
+    let mut best = Duration::MAX;
+    for _ in 0..20 {
+        let start = Instant::now();
+        black_box(f());
+        best = best.min(start.elapsed());
+    }
+    println!("{:<36} {:>12.2?}", name, best);
+}
+
+fn main() {
+    bench("box list: build and drop", || {
+        let mut list = List::new();
+        for n in 0..N {
+            list.push_front(n);
+        }
+    });
+    bench("arena list: build and drop", || {
+        let mut list = ArenaList::new();
+        for n in 0..N {
+            list.push_front(n);
+        }
+    });
+
+    let boxed: List<u32> = (0..N).collect();
+    let arena: ArenaList<u32> = (0..N).collect();
+    bench("box list: sum", || {
+        boxed.iter().map(|&n| n as u64).sum::<u64>()
+    });
+    bench("arena list: sum", || {
+        arena.iter().map(|&n| n as u64).sum::<u64>()
+    });
+
+    let mut reused = ArenaList::with_capacity(N as usize);
+    bench("arena list: refill after clear", || {
+        reused.clear();
+        reused.extend(0..N);
+    });
+}
@@ -0,0 +1,322 @@
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
+use std::ops::{Index, IndexMut};
+
+/* Box, Rc and Weak all allocate every node on its own. An arena keeps all the
+values of one type in a single Vec and hands out small Copy handles instead
+of pointers. Links between nodes are then just Ids, the borrow checker has
+nothing to object to, and freeing the whole structure is one Vec going away.
+
+A removed slot is reused by the next alloc. Every slot counts how often it
+was reused (its generation) and every Id remembers the generation it was
+made for, so an Id to a removed value is caught instead of quietly reading
+whatever lives in that slot now. Ids aren't tied to their arena, using one
+with a different arena is not detected */
+
+pub struct Id<T> {
+    index: u32,
+    generation: u32,
+    _marker: PhantomData<fn() -> T>,
+}
+
+/* Derive would want T: Clone etc, an Id is Copy whatever it points at */
+
+impl<T> Clone for Id<T> {
+    fn clone(&self) -> Id<T> {
+        *self
+    }
+}
+
+impl<T> Copy for Id<T> {}
+
+impl<T> PartialEq for Id<T> {
+    fn eq(&self, other: &Id<T>) -> bool {
+        self.index == other.index && self.generation == other.generation
+    }
+}
+
+impl<T> Eq for Id<T> {}
+
+impl<T> Hash for Id<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.index.hash(state);
+        self.generation.hash(state);
+    }
+}
+
+impl<T> fmt::Debug for Id<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Id({}v{})", self.index, self.generation)
+    }
+}
+
+enum Slot<T> {
+    Occupied {
+        generation: u32,
+        value: T,
+    },
+    Free {
+        generation: u32,
+        next_free: Option<u32>,
+    },
+}
+
+pub struct Arena<T> {
+    slots: Vec<Slot<T>>,
+    free_head: Option<u32>,
+    len: usize,
+}
+
+impl<T> Arena<T> {
+    pub fn new() -> Arena<T> {
+        Arena::with_capacity(0)
+    }
+
+    pub fn with_capacity(capacity: usize) -> Arena<T> {
+        Arena {
+            slots: Vec::with_capacity(capacity),
+            free_head: None,
+            len: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn alloc(&mut self, value: T) -> Id<T> {
+        let id = match self.free_head {
+            Some(index) => {
+                let slot = &mut self.slots[index as usize];
+                let (generation, next_free) = match *slot {
+                    Slot::Free {
+                        generation,
+                        next_free,
+                    } => (generation, next_free),
+                    Slot::Occupied { .. } => unreachable!("the free list only holds free slots"),
+                };
+                *slot = Slot::Occupied { generation, value };
+                self.free_head = next_free;
+                Id::new(index, generation)
+            }
+            None => {
+                let index = u32::try_from(self.slots.len()).expect("arena is full");
+                self.slots.push(Slot::Occupied {
+                    generation: 0,
+                    value,
+                });
+                Id::new(index, 0)
+            }
+        };
+        // only once there is a slot, the expect above may panic
+        self.len += 1;
+        id
+    }
+
+    /// `None` if the value has been removed since the Id was handed out
+    pub fn get(&self, id: Id<T>) -> Option<&T> {
+        match self.slots.get(id.index as usize) {
+            Some(Slot::Occupied { generation, value }) if *generation == id.generation => {
+                Some(value)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn get_mut(&mut self, id: Id<T>) -> Option<&mut T> {
+        match self.slots.get_mut(id.index as usize) {
+            Some(Slot::Occupied { generation, value }) if *generation == id.generation => {
+                Some(value)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn contains(&self, id: Id<T>) -> bool {
+        self.get(id).is_some()
+    }
+
+    /// Takes the value out. The Id, and every copy of it, stops working
+    pub fn remove(&mut self, id: Id<T>) -> Option<T> {
+        if !self.contains(id) {
+            return None;
+        }
+        let freed = Slot::Free {
+            generation: id.generation.wrapping_add(1),
+            next_free: self.free_head,
+        };
+        self.free_head = Some(id.index);
+        self.len -= 1;
+        match std::mem::replace(&mut self.slots[id.index as usize], freed) {
+            Slot::Occupied { value, .. } => Some(value),
+            Slot::Free { .. } => unreachable!("checked by contains"),
+        }
+    }
+
+    /// Frees every value at once. Old Ids all stop working, the memory is
+    /// kept for the next allocations
+    pub fn clear(&mut self) {
+        let mut next_free = None;
+        for (index, slot) in self.slots.iter_mut().enumerate().rev() {
+            let generation = match slot {
+                Slot::Occupied { generation, .. } => generation.wrapping_add(1),
+                Slot::Free { generation, .. } => *generation,
+            };
+            *slot = Slot::Free {
+                generation,
+                next_free,
+            };
+            next_free = Some(index as u32);
+        }
+        self.free_head = next_free;
+        self.len = 0;
+    }
+
+    /// Every live value with its Id, in slot order
+    pub fn iter(&self) -> impl Iterator<Item = (Id<T>, &T)> {
+        self.slots
+            .iter()
+            .enumerate()
+            .filter_map(|(index, slot)| match slot {
+                Slot::Occupied { generation, value } => {
+                    Some((Id::new(index as u32, *generation), value))
+                }
+                Slot::Free { .. } => None,
+            })
+    }
+}
+
+impl<T> Id<T> {
+    fn new(index: u32, generation: u32) -> Id<T> {
+        Id {
+            index,
+            generation,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T> Default for Arena<T> {
+    fn default() -> Arena<T> {
+        Arena::new()
+    }
+}
+
+/// Panics on an Id whose value has been removed, like indexing a Vec out of bounds
+impl<T> Index<Id<T>> for Arena<T> {
+    type Output = T;
+
+    fn index(&self, id: Id<T>) -> &T {
+        match self.get(id) {
+            Some(value) => value,
+            None => panic!("{:?} points at a value that has been removed", id),
+        }
+    }
+}
+
+impl<T> IndexMut<Id<T>> for Arena<T> {
+    fn index_mut(&mut self, id: Id<T>) -> &mut T {
+        match self.get_mut(id) {
+            Some(value) => value,
+            None => panic!("{:?} points at a value that has been removed", id),
+        }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Arena<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    #[test]
+    fn alloc_get_and_index() {
+        let mut arena = Arena::new();
+        let a = arena.alloc("a");
+        let b = arena.alloc("b");
+        assert_ne!(a, b);
+        assert_eq!((arena[a], arena[b]), ("a", "b"));
+        arena[b] = "B";
+        assert_eq!(arena.get(b), Some(&"B"));
+        assert_eq!(arena.len(), 2);
+    }
+
+    #[test]
+    fn removed_ids_are_caught_even_when_the_slot_is_reused() {
+        let mut arena = Arena::new();
+        let old = arena.alloc(1);
+        assert_eq!(arena.remove(old), Some(1));
+        assert_eq!(arena.remove(old), None);
+
+        let new = arena.alloc(2);
+        // same slot, different generation
+        assert_eq!(format!("{:?} {:?}", old, new), "Id(0v0) Id(0v1)");
+        assert_eq!(arena.get(old), None);
+        assert_eq!(arena[new], 2);
+        assert_eq!(arena.len(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "points at a value that has been removed")]
+    fn indexing_with_a_stale_id_panics() {
+        let mut arena = Arena::new();
+        let id = arena.alloc(1);
+        arena.remove(id);
+        let _ = arena[id];
+    }
+
+    #[test]
+    fn clear_frees_everything_and_reuses_the_slots() {
+        let drops = Rc::new(Cell::new(0));
+        struct Counted(Rc<Cell<usize>>);
+        impl Drop for Counted {
+            fn drop(&mut self) {
+                self.0.set(self.0.get() + 1);
+            }
+        }
+
+        let mut arena = Arena::new();
+        let ids: Vec<_> = (0..5)
+            .map(|_| arena.alloc(Counted(Rc::clone(&drops))))
+            .collect();
+        arena.remove(ids[2]);
+        assert_eq!(drops.get(), 1);
+
+        arena.clear();
+        assert_eq!(drops.get(), 5);
+        assert!(arena.is_empty());
+        assert!(ids.iter().all(|&id| !arena.contains(id)));
+
+        // the slots come back in order, without growing the Vec
+        let again: Vec<_> = (0..5)
+            .map(|_| arena.alloc(Counted(Rc::clone(&drops))))
+            .collect();
+        assert_eq!(arena.slots.len(), 5);
+        assert!(again
+            .iter()
+            .zip(&ids)
+            .all(|(new, old)| new.index == old.index));
+        drop(arena);
+        assert_eq!(drops.get(), 10);
+    }
+
+    #[test]
+    fn iter_skips_free_slots() {
+        let mut arena = Arena::new();
+        let ids: Vec<_> = (0..4).map(|n| arena.alloc(n)).collect();
+        arena.remove(ids[1]);
+        let live: Vec<_> = arena.iter().map(|(id, value)| (id, *value)).collect();
+        assert_eq!(live, vec![(ids[0], 0), (ids[2], 2), (ids[3], 3)]);
+    }
+}
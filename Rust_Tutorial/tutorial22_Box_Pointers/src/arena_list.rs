@@ -0,0 +1,253 @@
+use std::fmt;
+use std::iter::FromIterator;
+
+use crate::arena::{Arena, Id};
+
+/* The same Cons list as `list::List`, with its nodes in an Arena instead of
+one Box each. `next` is an Id rather than an owning pointer, so there is no
+recursive drop to work around and keeping the tail around costs nothing,
+which makes push_back and extend O(1). Clearing the list frees every node in
+one go and keeps the memory for the next ones */
+
+struct Node<T> {
+    value: T,
+    next: Option<Id<Node<T>>>,
+}
+
+pub struct ArenaList<T> {
+    nodes: Arena<Node<T>>,
+    head: Option<Id<Node<T>>>,
+    tail: Option<Id<Node<T>>>,
+}
+
+impl<T> ArenaList<T> {
+    pub fn new() -> ArenaList<T> {
+        ArenaList::with_capacity(0)
+    }
+
+    pub fn with_capacity(capacity: usize) -> ArenaList<T> {
+        ArenaList {
+            nodes: Arena::with_capacity(capacity),
+            head: None,
+            tail: None,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    pub fn push_front(&mut self, value: T) {
+        let id = self.nodes.alloc(Node {
+            value,
+            next: self.head,
+        });
+        self.head = Some(id);
+        self.tail.get_or_insert(id);
+    }
+
+    pub fn push_back(&mut self, value: T) {
+        let id = self.nodes.alloc(Node { value, next: None });
+        match self.tail {
+            Some(tail) => self.nodes[tail].next = Some(id),
+            None => self.head = Some(id),
+        }
+        self.tail = Some(id);
+    }
+
+    pub fn pop_front(&mut self) -> Option<T> {
+        let node = self.nodes.remove(self.head?)?;
+        self.head = node.next;
+        if self.head.is_none() {
+            self.tail = None;
+        }
+        Some(node.value)
+    }
+
+    pub fn front(&self) -> Option<&T> {
+        self.head.map(|id| &self.nodes[id].value)
+    }
+
+    pub fn front_mut(&mut self) -> Option<&mut T> {
+        let id = self.head?;
+        Some(&mut self.nodes[id].value)
+    }
+
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter {
+            nodes: &self.nodes,
+            next: self.head,
+            len: self.len(),
+        }
+    }
+
+    /// Turns the links around, no values are moved
+    pub fn reverse(&mut self) {
+        let mut reversed = None;
+        let mut rest = self.head;
+        while let Some(id) = rest {
+            let node = &mut self.nodes[id];
+            rest = node.next;
+            node.next = reversed;
+            reversed = Some(id);
+        }
+        self.tail = self.head;
+        self.head = reversed;
+    }
+
+    /// Drops every value at once, the memory stays allocated for reuse
+    pub fn clear(&mut self) {
+        self.nodes.clear();
+        self.head = None;
+        self.tail = None;
+    }
+}
+
+impl<T> Default for ArenaList<T> {
+    fn default() -> ArenaList<T> {
+        ArenaList::new()
+    }
+}
+
+impl<T: Clone> Clone for ArenaList<T> {
+    fn clone(&self) -> ArenaList<T> {
+        self.iter().cloned().collect()
+    }
+}
+
+impl<T: PartialEq> PartialEq for ArenaList<T> {
+    fn eq(&self, other: &ArenaList<T>) -> bool {
+        self.len() == other.len() && self.iter().eq(other.iter())
+    }
+}
+
+impl<T: Eq> Eq for ArenaList<T> {}
+
+/// Items keep the iterator's order, `[1, 2, 3]` becomes `1 -> 2 -> 3`
+impl<T> FromIterator<T> for ArenaList<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> ArenaList<T> {
+        let mut list = ArenaList::new();
+        list.extend(iter);
+        list
+    }
+}
+
+impl<T> Extend<T> for ArenaList<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for value in iter {
+            self.push_back(value);
+        }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for ArenaList<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+/// Prints like `list::List`: `Cons(1, Cons(2, Nil))`
+impl<T: fmt::Display> fmt::Display for ArenaList<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for value in self.iter() {
+            write!(f, "Cons({}, ", value)?;
+        }
+        write!(f, "Nil")?;
+        for _ in 0..self.len() {
+            write!(f, ")")?;
+        }
+        Ok(())
+    }
+}
+
+pub struct Iter<'a, T> {
+    nodes: &'a Arena<Node<T>>,
+    next: Option<Id<Node<T>>>,
+    len: usize,
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        let node = &self.nodes[self.next?];
+        self.next = node.next;
+        self.len -= 1;
+        Some(&node.value)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<T> ExactSizeIterator for Iter<'_, T> {}
+
+impl<'a, T> IntoIterator for &'a ArenaList<T> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Iter<'a, T> {
+        self.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::list::List;
+
+    #[test]
+    fn behaves_like_the_box_list() {
+        let mut boxed: List<i32> = (1..=3).collect();
+        let mut arena: ArenaList<i32> = (1..=3).collect();
+        assert_eq!(arena.to_string(), boxed.to_string());
+
+        boxed.reverse();
+        arena.reverse();
+        boxed.push_front(4);
+        arena.push_front(4);
+        assert_eq!(format!("{:?}", arena), format!("{:?}", boxed));
+        assert_eq!(arena.to_string(), "Cons(4, Cons(3, Cons(2, Cons(1, Nil))))");
+
+        *arena.front_mut().unwrap() = 40;
+        assert_eq!(arena.pop_front(), Some(40));
+        assert_eq!(arena.front(), Some(&3));
+        assert_eq!(arena.iter().len(), 3);
+    }
+
+    #[test]
+    fn tail_is_kept_right_through_pops_and_reverses() {
+        let mut list = ArenaList::new();
+        list.push_back(1);
+        list.push_front(0);
+        list.reverse();
+        list.push_back(2);
+        assert_eq!(list.iter().copied().collect::<Vec<_>>(), vec![1, 0, 2]);
+
+        while list.pop_front().is_some() {}
+        assert!(list.is_empty());
+        list.push_back(7);
+        assert_eq!(list.iter().copied().collect::<Vec<_>>(), vec![7]);
+    }
+
+    #[test]
+    fn clear_reuses_the_nodes() {
+        let mut list: ArenaList<u32> = (0..100).collect();
+        list.clear();
+        assert!(list.is_empty());
+        list.extend(0..100);
+        assert_eq!(list.iter().sum::<u32>(), 4950);
+        assert_eq!(list.clone(), list);
+    }
+
+    #[test]
+    fn long_lists_drop_without_recursion() {
+        let list: ArenaList<u32> = (0..1_000_000).collect();
+        assert_eq!(list.len(), 1_000_000);
+    }
+}
@@ -0,0 +1,3 @@
+pub mod arena;
+pub mod arena_list;
+pub mod list;
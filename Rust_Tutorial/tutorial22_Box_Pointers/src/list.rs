@@ -0,0 +1,368 @@
+use std::fmt;
+use std::iter::FromIterator;
+
+/* The Cons list from main, made generic and given methods.
+Same shape: each node owns the next one through a Box,
+the Nil at the end is the None */
+
+pub struct List<T> {
+    head: Link<T>,
+    len: usize,
+}
+
+type Link<T> = Option<Box<Node<T>>>;
+
+struct Node<T> {
+    value: T,
+    next: Link<T>,
+}
+
+impl<T> List<T> {
+    pub fn new() -> List<T> {
+        List { head: None, len: 0 }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.head.is_none()
+    }
+
+    pub fn push_front(&mut self, value: T) {
+        let next = self.head.take();
+        self.head = Some(Box::new(Node { value, next }));
+        self.len += 1;
+    }
+
+    pub fn pop_front(&mut self) -> Option<T> {
+        self.head.take().map(|node| {
+            self.head = node.next;
+            self.len -= 1;
+            node.value
+        })
+    }
+
+    pub fn front(&self) -> Option<&T> {
+        self.head.as_ref().map(|node| &node.value)
+    }
+
+    pub fn front_mut(&mut self) -> Option<&mut T> {
+        self.head.as_mut().map(|node| &mut node.value)
+    }
+
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter {
+            next: self.head.as_deref(),
+            len: self.len,
+        }
+    }
+
+    pub fn iter_mut(&mut self) -> IterMut<'_, T> {
+        IterMut {
+            next: self.head.as_deref_mut(),
+            len: self.len,
+        }
+    }
+
+    /// Turns the links around, no nodes are moved or copied
+    pub fn reverse(&mut self) {
+        let mut reversed: Link<T> = None;
+        let mut rest = self.head.take();
+        while let Some(mut node) = rest {
+            rest = node.next.take();
+            node.next = reversed;
+            reversed = Some(node);
+        }
+        self.head = reversed;
+    }
+
+    /// Moves every node of `other` onto the end of this list, leaving `other` empty
+    pub fn append(&mut self, other: &mut List<T>) {
+        let tail = self.tail_link();
+        *tail = other.head.take();
+        self.len += other.len;
+        other.len = 0;
+    }
+
+    /// Keeps the first `at` items and returns the rest as a new list
+    ///
+    /// Panics if `at > len`, like `Vec::split_off`
+    pub fn split_off(&mut self, at: usize) -> List<T> {
+        assert!(
+            at <= self.len,
+            "split index {} out of bounds for length {}",
+            at,
+            self.len
+        );
+
+        let mut link = &mut self.head;
+        for _ in 0..at {
+            link = &mut link.as_mut().expect("len is in sync with the nodes").next;
+        }
+        let rest = List {
+            head: link.take(),
+            len: self.len - at,
+        };
+        self.len = at;
+        rest
+    }
+
+    /* The None at the end of the list, where the next node would go */
+    fn tail_link(&mut self) -> &mut Link<T> {
+        let mut link = &mut self.head;
+        while let Some(node) = link {
+            link = &mut node.next;
+        }
+        link
+    }
+}
+
+impl<T> Default for List<T> {
+    fn default() -> List<T> {
+        List::new()
+    }
+}
+
+/* The default Drop would recurse once per node, a long list overflows the stack */
+impl<T> Drop for List<T> {
+    fn drop(&mut self) {
+        let mut link = self.head.take();
+        while let Some(mut node) = link {
+            link = node.next.take();
+        }
+    }
+}
+
+impl<T: Clone> Clone for List<T> {
+    fn clone(&self) -> List<T> {
+        self.iter().cloned().collect()
+    }
+}
+
+impl<T: PartialEq> PartialEq for List<T> {
+    fn eq(&self, other: &List<T>) -> bool {
+        self.len == other.len && self.iter().eq(other.iter())
+    }
+}
+
+impl<T: Eq> Eq for List<T> {}
+
+/// Items keep the iterator's order, `[1, 2, 3]` becomes `1 -> 2 -> 3`
+impl<T> FromIterator<T> for List<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> List<T> {
+        let mut list = List::new();
+        list.extend(iter);
+        list
+    }
+}
+
+impl<T> Extend<T> for List<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        let mut added = 0;
+        let mut tail = self.tail_link();
+        for value in iter {
+            let node = tail.insert(Box::new(Node { value, next: None }));
+            tail = &mut node.next;
+            added += 1;
+        }
+        self.len += added;
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for List<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+/// Prints the way main spells it: `Cons(1, Cons(2, Nil))`
+impl<T: fmt::Display> fmt::Display for List<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for value in self.iter() {
+            write!(f, "Cons({}, ", value)?;
+        }
+        write!(f, "Nil")?;
+        for _ in 0..self.len {
+            write!(f, ")")?;
+        }
+        Ok(())
+    }
+}
+
+/* Iterators */
+
+pub struct Iter<'a, T> {
+    next: Option<&'a Node<T>>,
+    len: usize,
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        self.next.map(|node| {
+            self.next = node.next.as_deref();
+            self.len -= 1;
+            &node.value
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<T> ExactSizeIterator for Iter<'_, T> {}
+
+pub struct IterMut<'a, T> {
+    next: Option<&'a mut Node<T>>,
+    len: usize,
+}
+
+impl<'a, T> Iterator for IterMut<'a, T> {
+    type Item = &'a mut T;
+
+    fn next(&mut self) -> Option<&'a mut T> {
+        self.next.take().map(|node| {
+            self.next = node.next.as_deref_mut();
+            self.len -= 1;
+            &mut node.value
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<T> ExactSizeIterator for IterMut<'_, T> {}
+
+pub struct IntoIter<T>(List<T>);
+
+impl<T> Iterator for IntoIter<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.0.pop_front()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.0.len, Some(self.0.len))
+    }
+}
+
+impl<T> ExactSizeIterator for IntoIter<T> {}
+
+impl<T> IntoIterator for List<T> {
+    type Item = T;
+    type IntoIter = IntoIter<T>;
+
+    fn into_iter(self) -> IntoIter<T> {
+        IntoIter(self)
+    }
+}
+
+impl<'a, T> IntoIterator for &'a List<T> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Iter<'a, T> {
+        self.iter()
+    }
+}
+
+impl<'a, T> IntoIterator for &'a mut List<T> {
+    type Item = &'a mut T;
+    type IntoIter = IterMut<'a, T>;
+
+    fn into_iter(self) -> IterMut<'a, T> {
+        self.iter_mut()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn list(items: &[i32]) -> List<i32> {
+        items.iter().copied().collect()
+    }
+
+    #[test]
+    fn push_and_pop_at_the_front() {
+        let mut l = List::new();
+        assert_eq!(l.pop_front(), None);
+        l.push_front(1);
+        l.push_front(2);
+        assert_eq!(l.front(), Some(&2));
+        *l.front_mut().unwrap() = 20;
+        assert_eq!(l.len(), 2);
+        assert_eq!(l.pop_front(), Some(20));
+        assert_eq!(l.pop_front(), Some(1));
+        assert!(l.is_empty());
+        assert_eq!(l.len(), 0);
+    }
+
+    #[test]
+    fn three_kinds_of_iteration() {
+        let mut l = list(&[1, 2, 3]);
+        assert_eq!(l.iter().len(), 3);
+        for x in &mut l {
+            *x *= 10;
+        }
+        assert_eq!(l.iter().copied().collect::<Vec<_>>(), vec![10, 20, 30]);
+        let mut owned = l.into_iter();
+        assert_eq!(owned.next(), Some(10));
+        assert_eq!(owned.len(), 2);
+        assert_eq!(owned.sum::<i32>(), 50);
+    }
+
+    #[test]
+    fn reverse_append_and_split_off() {
+        let mut l = list(&[1, 2, 3]);
+        l.reverse();
+        assert_eq!(l, list(&[3, 2, 1]));
+
+        let mut other = list(&[7, 8]);
+        l.append(&mut other);
+        assert_eq!(l, list(&[3, 2, 1, 7, 8]));
+        assert!(other.is_empty());
+
+        let tail = l.split_off(2);
+        assert_eq!(l, list(&[3, 2]));
+        assert_eq!(tail, list(&[1, 7, 8]));
+        assert_eq!((l.len(), tail.len()), (2, 3));
+
+        let everything = l.split_off(0);
+        assert!(l.is_empty());
+        assert_eq!(everything.len(), 2);
+
+        l.extend(vec![4, 5]);
+        assert_eq!(l, list(&[4, 5]));
+    }
+
+    #[test]
+    #[should_panic(expected = "out of bounds")]
+    fn split_off_past_the_end_panics() {
+        list(&[1]).split_off(2);
+    }
+
+    #[test]
+    fn debug_and_display() {
+        let l = list(&[1, 2, 3]);
+        assert_eq!(format!("{:?}", l), "[1, 2, 3]");
+        assert_eq!(l.to_string(), "Cons(1, Cons(2, Cons(3, Nil)))");
+        assert_eq!(List::<i32>::new().to_string(), "Nil");
+    }
+
+    #[test]
+    fn dropping_a_million_nodes_does_not_overflow() {
+        let l: List<u32> = (0..1_000_000).collect();
+        assert_eq!(l.len(), 1_000_000);
+        let copy = l.clone();
+        drop(l);
+        drop(copy);
+    }
+}
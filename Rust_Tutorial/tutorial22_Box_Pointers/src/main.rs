@@ -14,4 +14,17 @@ fn main() {
 
     /*List Recursive type */
     let list = Cons(1, Box::new(Cons(2, Box::new(Cons(3, Box::new(Nil))))));
+
+    /*The same list, without building the boxes by hand */
+    let mut generic: tutorial22_box_pointers::list::List<i32> = (1..=3).collect();
+    println!("{}", generic);
+    generic.reverse();
+    generic.push_front(4);
+    println!("{:?}", generic);
+
+    /*Or with every node in one arena instead of a Box each */
+    let mut arena: tutorial22_box_pointers::arena_list::ArenaList<i32> = (1..=3).collect();
+    arena.reverse();
+    arena.push_front(4);
+    println!("{}", arena);
 }
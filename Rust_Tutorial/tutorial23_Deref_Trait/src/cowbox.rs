@@ -0,0 +1,225 @@
+use std::cell::Cell;
+use std::fmt;
+use std::marker::PhantomData;
+use std::ops::Deref;
+use std::ptr::NonNull;
+
+use crate::mybox::MyBox;
+
+/* A box that can be cloned for free. Clones share one allocation and a count,
+the value is only copied when one of them is written to while others still
+share it. Single threaded, like Rc */
+
+struct Shared<T> {
+    count: Cell<usize>,
+    value: T,
+}
+
+pub struct CowBox<T: Clone> {
+    ptr: NonNull<Shared<T>>,
+    _marker: PhantomData<Shared<T>>,
+}
+
+impl<T: Clone> CowBox<T> {
+    pub fn new(value: T) -> CowBox<T> {
+        let shared = MyBox::new(Shared {
+            count: Cell::new(1),
+            value,
+        });
+        CowBox {
+            // SAFETY: MyBox never hands out a null pointer
+            ptr: unsafe { NonNull::new_unchecked(MyBox::into_raw(shared)) },
+            _marker: PhantomData,
+        }
+    }
+
+    fn shared(&self) -> &Shared<T> {
+        // SAFETY: the allocation lives until the last CowBox sharing it drops
+        unsafe { self.ptr.as_ref() }
+    }
+
+    /// How many CowBoxes share this value, 1 if it's only this one
+    pub fn share_count(this: &CowBox<T>) -> usize {
+        this.shared().count.get()
+    }
+
+    pub fn ptr_eq(a: &CowBox<T>, b: &CowBox<T>) -> bool {
+        a.ptr == b.ptr
+    }
+
+    /// The value for writing, copied first if anyone else can see it
+    pub fn to_mut(&mut self) -> &mut T {
+        if CowBox::share_count(self) > 1 {
+            *self = CowBox::new((**self).clone());
+        }
+        // SAFETY: the count is 1 now, so no other CowBox can read the value
+        // while this borrow of self lasts
+        unsafe { &mut self.ptr.as_mut().value }
+    }
+
+    /// The value itself if this was the last one sharing it, a copy otherwise
+    pub fn into_owned(self) -> T {
+        if CowBox::share_count(&self) > 1 {
+            return (*self).clone();
+        }
+        let this = std::mem::ManuallyDrop::new(self);
+        // SAFETY: the only owner, and `this` is never dropped, so the
+        // allocation is taken over exactly once
+        let shared = unsafe { MyBox::from_raw(this.ptr.as_ptr()) };
+        MyBox::into_inner(shared).value
+    }
+}
+
+impl<T: Clone> Clone for CowBox<T> {
+    fn clone(&self) -> CowBox<T> {
+        let count = &self.shared().count;
+        count.set(count.get() + 1);
+        CowBox {
+            ptr: self.ptr,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T: Clone> Drop for CowBox<T> {
+    fn drop(&mut self) {
+        let count = &self.shared().count;
+        count.set(count.get() - 1);
+        if count.get() == 0 {
+            // SAFETY: the last CowBox, nothing else points at the allocation
+            drop(unsafe { MyBox::from_raw(self.ptr.as_ptr()) });
+        }
+    }
+}
+
+impl<T: Clone> Deref for CowBox<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.shared().value
+    }
+}
+
+impl<T: Clone> From<T> for CowBox<T> {
+    fn from(value: T) -> CowBox<T> {
+        CowBox::new(value)
+    }
+}
+
+impl<T: Clone + fmt::Debug> fmt::Debug for CowBox<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<T: Clone + fmt::Display> fmt::Display for CowBox<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&**self, f)
+    }
+}
+
+impl<T: Clone + PartialEq> PartialEq for CowBox<T> {
+    fn eq(&self, other: &CowBox<T>) -> bool {
+        **self == **other
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::rc::Rc;
+
+    /* Counts how often it was cloned and dropped */
+    #[derive(Debug)]
+    struct Counted {
+        clones: Rc<Cell<usize>>,
+        drops: Rc<Cell<usize>>,
+        value: i32,
+    }
+
+    impl Clone for Counted {
+        fn clone(&self) -> Counted {
+            self.clones.set(self.clones.get() + 1);
+            Counted {
+                clones: Rc::clone(&self.clones),
+                drops: Rc::clone(&self.drops),
+                value: self.value,
+            }
+        }
+    }
+
+    impl Drop for Counted {
+        fn drop(&mut self) {
+            self.drops.set(self.drops.get() + 1);
+        }
+    }
+
+    fn counted() -> (CowBox<Counted>, Rc<Cell<usize>>, Rc<Cell<usize>>) {
+        let clones = Rc::new(Cell::new(0));
+        let drops = Rc::new(Cell::new(0));
+        let value = Counted {
+            clones: Rc::clone(&clones),
+            drops: Rc::clone(&drops),
+            value: 1,
+        };
+        (CowBox::new(value), clones, drops)
+    }
+
+    #[test]
+    fn clones_share_until_written() {
+        let mut a = CowBox::new(String::from("Rust"));
+        let b = a.clone();
+        assert!(CowBox::ptr_eq(&a, &b));
+        assert_eq!(CowBox::share_count(&a), 2);
+
+        a.to_mut().push('!');
+        assert_eq!((a.as_str(), b.as_str()), ("Rust!", "Rust"));
+        assert!(!CowBox::ptr_eq(&a, &b));
+        assert_eq!(CowBox::share_count(&b), 1);
+    }
+
+    #[test]
+    fn the_value_is_only_copied_when_shared() {
+        let (mut a, clones, drops) = counted();
+        a.to_mut().value += 1;
+        assert_eq!(clones.get(), 0);
+
+        let b = a.clone();
+        let c = b.clone();
+        assert_eq!(clones.get(), 0);
+        a.to_mut().value += 1;
+        assert_eq!(clones.get(), 1);
+        assert_eq!((a.value, b.value), (3, 2));
+
+        drop(b);
+        assert_eq!(drops.get(), 0);
+        drop(c);
+        assert_eq!(drops.get(), 1);
+        drop(a);
+        assert_eq!(drops.get(), 2);
+    }
+
+    #[test]
+    fn into_owned_moves_out_when_it_can() {
+        let (a, clones, drops) = counted();
+        let b = a.clone();
+        let copy = a.into_owned();
+        assert_eq!(clones.get(), 1);
+
+        let original = b.into_owned();
+        assert_eq!(clones.get(), 1);
+        assert_eq!(drops.get(), 0);
+        drop((copy, original));
+        assert_eq!(drops.get(), 2);
+    }
+
+    #[test]
+    fn deref_coercion() {
+        fn hello(name: &str) -> String {
+            format!("Hello, {name}!")
+        }
+        let name = CowBox::from(String::from("Rust"));
+        assert_eq!(hello(&name), "Hello, Rust!");
+        assert_eq!(format!("{} {:?}", name, name), "Rust \"Rust\"");
+    }
+}
@@ -0,0 +1,3 @@
+pub mod cowbox;
+pub mod mybox;
+pub mod smallbox;
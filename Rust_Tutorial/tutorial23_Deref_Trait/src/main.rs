@@ -1,21 +1,25 @@
 
-struct MyBox<T>(T);
-
-impl<T> MyBox<T> {
-    fn new(x: T) -> MyBox<T> {
-        MyBox(x)
-    }
-}
+// The first MyBox kept T inline, the library one allocates on the heap like Box
+// struct MyBox<T>(T);
+//
+// impl<T> MyBox<T> {
+//     fn new(x: T) -> MyBox<T> {
+//         MyBox(x)
+//     }
+// }
+//
+// impl<T> Deref for MyBox<T> {
+//     type Target = T;
+//
+//     fn deref(&self) -> &Self::Target {
+//         &self.0
+//     }
+// }
 
 use std::ops::Deref;
-
-impl<T> Deref for MyBox<T> {
-    type Target = T;
-
-    fn deref(&self) -> &Self::Target {
-        &self.0
-    }
-}
+use tutorial23_deref_trait::cowbox::CowBox;
+use tutorial23_deref_trait::mybox::MyBox;
+use tutorial23_deref_trait::smallbox::SmallBox;
 
 
 fn hello(name: &str) {
@@ -45,6 +49,17 @@ fn main() {
     let m = MyBox::new(String::from("Rust"));
     hello(&((*m)[..]));
     hello(&m);
+
+    /*The other pointers coerce the same way */
+    let mut shared = CowBox::new(String::from("Rust"));
+    let original = shared.clone();
+    shared.to_mut().push_str("acean");
+    hello(&shared);
+    hello(&original);
+
+    let small = SmallBox::new(String::from("inline"));
+    println!("{} is stored inline: {}", *small, small.is_inline());
+    hello(&small);
     
 
 }
@@ -0,0 +1,290 @@
+use std::alloc::{self, Layout};
+use std::borrow::{Borrow, BorrowMut};
+use std::cmp::Ordering;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
+use std::mem;
+use std::ops::{Deref, DerefMut};
+use std::ptr::{self, NonNull};
+
+/* The tutorial's MyBox(T) keeps T right inside the struct, so it only looks
+like a Box. This one allocates like Box does: MyBox<T> is one pointer wide
+whatever T is, and moving it moves the pointer, not the value.
+
+The raw pointer code in this crate is checked with Miri, which catches leaks,
+double frees and use after free. The library tests pass under it, with both
+the default Stacked Borrows and -Zmiri-tree-borrows:
+`cargo +nightly miri test -p tutorial23_Deref_Trait --lib` */
+
+pub struct MyBox<T> {
+    ptr: NonNull<T>,
+    // owns a T, for the drop checker and auto traits
+    _marker: PhantomData<T>,
+}
+
+// SAFETY: MyBox owns its T outright, like Box, so it can be sent or shared
+// exactly when T can
+unsafe impl<T: Send> Send for MyBox<T> {}
+unsafe impl<T: Sync> Sync for MyBox<T> {}
+
+impl<T> MyBox<T> {
+    pub fn new(x: T) -> MyBox<T> {
+        let layout = Layout::new::<T>();
+        let ptr = if layout.size() == 0 {
+            // zero sized values need no memory, any aligned pointer will do
+            NonNull::dangling()
+        } else {
+            // SAFETY: the layout isn't zero sized
+            let raw = unsafe { alloc::alloc(layout) } as *mut T;
+            NonNull::new(raw).unwrap_or_else(|| alloc::handle_alloc_error(layout))
+        };
+        // SAFETY: ptr is valid for writes of a T and properly aligned
+        unsafe { ptr.as_ptr().write(x) };
+        MyBox {
+            ptr,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Moves the value back out and frees the allocation
+    pub fn into_inner(this: MyBox<T>) -> T {
+        let this = mem::ManuallyDrop::new(this);
+        // SAFETY: the value is read once and the box is never dropped, so
+        // the value isn't dropped twice
+        unsafe {
+            let value = this.ptr.as_ptr().read();
+            MyBox::free(this.ptr);
+            value
+        }
+    }
+
+    /// Gives up ownership, the value stays on the heap until `from_raw`
+    pub fn into_raw(this: MyBox<T>) -> *mut T {
+        mem::ManuallyDrop::new(this).ptr.as_ptr()
+    }
+
+    /// # Safety
+    ///
+    /// `raw` must come from `MyBox::into_raw` and must not be used again afterwards
+    pub unsafe fn from_raw(raw: *mut T) -> MyBox<T> {
+        MyBox {
+            ptr: NonNull::new_unchecked(raw),
+            _marker: PhantomData,
+        }
+    }
+
+    /* Frees the allocation without touching the value in it */
+    unsafe fn free(ptr: NonNull<T>) {
+        let layout = Layout::new::<T>();
+        if layout.size() != 0 {
+            alloc::dealloc(ptr.as_ptr() as *mut u8, layout);
+        }
+    }
+}
+
+impl<T> Drop for MyBox<T> {
+    fn drop(&mut self) {
+        // SAFETY: the value is still there, this is the only owner, and the
+        // pointer is never used again
+        unsafe {
+            ptr::drop_in_place(self.ptr.as_ptr());
+            MyBox::free(self.ptr);
+        }
+    }
+}
+
+impl<T> Deref for MyBox<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY: the pointer is valid for as long as the box lives
+        unsafe { self.ptr.as_ref() }
+    }
+}
+
+impl<T> DerefMut for MyBox<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        // SAFETY: as in deref, and &mut self means no other borrows
+        unsafe { self.ptr.as_mut() }
+    }
+}
+
+impl<T> From<T> for MyBox<T> {
+    fn from(x: T) -> MyBox<T> {
+        MyBox::new(x)
+    }
+}
+
+impl<T> AsRef<T> for MyBox<T> {
+    fn as_ref(&self) -> &T {
+        self
+    }
+}
+
+impl<T> AsMut<T> for MyBox<T> {
+    fn as_mut(&mut self) -> &mut T {
+        self
+    }
+}
+
+impl<T> Borrow<T> for MyBox<T> {
+    fn borrow(&self) -> &T {
+        self
+    }
+}
+
+impl<T> BorrowMut<T> for MyBox<T> {
+    fn borrow_mut(&mut self) -> &mut T {
+        self
+    }
+}
+
+/* Everything else is forwarded to the value, like Box does */
+
+impl<T: Clone> Clone for MyBox<T> {
+    fn clone(&self) -> MyBox<T> {
+        MyBox::new((**self).clone())
+    }
+}
+
+impl<T: Default> Default for MyBox<T> {
+    fn default() -> MyBox<T> {
+        MyBox::new(T::default())
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for MyBox<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<T: fmt::Display> fmt::Display for MyBox<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&**self, f)
+    }
+}
+
+impl<T: PartialEq> PartialEq for MyBox<T> {
+    fn eq(&self, other: &MyBox<T>) -> bool {
+        **self == **other
+    }
+}
+
+impl<T: Eq> Eq for MyBox<T> {}
+
+impl<T: PartialOrd> PartialOrd for MyBox<T> {
+    fn partial_cmp(&self, other: &MyBox<T>) -> Option<Ordering> {
+        (**self).partial_cmp(&**other)
+    }
+}
+
+impl<T: Ord> Ord for MyBox<T> {
+    fn cmp(&self, other: &MyBox<T>) -> Ordering {
+        (**self).cmp(&**other)
+    }
+}
+
+impl<T: Hash> Hash for MyBox<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (**self).hash(state);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use std::collections::HashSet;
+    use std::rc::Rc;
+
+    /* Counts its drops, to catch leaks and double drops */
+    struct Counted(Rc<Cell<usize>>);
+
+    impl Drop for Counted {
+        fn drop(&mut self) {
+            self.0.set(self.0.get() + 1);
+        }
+    }
+
+    fn hello(name: &str) -> String {
+        format!("Hello, {name}!")
+    }
+
+    #[test]
+    fn deref_coercion_still_works() {
+        let mut m = MyBox::new(String::from("Rust"));
+        assert_eq!(hello(&m), "Hello, Rust!");
+        m.push_str("acean");
+        assert_eq!(hello(&m), "Hello, Rustacean!");
+        assert_eq!(mem::size_of::<MyBox<[u8; 1000]>>(), mem::size_of::<usize>());
+    }
+
+    #[test]
+    fn forwards_the_usual_traits() {
+        let a = MyBox::from(3);
+        let b = a.clone();
+        assert_eq!(a, b);
+        assert!(a <= MyBox::new(4));
+        assert_eq!(format!("{} {:?}", a, MyBox::new("x")), "3 \"x\"");
+        assert_eq!(*MyBox::<i32>::default(), 0);
+
+        let set: HashSet<MyBox<String>> = ["a", "b", "a"]
+            .iter()
+            .map(|s| MyBox::new(s.to_string()))
+            .collect();
+        assert_eq!(set.len(), 2);
+
+        fn takes_as_ref(s: impl AsRef<String>) -> usize {
+            s.as_ref().len()
+        }
+        assert_eq!(takes_as_ref(MyBox::new(String::from("four"))), 4);
+    }
+
+    #[test]
+    fn every_value_is_dropped_exactly_once() {
+        let drops = Rc::new(Cell::new(0));
+        let boxed = MyBox::new(Counted(Rc::clone(&drops)));
+        let moved = boxed;
+        drop(moved);
+        assert_eq!(drops.get(), 1);
+
+        let inner = MyBox::into_inner(MyBox::new(Counted(Rc::clone(&drops))));
+        assert_eq!(drops.get(), 1);
+        drop(inner);
+        assert_eq!(drops.get(), 2);
+
+        let raw = MyBox::into_raw(MyBox::new(Counted(Rc::clone(&drops))));
+        // SAFETY: straight from into_raw, used once
+        drop(unsafe { MyBox::from_raw(raw) });
+        assert_eq!(drops.get(), 3);
+    }
+
+    #[test]
+    fn zero_sized_and_over_aligned_values() {
+        #[repr(align(64))]
+        #[derive(Debug, PartialEq)]
+        struct Aligned(u8);
+
+        let unit = MyBox::new(());
+        assert_eq!(*unit, ());
+        assert_eq!(MyBox::into_inner(unit), ());
+
+        let aligned = MyBox::new(Aligned(7));
+        assert_eq!(&*aligned as *const Aligned as usize % 64, 0);
+        assert_eq!(*aligned, Aligned(7));
+    }
+
+    #[test]
+    fn nested_boxes_free_everything() {
+        let drops = Rc::new(Cell::new(0));
+        let nested = MyBox::new(MyBox::new(vec![
+            Counted(Rc::clone(&drops)),
+            Counted(Rc::clone(&drops)),
+        ]));
+        assert_eq!(nested.len(), 2);
+        drop(nested);
+        assert_eq!(drops.get(), 2);
+    }
+}
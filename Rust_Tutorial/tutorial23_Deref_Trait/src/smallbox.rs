@@ -0,0 +1,241 @@
+use std::cell::UnsafeCell;
+use std::fmt;
+use std::marker::PhantomData;
+use std::mem::{self, MaybeUninit};
+use std::ops::{Deref, DerefMut};
+use std::ptr;
+
+use crate::mybox::MyBox;
+
+/* A box that skips the allocation for small values. Values that fit in three
+words are kept inside the SmallBox itself, anything bigger goes on the heap
+in a MyBox, whose pointer is kept in the same space. Either way a SmallBox is
+three words, and Deref hides which one it is.
+
+The storage is an UnsafeCell because a shared &T to an inline Cell, RefCell
+and so on may still write to it, which is only allowed inside an UnsafeCell */
+
+type Storage = MaybeUninit<[usize; 3]>;
+
+pub struct SmallBox<T> {
+    storage: UnsafeCell<Storage>,
+    _marker: PhantomData<T>,
+}
+
+// SAFETY: a shared SmallBox only hands out &T, the UnsafeCell is only there
+// for T's own interior mutability, so sharing it is as safe as sharing a T
+unsafe impl<T: Sync> Sync for SmallBox<T> {}
+
+impl<T> SmallBox<T> {
+    /// Whether values of type T are kept inline
+    pub const INLINE: bool = mem::size_of::<T>() <= mem::size_of::<Storage>()
+        && mem::align_of::<T>() <= mem::align_of::<Storage>();
+
+    pub fn new(value: T) -> SmallBox<T> {
+        let storage = UnsafeCell::new(Storage::uninit());
+        // SAFETY: when INLINE the storage is big and aligned enough for a T,
+        // otherwise for a MyBox, which is one pointer
+        unsafe {
+            if Self::INLINE {
+                storage.get().cast::<T>().write(value);
+            } else {
+                storage.get().cast::<MyBox<T>>().write(MyBox::new(value));
+            }
+        }
+        SmallBox {
+            storage,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn is_inline(&self) -> bool {
+        Self::INLINE
+    }
+
+    pub fn into_inner(self) -> T {
+        let this = mem::ManuallyDrop::new(self);
+        // SAFETY: the storage holds whatever `new` wrote, read once here and
+        // never dropped since `this` is ManuallyDrop
+        unsafe {
+            if Self::INLINE {
+                this.storage.get().cast::<T>().read()
+            } else {
+                MyBox::into_inner(this.storage.get().cast::<MyBox<T>>().read())
+            }
+        }
+    }
+
+    fn value_ptr(&self) -> *const T {
+        if Self::INLINE {
+            self.storage.get().cast::<T>()
+        } else {
+            // SAFETY: not inline, so the storage holds a MyBox
+            let boxed = unsafe { &*self.storage.get().cast::<MyBox<T>>() };
+            &**boxed
+        }
+    }
+}
+
+impl<T> Drop for SmallBox<T> {
+    fn drop(&mut self) {
+        // SAFETY: the storage holds whatever `new` wrote and is dropped once
+        unsafe {
+            if Self::INLINE {
+                ptr::drop_in_place(self.storage.get_mut().as_mut_ptr().cast::<T>());
+            } else {
+                ptr::drop_in_place(self.storage.get_mut().as_mut_ptr().cast::<MyBox<T>>());
+            }
+        }
+    }
+}
+
+impl<T> Deref for SmallBox<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY: points at the live value, inline or on the heap
+        unsafe { &*self.value_ptr() }
+    }
+}
+
+impl<T> DerefMut for SmallBox<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        // SAFETY: as in deref. Inline values are borrowed through &mut self,
+        // heap ones through the MyBox we own
+        unsafe {
+            if Self::INLINE {
+                &mut *self.storage.get_mut().as_mut_ptr().cast::<T>()
+            } else {
+                &mut *self.storage.get_mut().as_mut_ptr().cast::<MyBox<T>>()
+            }
+        }
+    }
+}
+
+impl<T> From<T> for SmallBox<T> {
+    fn from(value: T) -> SmallBox<T> {
+        SmallBox::new(value)
+    }
+}
+
+impl<T: Clone> Clone for SmallBox<T> {
+    fn clone(&self) -> SmallBox<T> {
+        SmallBox::new((**self).clone())
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for SmallBox<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<T: fmt::Display> fmt::Display for SmallBox<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&**self, f)
+    }
+}
+
+impl<T: PartialEq> PartialEq for SmallBox<T> {
+    fn eq(&self, other: &SmallBox<T>) -> bool {
+        **self == **other
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    struct Counted(Rc<Cell<usize>>);
+
+    impl Drop for Counted {
+        fn drop(&mut self) {
+            self.0.set(self.0.get() + 1);
+        }
+    }
+
+    #[test]
+    fn small_values_stay_inline() {
+        assert!(SmallBox::new(5u64).is_inline());
+        assert!(SmallBox::new(String::from("Rust")).is_inline());
+        assert!(!SmallBox::new([0u64; 4]).is_inline());
+        assert_eq!(
+            mem::size_of::<SmallBox<[u8; 1000]>>(),
+            mem::size_of::<SmallBox<u8>>()
+        );
+    }
+
+    #[test]
+    fn inline_and_heap_values_read_and_write_the_same() {
+        let mut small = SmallBox::new(String::from("Rust"));
+        let mut big = SmallBox::new([1u64; 8]);
+        small.push('!');
+        big[7] = 9;
+
+        // moving the box moves an inline value along with it
+        let moved = small;
+        assert_eq!(&*moved, "Rust!");
+        assert_eq!(big.iter().sum::<u64>(), 16);
+        assert_eq!(SmallBox::new([1u64; 8]).clone(), SmallBox::new([1u64; 8]));
+
+        fn hello(name: &str) -> String {
+            format!("Hello, {name}!")
+        }
+        assert_eq!(hello(&moved), "Hello, Rust!!");
+    }
+
+    #[test]
+    fn every_value_is_dropped_exactly_once() {
+        let drops = Rc::new(Cell::new(0));
+        drop(SmallBox::new(Counted(Rc::clone(&drops))));
+        drop(SmallBox::new([
+            Counted(Rc::clone(&drops)),
+            Counted(Rc::clone(&drops)),
+            Counted(Rc::clone(&drops)),
+            Counted(Rc::clone(&drops)),
+        ]));
+        assert_eq!(drops.get(), 5);
+
+        let inline = SmallBox::new(Counted(Rc::clone(&drops))).into_inner();
+        let heap = SmallBox::new([
+            Counted(Rc::clone(&drops)),
+            Counted(Rc::clone(&drops)),
+            Counted(Rc::clone(&drops)),
+            Counted(Rc::clone(&drops)),
+        ])
+        .into_inner();
+        assert_eq!(drops.get(), 5);
+        drop((inline, heap));
+        assert_eq!(drops.get(), 10);
+    }
+
+    #[test]
+    fn inline_values_can_be_mutated_through_shared_references() {
+        let cell = SmallBox::new(Cell::new(1));
+        assert!(cell.is_inline());
+        let (a, b) = (&cell, &cell);
+        a.set(2);
+        b.set(b.get() + 1);
+        assert_eq!(cell.get(), 3);
+
+        let list = SmallBox::new(std::cell::RefCell::new(vec![1]));
+        list.borrow_mut().push(2);
+        assert_eq!(*list.borrow(), vec![1, 2]);
+    }
+
+    #[test]
+    fn zero_sized_and_over_aligned_values() {
+        #[repr(align(32))]
+        struct Aligned(u8);
+
+        let unit = SmallBox::new(());
+        assert!(unit.is_inline());
+        // too aligned for the storage, so it goes on the heap
+        let aligned = SmallBox::new(Aligned(3));
+        assert!(!aligned.is_inline());
+        assert_eq!(&*aligned as *const Aligned as usize % 32, 0);
+        assert_eq!(aligned.0, 3);
+    }
+}
@@ -0,0 +1,402 @@
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/* CustomSmartPointer only prints when it goes out of scope, but Drop is the
+place for any cleanup that must happen however the scope is left: a normal
+return, `?`, or a panic unwinding through it. These guards do that cleanup.
+
+None of them should panic in drop. A panic while another one is already
+unwinding aborts the whole process, so errors are swallowed there and the
+guards that can fail offer a method that reports them instead */
+
+/// When a `ScopeGuard` runs its closure
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum When {
+    Always,
+    /// Only if the scope is left normally
+    OnSuccess,
+    /// Only if the scope is left by a panic
+    OnUnwind,
+}
+
+/// Runs a closure when dropped, unless `dismiss` is called first
+pub struct ScopeGuard<F: FnOnce()> {
+    action: Option<F>,
+    when: When,
+}
+
+impl<F: FnOnce()> ScopeGuard<F> {
+    pub fn new(action: F) -> ScopeGuard<F> {
+        ScopeGuard::when(When::Always, action)
+    }
+
+    pub fn when(when: When, action: F) -> ScopeGuard<F> {
+        ScopeGuard {
+            action: Some(action),
+            when,
+        }
+    }
+
+    /// Drops the guard without running the closure
+    pub fn dismiss(mut self) {
+        self.action = None;
+    }
+}
+
+impl<F: FnOnce()> Drop for ScopeGuard<F> {
+    fn drop(&mut self) {
+        let run = match self.when {
+            When::Always => true,
+            When::OnSuccess => !thread::panicking(),
+            When::OnUnwind => thread::panicking(),
+        };
+        if let Some(action) = self.action.take() {
+            if run {
+                action();
+            }
+        }
+    }
+}
+
+/// Runs the block when the enclosing scope ends, like Go's `defer`.
+/// Name the guard to be able to dismiss it
+///
+/// ```
+/// use std::cell::RefCell;
+/// use tutorial24_drop_trait::defer;
+///
+/// let log = RefCell::new(vec![]);
+/// {
+///     defer! { log.borrow_mut().push("first deferred, runs last") }
+///     defer!(second => { log.borrow_mut().push("never runs") });
+///     second.dismiss();
+///     log.borrow_mut().push("body");
+/// }
+/// assert_eq!(*log.borrow(), ["body", "first deferred, runs last"]);
+/// ```
+#[macro_export]
+macro_rules! defer {
+    ($name:ident => $body:block) => {
+        let $name = $crate::guard::ScopeGuard::new(|| $body);
+    };
+    ($($body:tt)*) => {
+        let _deferred = $crate::guard::ScopeGuard::new(|| { $($body)* });
+    };
+}
+
+/* Temporary names, unique within the process and unlikely to clash with others */
+
+fn unique_name(prefix: &str) -> String {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.subsec_nanos());
+    format!(
+        "{}{}-{}-{}",
+        prefix,
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed),
+        nanos
+    )
+}
+
+/// Writes to a file that only replaces `path` on `commit`. Dropped without
+/// committing, including during a panic, the original file is left as it was
+/// and the half-written data is removed
+pub struct FileTransaction {
+    target: PathBuf,
+    temp: PathBuf,
+    file: Option<File>,
+}
+
+impl FileTransaction {
+    /// Starts writing the new contents of `path`. The data goes to a
+    /// temporary file in the same directory, so the final rename can't cross
+    /// file systems
+    pub fn begin(path: impl AsRef<Path>) -> io::Result<FileTransaction> {
+        let target = path.as_ref().to_path_buf();
+        let name = target
+            .file_name()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "path has no file name"))?
+            .to_string_lossy();
+        let temp = target.with_file_name(unique_name(&format!(".{}.", name)));
+        let file = File::create(&temp)?;
+        Ok(FileTransaction {
+            target,
+            temp,
+            file: Some(file),
+        })
+    }
+
+    /// Replaces the target with everything written so far
+    pub fn commit(mut self) -> io::Result<()> {
+        if let Some(mut file) = self.file.take() {
+            file.flush()?;
+            file.sync_all()?;
+        }
+        fs::rename(&self.temp, &self.target)
+    }
+
+    fn file(&mut self) -> &mut File {
+        self.file
+            .as_mut()
+            .expect("the file is only taken by commit, which consumes the transaction")
+    }
+}
+
+impl Write for FileTransaction {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.file().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file().flush()
+    }
+}
+
+impl Drop for FileTransaction {
+    fn drop(&mut self) {
+        // after a successful commit the temp name is gone, this fails quietly
+        drop(self.file.take());
+        let _ = fs::remove_file(&self.temp);
+    }
+}
+
+/// A fresh directory that is removed with everything in it when dropped
+pub struct TempDir {
+    path: Option<PathBuf>,
+}
+
+impl TempDir {
+    /// Creates a new directory under the system temp directory
+    pub fn new(prefix: &str) -> io::Result<TempDir> {
+        TempDir::new_in(std::env::temp_dir(), prefix)
+    }
+
+    pub fn new_in(parent: impl AsRef<Path>, prefix: &str) -> io::Result<TempDir> {
+        let path = parent.as_ref().join(unique_name(prefix));
+        fs::create_dir(&path)?;
+        Ok(TempDir { path: Some(path) })
+    }
+
+    pub fn path(&self) -> &Path {
+        self.path
+            .as_deref()
+            .expect("only taken when the guard is consumed")
+    }
+
+    /// Keeps the directory, the caller is now responsible for it
+    pub fn keep(mut self) -> PathBuf {
+        self.path
+            .take()
+            .expect("only taken when the guard is consumed")
+    }
+
+    /// Removes the directory now and reports whether that worked, which
+    /// dropping the guard can't
+    pub fn close(mut self) -> io::Result<()> {
+        match self.path.take() {
+            Some(path) => fs::remove_dir_all(path),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        if let Some(path) = self.path.take() {
+            let _ = fs::remove_dir_all(path);
+        }
+    }
+}
+
+/// What a `Timer` measured
+#[derive(Debug, Clone, PartialEq)]
+pub struct Timing {
+    pub label: String,
+    pub elapsed: Duration,
+    /// The scope was left by a panic
+    pub panicked: bool,
+}
+
+impl fmt::Display for Timing {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} took {:?}", self.label, self.elapsed)?;
+        if self.panicked {
+            write!(f, " (panicked)")?;
+        }
+        Ok(())
+    }
+}
+
+fn print_timing(timing: &Timing) {
+    eprintln!("{}", timing);
+}
+
+/// Measures from creation to drop and hands the result to its sink
+pub struct Timer<F: FnOnce(&Timing)> {
+    label: String,
+    start: Instant,
+    sink: Option<F>,
+}
+
+impl Timer<fn(&Timing)> {
+    /// Prints the timing to stderr
+    pub fn new(label: &str) -> Timer<fn(&Timing)> {
+        Timer::with_sink(label, print_timing)
+    }
+}
+
+impl<F: FnOnce(&Timing)> Timer<F> {
+    pub fn with_sink(label: &str, sink: F) -> Timer<F> {
+        Timer {
+            label: label.to_string(),
+            start: Instant::now(),
+            sink: Some(sink),
+        }
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+}
+
+impl<F: FnOnce(&Timing)> Drop for Timer<F> {
+    fn drop(&mut self) {
+        if let Some(sink) = self.sink.take() {
+            sink(&Timing {
+                label: std::mem::take(&mut self.label),
+                elapsed: self.start.elapsed(),
+                panicked: thread::panicking(),
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::panic::{self, AssertUnwindSafe};
+
+    fn panics(f: impl FnOnce()) {
+        assert!(panic::catch_unwind(AssertUnwindSafe(f)).is_err());
+    }
+
+    #[test]
+    fn deferred_blocks_run_in_reverse_order_even_when_panicking() {
+        let log = RefCell::new(vec![]);
+        panics(|| {
+            defer! { log.borrow_mut().push(1) }
+            defer! { log.borrow_mut().push(2) }
+            panic!("boom");
+        });
+        assert_eq!(*log.borrow(), vec![2, 1]);
+    }
+
+    #[test]
+    fn dismissed_guards_do_nothing() {
+        let log = RefCell::new(vec![]);
+        {
+            defer!(undo => { log.borrow_mut().push("undo") });
+            log.borrow_mut().push("done");
+            undo.dismiss();
+        }
+        assert_eq!(*log.borrow(), vec!["done"]);
+    }
+
+    #[test]
+    fn guards_can_run_only_on_success_or_only_on_unwind() {
+        let log = RefCell::new(vec![]);
+        let body = |fail: bool| {
+            let _ok = ScopeGuard::when(When::OnSuccess, || log.borrow_mut().push("ok"));
+            let _rollback = ScopeGuard::when(When::OnUnwind, || log.borrow_mut().push("rollback"));
+            if fail {
+                panic!("boom");
+            }
+        };
+        body(false);
+        panics(|| body(true));
+        assert_eq!(*log.borrow(), vec!["ok", "rollback"]);
+    }
+
+    #[test]
+    fn file_transaction_only_replaces_the_file_on_commit() {
+        let dir = TempDir::new("guard-test-").unwrap();
+        let path = dir.path().join("config.txt");
+        fs::write(&path, "old").unwrap();
+
+        {
+            let mut tx = FileTransaction::begin(&path).unwrap();
+            tx.write_all(b"half written").unwrap();
+        }
+        panics(|| {
+            let mut tx = FileTransaction::begin(&path).unwrap();
+            tx.write_all(b"half").unwrap();
+            panic!("crashed mid-write");
+        });
+        assert_eq!(fs::read_to_string(&path).unwrap(), "old");
+
+        let mut tx = FileTransaction::begin(&path).unwrap();
+        tx.write_all(b"new").unwrap();
+        tx.commit().unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+        // no temporary files left behind
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn temp_dir_is_removed_with_its_contents() {
+        let kept;
+        let path = {
+            let dir = TempDir::new("guard-test-").unwrap();
+            fs::create_dir_all(dir.path().join("a/b")).unwrap();
+            fs::write(dir.path().join("a/b/file"), "x").unwrap();
+            kept = TempDir::new("guard-test-").unwrap().keep();
+            dir.path().to_path_buf()
+        };
+        assert!(!path.exists());
+        assert!(kept.exists());
+        fs::remove_dir(&kept).unwrap();
+
+        let mut inside = None;
+        panics(|| {
+            let dir = TempDir::new("guard-test-").unwrap();
+            inside = Some(dir.path().to_path_buf());
+            panic!("boom");
+        });
+        assert!(!inside.unwrap().exists());
+
+        let dir = TempDir::new("guard-test-").unwrap();
+        let path = dir.path().to_path_buf();
+        dir.close().unwrap();
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn timer_reports_how_the_scope_ended() {
+        let timings = RefCell::new(vec![]);
+        {
+            let _timer =
+                Timer::with_sink("sleep", |t: &Timing| timings.borrow_mut().push(t.clone()));
+            thread::sleep(Duration::from_millis(20));
+        }
+        panics(|| {
+            let _timer =
+                Timer::with_sink("fails", |t: &Timing| timings.borrow_mut().push(t.clone()));
+            panic!("boom");
+        });
+
+        let timings = timings.borrow();
+        assert_eq!(timings[0].label, "sleep");
+        assert!(timings[0].elapsed >= Duration::from_millis(20));
+        assert!(!timings[0].panicked);
+        assert!(timings[1].panicked);
+        assert!(timings[1].to_string().ends_with("(panicked)"));
+    }
+}
@@ -1,3 +1,7 @@
+use tutorial24_drop_trait::defer;
+use tutorial24_drop_trait::guard::Timer;
+use tutorial24_drop_trait::tracked::{self, Tracked};
+
 struct CustomSmartPointer {
     data: String,
 }
@@ -17,4 +21,20 @@ fn main() {
     };
     drop(c);
     println!("CustomSmartPointers created.");
+
+    /*The same idea without a new struct for every kind of cleanup */
+    let _timer = Timer::new("main");
+    defer! { println!("Deferred cleanup runs when main ends, before d is dropped") }
+    defer!(skipped => { println!("Never printed") });
+    skipped.dismiss();
+
+    /*The drop(c) example again, with the order recorded instead of printed */
+    {
+        let c = Tracked::new("c", String::from("my stuff"));
+        let _d = Tracked::new("d", String::from("other stuff"));
+        drop(c);
+    }
+    for event in tracked::events() {
+        println!("{}", event);
+    }
 }
\ No newline at end of file
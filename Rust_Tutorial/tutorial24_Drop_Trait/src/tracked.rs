@@ -0,0 +1,326 @@
+use std::cell::{Cell, RefCell};
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+
+/* CustomSmartPointer shows drop order by printing, which someone has to read.
+Tracked<T> writes the same story into a log instead, one numbered event per
+construction, clone, move into a container and drop, so a test can assert on
+it. The log is per thread, tests running in parallel don't see each other */
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EventKind {
+    Created,
+    /// A clone of the value with this label
+    Cloned {
+        from: String,
+    },
+    /// Marked with `Tracked::moved_into`, Rust doesn't report moves itself
+    Moved {
+        into: String,
+    },
+    Dropped,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Event {
+    /// Order of the event in this thread's log, from 0
+    pub seq: u64,
+    pub label: String,
+    pub kind: EventKind,
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.kind {
+            EventKind::Created => write!(f, "{}: created {}", self.seq, self.label),
+            EventKind::Cloned { from } => {
+                write!(f, "{}: cloned {} from {}", self.seq, self.label, from)
+            }
+            EventKind::Moved { into } => {
+                write!(f, "{}: moved {} into {}", self.seq, self.label, into)
+            }
+            EventKind::Dropped => write!(f, "{}: dropped {}", self.seq, self.label),
+        }
+    }
+}
+
+thread_local! {
+    static LOG: RefCell<Vec<Event>> = const { RefCell::new(Vec::new()) };
+    static NEXT_SEQ: Cell<u64> = const { Cell::new(0) };
+}
+
+fn record(label: &str, kind: EventKind) -> u64 {
+    let seq = NEXT_SEQ.with(|next| next.replace(next.get() + 1));
+    LOG.with(|log| {
+        log.borrow_mut().push(Event {
+            seq,
+            label: label.to_string(),
+            kind,
+        })
+    });
+    seq
+}
+
+/// Everything recorded on this thread since the last `clear`
+pub fn events() -> Vec<Event> {
+    LOG.with(|log| log.borrow().clone())
+}
+
+/// Empties the log and starts numbering from 0 again
+pub fn clear() {
+    LOG.with(|log| log.borrow_mut().clear());
+    NEXT_SEQ.with(|next| next.set(0));
+}
+
+/// Labels in the order they were dropped
+pub fn drop_order() -> Vec<String> {
+    events()
+        .into_iter()
+        .filter(|event| event.kind == EventKind::Dropped)
+        .map(|event| event.label)
+        .collect()
+}
+
+/// The whole log, one event per line, for assertion messages
+pub fn dump() -> String {
+    events()
+        .iter()
+        .map(|event| format!("  {}\n", event))
+        .collect()
+}
+
+/// A value that logs what happens to it under `label`
+pub struct Tracked<T> {
+    label: String,
+    value: T,
+}
+
+impl<T> Tracked<T> {
+    pub fn new(label: &str, value: T) -> Tracked<T> {
+        record(label, EventKind::Created);
+        Tracked {
+            label: label.to_string(),
+            value,
+        }
+    }
+
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+
+    /// Records that this value is being moved into `container`, and passes it on.
+    /// `vec.push(item.moved_into("vec"))`
+    pub fn moved_into(self, container: &str) -> Tracked<T> {
+        record(
+            &self.label,
+            EventKind::Moved {
+                into: container.to_string(),
+            },
+        );
+        self
+    }
+}
+
+/// The clone is labelled `<label>#<seq>`, seq being the clone event's number
+impl<T: Clone> Clone for Tracked<T> {
+    fn clone(&self) -> Tracked<T> {
+        let seq = NEXT_SEQ.with(|next| next.get());
+        let label = format!("{}#{}", self.label, seq);
+        record(
+            &label,
+            EventKind::Cloned {
+                from: self.label.clone(),
+            },
+        );
+        Tracked {
+            label,
+            value: self.value.clone(),
+        }
+    }
+}
+
+impl<T> Deref for Tracked<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T> DerefMut for Tracked<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Tracked<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("Tracked")
+            .field(&self.label)
+            .field(&self.value)
+            .finish()
+    }
+}
+
+impl<T> Drop for Tracked<T> {
+    fn drop(&mut self) {
+        record(&self.label, EventKind::Dropped);
+    }
+}
+
+/* Assertions on the log. They panic with the whole log, so a failure shows
+what actually happened */
+
+fn dropped_at(label: &str) -> Option<u64> {
+    events()
+        .into_iter()
+        .find(|event| event.label == label && event.kind == EventKind::Dropped)
+        .map(|event| event.seq)
+}
+
+#[track_caller]
+pub fn assert_dropped(label: &str) {
+    if dropped_at(label).is_none() {
+        panic!("`{}` has not been dropped, log:\n{}", label, dump());
+    }
+}
+
+#[track_caller]
+pub fn assert_alive(label: &str) {
+    if dropped_at(label).is_some() {
+        panic!("`{}` has already been dropped, log:\n{}", label, dump());
+    }
+}
+
+/// Both have been dropped, `a` first
+#[track_caller]
+pub fn assert_dropped_before(a: &str, b: &str) {
+    match (dropped_at(a), dropped_at(b)) {
+        (Some(first), Some(second)) if first < second => {}
+        (Some(_), Some(_)) => panic!("`{}` was dropped after `{}`, log:\n{}", a, b, dump()),
+        (None, _) => panic!("`{}` has not been dropped, log:\n{}", a, dump()),
+        (_, None) => panic!("`{}` has not been dropped, log:\n{}", b, dump()),
+    }
+}
+
+/// Exactly these labels have been dropped, in this order
+#[track_caller]
+pub fn assert_drop_order(expected: &[&str]) {
+    let actual = drop_order();
+    if actual != expected {
+        panic!(
+            "drop order was {:?}, expected {:?}, log:\n{}",
+            actual,
+            expected,
+            dump()
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::panic::{self, AssertUnwindSafe};
+
+    #[test]
+    fn explicit_drop_comes_before_the_end_of_scope() {
+        clear();
+        {
+            let c = Tracked::new("c", "my stuff");
+            let _d = Tracked::new("d", "other stuff");
+            drop(c);
+            assert_alive("d");
+        }
+        assert_dropped_before("c", "d");
+        assert_eq!(
+            events().iter().map(|e| e.to_string()).collect::<Vec<_>>(),
+            vec![
+                "0: created c",
+                "1: created d",
+                "2: dropped c",
+                "3: dropped d"
+            ]
+        );
+    }
+
+    #[test]
+    fn locals_drop_in_reverse_and_fields_in_declaration_order() {
+        struct Pair {
+            _first: Tracked<()>,
+            _second: Tracked<()>,
+        }
+
+        clear();
+        {
+            let _a = Tracked::new("a", ());
+            let _b = Tracked::new("b", ());
+            let _pair = Pair {
+                _first: Tracked::new("first", ()),
+                _second: Tracked::new("second", ()),
+            };
+        }
+        assert_drop_order(&["first", "second", "b", "a"]);
+    }
+
+    #[test]
+    fn containers_drop_their_items_front_to_back() {
+        clear();
+        let mut vec = Vec::new();
+        let x = Tracked::new("x", 1);
+        let y = x.clone();
+        vec.push(x.moved_into("vec"));
+        vec.push(y.moved_into("vec"));
+        *vec[1] += 1;
+        assert_eq!(*vec[1], 2);
+
+        let events = events();
+        assert_eq!(
+            events[1].kind,
+            EventKind::Cloned {
+                from: "x".to_string()
+            }
+        );
+        assert_eq!(events[1].label, "x#1");
+        assert_eq!(
+            events[3].kind,
+            EventKind::Moved {
+                into: "vec".to_string()
+            }
+        );
+
+        drop(vec);
+        assert_drop_order(&["x", "x#1"]);
+    }
+
+    #[test]
+    fn unwinding_drops_are_recorded_too() {
+        clear();
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            let _outer = Tracked::new("outer", ());
+            let _inner = Tracked::new("inner", ());
+            panic!("boom");
+        }));
+        assert!(result.is_err());
+        assert_dropped_before("inner", "outer");
+    }
+
+    #[test]
+    #[should_panic(expected = "`a` was dropped after `b`")]
+    fn wrong_order_fails_with_the_log() {
+        clear();
+        let a = Tracked::new("a", ());
+        let b = Tracked::new("b", ());
+        drop(b);
+        drop(a);
+        assert_dropped_before("a", "b");
+    }
+
+    #[test]
+    #[should_panic(expected = "`b` has not been dropped")]
+    fn a_value_still_alive_fails() {
+        clear();
+        let _b = Tracked::new("b", ());
+        drop(Tracked::new("a", ()));
+        assert_dropped_before("a", "b");
+    }
+}
@@ -17,4 +17,13 @@ fn main() {
         println!("count after creating c = {}", Rc::strong_count(&a));
     }
     println!("count after c goes out of scope = {}", Rc::strong_count(&a));
+
+    /*Same sharing with the persistent list, kept as an undo history */
+    use tutorial25_reference_counting::persistent::rc::Stack;
+    let history = Stack::new().push("draft").push("draft v2");
+    let edited = history.push("final");
+    if let Some((undone, previous)) = edited.pop() {
+        println!("undo {:?}, back to {:?}", undone, previous.peek());
+    }
+    println!("versions kept: {} and {}", history.len(), edited.len());
 }
\ No newline at end of file
@@ -0,0 +1,350 @@
+/* Persistent lists and stacks, the grown up version of b and c sharing a in main.
+Nothing is ever changed in place: cons/push make a new node that points at the old
+list, so every older version stays valid and costs nothing to keep around.
+The same code is stamped out twice, on Rc for one thread and on Arc for many */
+
+macro_rules! persistent {
+    ($ptr:ident) => {
+        use std::fmt;
+        use std::iter::FromIterator;
+
+        pub struct List<T> {
+            head: Option<$ptr<Node<T>>>,
+            len: usize,
+        }
+
+        struct Node<T> {
+            value: T,
+            next: Option<$ptr<Node<T>>>,
+        }
+
+        impl<T> List<T> {
+            pub fn new() -> List<T> {
+                List { head: None, len: 0 }
+            }
+
+            /// A new list with `value` in front of this one. O(1), `self` is untouched
+            pub fn cons(&self, value: T) -> List<T> {
+                List {
+                    head: Some($ptr::new(Node {
+                        value,
+                        next: self.head.clone(),
+                    })),
+                    len: self.len + 1,
+                }
+            }
+
+            pub fn head(&self) -> Option<&T> {
+                self.head.as_ref().map(|node| &node.value)
+            }
+
+            /// Everything after the head, sharing its nodes. `None` for an empty list
+            pub fn tail(&self) -> Option<List<T>> {
+                self.head.as_ref().map(|node| List {
+                    head: node.next.clone(),
+                    len: self.len - 1,
+                })
+            }
+
+            pub fn len(&self) -> usize {
+                self.len
+            }
+
+            pub fn is_empty(&self) -> bool {
+                self.head.is_none()
+            }
+
+            pub fn iter(&self) -> Iter<'_, T> {
+                Iter {
+                    next: self.head.as_deref(),
+                    len: self.len,
+                }
+            }
+
+            /// Both lists start at the very same node
+            pub fn ptr_eq(&self, other: &List<T>) -> bool {
+                match (&self.head, &other.head) {
+                    (Some(a), Some(b)) => $ptr::ptr_eq(a, b),
+                    (None, None) => true,
+                    _ => false,
+                }
+            }
+
+            /// `strong_count` of every node from the head on. A count above 1 means some
+            /// other list or clone points at that node too
+            pub fn strong_counts(&self) -> Vec<usize> {
+                let mut counts = Vec::with_capacity(self.len);
+                let mut next = self.head.as_ref();
+                while let Some(node) = next {
+                    counts.push($ptr::strong_count(node));
+                    next = node.next.as_ref();
+                }
+                counts
+            }
+        }
+
+        impl<T> Default for List<T> {
+            fn default() -> List<T> {
+                List::new()
+            }
+        }
+
+        /// Only bumps the count on the head node, `T` doesn't need to be Clone
+        impl<T> Clone for List<T> {
+            fn clone(&self) -> List<T> {
+                List {
+                    head: self.head.clone(),
+                    len: self.len,
+                }
+            }
+        }
+
+        /* Unlinks nodes nobody else shares one at a time, so a long list can't
+        overflow the stack. Stops at the first node that is still shared.
+        into_inner rather than try_unwrap: with Arc, two threads dropping lists
+        with a shared tail could both fail try_unwrap, and whichever Err turned
+        out to be the last reference would drop the whole tail recursively */
+        impl<T> Drop for List<T> {
+            fn drop(&mut self) {
+                let mut next = self.head.take();
+                while let Some(node) = next {
+                    match $ptr::into_inner(node) {
+                        Some(mut node) => next = node.next.take(),
+                        None => break,
+                    }
+                }
+            }
+        }
+
+        impl<T: PartialEq> PartialEq for List<T> {
+            fn eq(&self, other: &List<T>) -> bool {
+                self.len == other.len && (self.ptr_eq(other) || self.iter().eq(other.iter()))
+            }
+        }
+
+        impl<T: fmt::Debug> fmt::Debug for List<T> {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.debug_list().entries(self.iter()).finish()
+            }
+        }
+
+        /// Keeps the iterator's order, the first item becomes the head
+        impl<T> FromIterator<T> for List<T> {
+            fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> List<T> {
+                let items: Vec<T> = iter.into_iter().collect();
+                items
+                    .into_iter()
+                    .rev()
+                    .fold(List::new(), |list, value| list.cons(value))
+            }
+        }
+
+        pub struct Iter<'a, T> {
+            next: Option<&'a Node<T>>,
+            len: usize,
+        }
+
+        impl<'a, T> Iterator for Iter<'a, T> {
+            type Item = &'a T;
+
+            fn next(&mut self) -> Option<&'a T> {
+                self.next.map(|node| {
+                    self.next = node.next.as_deref();
+                    self.len -= 1;
+                    &node.value
+                })
+            }
+
+            fn size_hint(&self) -> (usize, Option<usize>) {
+                (self.len, Some(self.len))
+            }
+        }
+
+        impl<T> ExactSizeIterator for Iter<'_, T> {}
+
+        impl<'a, T> IntoIterator for &'a List<T> {
+            type Item = &'a T;
+            type IntoIter = Iter<'a, T>;
+
+            fn into_iter(self) -> Iter<'a, T> {
+                self.iter()
+            }
+        }
+
+        /* A stack is a list seen from the top. Every push and pop is a new
+        version, older ones stay usable, which is all an undo history needs */
+
+        pub struct Stack<T>(List<T>);
+
+        impl<T> Stack<T> {
+            pub fn new() -> Stack<T> {
+                Stack(List::new())
+            }
+
+            pub fn push(&self, value: T) -> Stack<T> {
+                Stack(self.0.cons(value))
+            }
+
+            pub fn peek(&self) -> Option<&T> {
+                self.0.head()
+            }
+
+            /// The top item and the stack underneath it
+            pub fn pop(&self) -> Option<(&T, Stack<T>)> {
+                Some((self.0.head()?, Stack(self.0.tail()?)))
+            }
+
+            pub fn len(&self) -> usize {
+                self.0.len()
+            }
+
+            pub fn is_empty(&self) -> bool {
+                self.0.is_empty()
+            }
+
+            /// Top first
+            pub fn iter(&self) -> Iter<'_, T> {
+                self.0.iter()
+            }
+
+            pub fn as_list(&self) -> &List<T> {
+                &self.0
+            }
+        }
+
+        impl<T> Default for Stack<T> {
+            fn default() -> Stack<T> {
+                Stack::new()
+            }
+        }
+
+        impl<T> Clone for Stack<T> {
+            fn clone(&self) -> Stack<T> {
+                Stack(self.0.clone())
+            }
+        }
+
+        impl<T: fmt::Debug> fmt::Debug for Stack<T> {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.debug_tuple("Stack").field(&self.0).finish()
+            }
+        }
+
+        /// Test helper: panics unless `list.strong_counts()` is `expected`.
+        /// For b in main that is `&[1, 3, 1]`: b's own head, then a's head which
+        /// b, c and a all point at, then a node only a's head points at
+        #[track_caller]
+        pub fn assert_sharing<T>(list: &List<T>, expected: &[usize]) {
+            let actual = list.strong_counts();
+            assert_eq!(
+                actual, expected,
+                "strong counts from the head on were {:?}, expected {:?}",
+                actual, expected
+            );
+        }
+    };
+}
+
+pub mod rc {
+    use std::rc::Rc;
+    persistent!(Rc);
+}
+
+/// Same API, `Send + Sync` when `T` is
+pub mod sync {
+    use std::sync::Arc;
+    persistent!(Arc);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::rc::{assert_sharing, List, Stack};
+    use super::sync;
+    use std::thread;
+
+    #[test]
+    fn cons_shares_the_tail_like_main() {
+        let a: List<i32> = vec![5, 10].into_iter().collect();
+        let b = a.cons(3);
+        let c = a.cons(4);
+
+        assert_eq!(b.iter().copied().collect::<Vec<_>>(), vec![3, 5, 10]);
+        assert_eq!(c.head(), Some(&4));
+        assert!(b.tail().unwrap().ptr_eq(&a));
+        // a's head is held by a, b and c. The node after it only by the head
+        assert_sharing(&a, &[3, 1]);
+        assert_sharing(&b, &[1, 3, 1]);
+
+        drop(c);
+        assert_sharing(&a, &[2, 1]);
+    }
+
+    #[test]
+    fn clones_and_tails_are_cheap_and_equal() {
+        let a: List<String> = ["x", "y", "z"].iter().map(|s| s.to_string()).collect();
+        let copy = a.clone();
+        assert!(copy.ptr_eq(&a));
+        assert_eq!(copy, a);
+        assert_sharing(&a, &[2, 1, 1]);
+
+        let rebuilt: List<String> = a.iter().cloned().collect();
+        assert!(!rebuilt.ptr_eq(&a));
+        assert_eq!(rebuilt, a);
+
+        let tail = a.tail().unwrap();
+        assert_eq!(tail.len(), 2);
+        assert_eq!(format!("{:?}", tail), r#"["y", "z"]"#);
+        assert!(List::<String>::new().tail().is_none());
+    }
+
+    #[test]
+    fn stack_versions_work_as_undo_history() {
+        let empty = Stack::new();
+        let one = empty.push("hello");
+        let two = one.push("hello world");
+        let three = two.push("hello world!");
+
+        let (current, undone) = three.pop().unwrap();
+        assert_eq!(*current, "hello world!");
+        assert!(undone.as_list().ptr_eq(two.as_list()));
+        assert_eq!(undone.peek(), Some(&"hello world"));
+
+        // branching off an old version leaves the newer ones alone
+        let branch = one.push("hello there");
+        assert_eq!(three.len(), 3);
+        assert_eq!(
+            branch.iter().copied().collect::<Vec<_>>(),
+            ["hello there", "hello"]
+        );
+        assert_sharing(one.as_list(), &[3]);
+        assert!(empty.pop().is_none());
+    }
+
+    #[test]
+    fn dropping_a_long_list_does_not_overflow() {
+        let long: List<u32> = (0..1_000_000).collect();
+        let shared = long.tail().unwrap();
+        drop(long);
+        assert_eq!(shared.len(), 999_999);
+        // the old head is gone, nothing else holds the rest
+        assert!(shared.strong_counts().iter().all(|&count| count == 1));
+    }
+
+    #[test]
+    fn arc_lists_are_shared_between_threads() {
+        let base: sync::List<u32> = (1..=3).collect();
+        let handles: Vec<_> = (0..4)
+            .map(|n| {
+                let base = base.clone();
+                thread::spawn(move || base.cons(n * 10))
+            })
+            .collect();
+        let lists: Vec<_> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+
+        for (n, list) in lists.iter().enumerate() {
+            assert_eq!(list.head(), Some(&(n as u32 * 10)));
+            assert!(list.tail().unwrap().ptr_eq(&base));
+        }
+        sync::assert_sharing(&base, &[5, 1, 1]);
+    }
+}
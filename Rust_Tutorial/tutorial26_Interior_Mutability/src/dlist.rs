@@ -0,0 +1,481 @@
+use std::cell::{Ref, RefCell, RefMut};
+use std::fmt;
+use std::rc::{Rc, Weak};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/* Doubly linked list on Rc<RefCell<..>> like the Cons list in main.
+Forward links are Rc and own the next node, back links are Weak so
+a node and its neighbour never keep each other alive */
+
+type Link<T> = Rc<RefCell<Node<T>>>;
+
+struct Node<T> {
+    value: T,
+    next: Option<Link<T>>,
+    prev: Option<Weak<RefCell<Node<T>>>>,
+    // which list the node lives in, so a Handle can't be used on another list
+    owner: usize,
+}
+
+static NEXT_LIST_ID: AtomicUsize = AtomicUsize::new(0);
+
+pub struct DList<T> {
+    head: Option<Link<T>>,
+    tail: Option<Link<T>>,
+    len: usize,
+    id: usize,
+}
+
+/// Points at one node without keeping it alive. Used for O(1) moves and removes
+pub struct Handle<T>(Weak<RefCell<Node<T>>>);
+
+impl<T> Handle<T> {
+    /// False once the node has been removed or its list dropped
+    pub fn is_alive(&self) -> bool {
+        self.0.strong_count() > 0
+    }
+
+    /// Strong references to the node, 0 means it has been freed
+    pub fn strong_count(&self) -> usize {
+        self.0.strong_count()
+    }
+}
+
+impl<T> Clone for Handle<T> {
+    fn clone(&self) -> Handle<T> {
+        Handle(Weak::clone(&self.0))
+    }
+}
+
+impl<T> DList<T> {
+    pub fn new() -> DList<T> {
+        DList {
+            head: None,
+            tail: None,
+            len: 0,
+            id: NEXT_LIST_ID.fetch_add(1, Ordering::Relaxed),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.head.is_none()
+    }
+
+    pub fn push_front(&mut self, value: T) -> Handle<T> {
+        let head = self.head.clone();
+        self.insert_between(None, head, value)
+    }
+
+    pub fn push_back(&mut self, value: T) -> Handle<T> {
+        let tail = self.tail.clone();
+        self.insert_between(tail, None, value)
+    }
+
+    pub fn pop_front(&mut self) -> Option<T> {
+        let node = self.head.clone()?;
+        self.unlink(&node);
+        Some(into_value(node))
+    }
+
+    pub fn pop_back(&mut self) -> Option<T> {
+        let node = self.tail.clone()?;
+        self.unlink(&node);
+        Some(into_value(node))
+    }
+
+    pub fn front(&self) -> Option<Ref<'_, T>> {
+        self.head
+            .as_ref()
+            .map(|node| Ref::map(node.borrow(), |node| &node.value))
+    }
+
+    pub fn front_mut(&mut self) -> Option<RefMut<'_, T>> {
+        self.head
+            .as_ref()
+            .map(|node| RefMut::map(node.borrow_mut(), |node| &mut node.value))
+    }
+
+    pub fn back(&self) -> Option<Ref<'_, T>> {
+        self.tail
+            .as_ref()
+            .map(|node| Ref::map(node.borrow(), |node| &node.value))
+    }
+
+    /// Takes the node `handle` points at out of the list. `None` if it was
+    /// already removed or belongs to another list
+    pub fn remove(&mut self, handle: &Handle<T>) -> Option<T> {
+        let node = self.own(handle)?;
+        self.unlink(&node);
+        Some(into_value(node))
+    }
+
+    /// Relinks the node at the front, returns false for a dead or foreign handle
+    pub fn move_to_front(&mut self, handle: &Handle<T>) -> bool {
+        let node = match self.own(handle) {
+            Some(node) => node,
+            None => return false,
+        };
+        if self
+            .head
+            .as_ref()
+            .is_some_and(|head| Rc::ptr_eq(head, &node))
+        {
+            return true;
+        }
+
+        self.unlink(&node);
+        let head = self.head.clone();
+        self.link_between(None, head, &node);
+        true
+    }
+
+    /// Front to back
+    pub fn map_to_vec<R>(&self, mut f: impl FnMut(&T) -> R) -> Vec<R> {
+        let mut out = Vec::with_capacity(self.len);
+        let mut next = self.head.clone();
+        while let Some(node) = next {
+            let node = node.borrow();
+            out.push(f(&node.value));
+            next = node.next.clone();
+        }
+        out
+    }
+
+    pub fn to_vec(&self) -> Vec<T>
+    where
+        T: Clone,
+    {
+        self.map_to_vec(T::clone)
+    }
+
+    /// Cursor on the first node
+    pub fn cursor_front_mut(&mut self) -> CursorMut<'_, T> {
+        let current = self.head.clone();
+        CursorMut {
+            list: self,
+            current,
+        }
+    }
+
+    /// Cursor on the last node
+    pub fn cursor_back_mut(&mut self) -> CursorMut<'_, T> {
+        let current = self.tail.clone();
+        CursorMut {
+            list: self,
+            current,
+        }
+    }
+
+    fn own(&self, handle: &Handle<T>) -> Option<Link<T>> {
+        handle
+            .0
+            .upgrade()
+            .filter(|node| node.borrow().owner == self.id)
+    }
+
+    fn insert_between(
+        &mut self,
+        prev: Option<Link<T>>,
+        next: Option<Link<T>>,
+        value: T,
+    ) -> Handle<T> {
+        let node = Rc::new(RefCell::new(Node {
+            value,
+            next: None,
+            prev: None,
+            owner: self.id,
+        }));
+        self.link_between(prev, next, &node);
+        Handle(Rc::downgrade(&node))
+    }
+
+    /* `prev` and `next` must be neighbours in this list (or the ends), `node` detached */
+    fn link_between(&mut self, prev: Option<Link<T>>, next: Option<Link<T>>, node: &Link<T>) {
+        {
+            let mut n = node.borrow_mut();
+            n.prev = prev.as_ref().map(Rc::downgrade);
+            n.next = next.clone();
+        }
+        match &prev {
+            Some(prev) => prev.borrow_mut().next = Some(Rc::clone(node)),
+            None => self.head = Some(Rc::clone(node)),
+        }
+        match &next {
+            Some(next) => next.borrow_mut().prev = Some(Rc::downgrade(node)),
+            None => self.tail = Some(Rc::clone(node)),
+        }
+        self.len += 1;
+    }
+
+    /* Detaches `node`, the caller's Rc is then the only one left */
+    fn unlink(&mut self, node: &Link<T>) {
+        let (prev, next) = {
+            let mut n = node.borrow_mut();
+            (n.prev.take().and_then(|prev| prev.upgrade()), n.next.take())
+        };
+        match &prev {
+            Some(prev) => prev.borrow_mut().next = next.clone(),
+            None => self.head = next.clone(),
+        }
+        match &next {
+            Some(next) => next.borrow_mut().prev = prev.as_ref().map(Rc::downgrade),
+            None => self.tail = prev,
+        }
+        self.len -= 1;
+    }
+}
+
+fn into_value<T>(node: Link<T>) -> T {
+    match Rc::try_unwrap(node) {
+        Ok(cell) => cell.into_inner().value,
+        Err(_) => panic!("a detached node has exactly one owner"),
+    }
+}
+
+impl<T> Default for DList<T> {
+    fn default() -> DList<T> {
+        DList::new()
+    }
+}
+
+/* Walks the Rc chain instead of letting each node drop the next recursively */
+impl<T> Drop for DList<T> {
+    fn drop(&mut self) {
+        self.tail = None;
+        let mut next = self.head.take();
+        while let Some(node) = next {
+            next = node.borrow_mut().next.take();
+        }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for DList<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut list = f.debug_list();
+        let mut next = self.head.clone();
+        while let Some(node) = next {
+            let node = node.borrow();
+            list.entry(&node.value);
+            next = node.next.clone();
+        }
+        list.finish()
+    }
+}
+
+impl<T> FromIterator<T> for DList<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> DList<T> {
+        let mut list = DList::new();
+        for value in iter {
+            list.push_back(value);
+        }
+        list
+    }
+}
+
+pub struct IntoIter<T>(DList<T>);
+
+impl<T> Iterator for IntoIter<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.0.pop_front()
+    }
+}
+
+impl<T> DoubleEndedIterator for IntoIter<T> {
+    fn next_back(&mut self) -> Option<T> {
+        self.0.pop_back()
+    }
+}
+
+impl<T> IntoIterator for DList<T> {
+    type Item = T;
+    type IntoIter = IntoIter<T>;
+
+    fn into_iter(self) -> IntoIter<T> {
+        IntoIter(self)
+    }
+}
+
+/* Cursor. Sits on a node, or on the "ghost" position past the back and
+before the front when `current` is None, like std's LinkedList cursors */
+
+pub struct CursorMut<'a, T> {
+    list: &'a mut DList<T>,
+    current: Option<Link<T>>,
+}
+
+impl<T> CursorMut<'_, T> {
+    /// From the ghost this goes to the front
+    pub fn move_next(&mut self) {
+        self.current = match self.current.take() {
+            Some(node) => node.borrow().next.clone(),
+            None => self.list.head.clone(),
+        };
+    }
+
+    /// From the ghost this goes to the back
+    pub fn move_prev(&mut self) {
+        self.current = match self.current.take() {
+            Some(node) => node.borrow().prev.as_ref().and_then(Weak::upgrade),
+            None => self.list.tail.clone(),
+        };
+    }
+
+    pub fn current(&self) -> Option<Ref<'_, T>> {
+        self.current
+            .as_ref()
+            .map(|node| Ref::map(node.borrow(), |node| &node.value))
+    }
+
+    pub fn current_mut(&mut self) -> Option<RefMut<'_, T>> {
+        self.current
+            .as_ref()
+            .map(|node| RefMut::map(node.borrow_mut(), |node| &mut node.value))
+    }
+
+    /// On the ghost, this pushes to the back
+    pub fn insert_before(&mut self, value: T) -> Handle<T> {
+        let prev = match &self.current {
+            Some(node) => node.borrow().prev.as_ref().and_then(Weak::upgrade),
+            None => self.list.tail.clone(),
+        };
+        let next = self.current.clone();
+        self.list.insert_between(prev, next, value)
+    }
+
+    /// On the ghost, this pushes to the front
+    pub fn insert_after(&mut self, value: T) -> Handle<T> {
+        let next = match &self.current {
+            Some(node) => node.borrow().next.clone(),
+            None => self.list.head.clone(),
+        };
+        let prev = self.current.clone();
+        self.list.insert_between(prev, next, value)
+    }
+
+    /// Removes the current node and moves on to the one after it
+    pub fn remove_current(&mut self) -> Option<T> {
+        let node = self.current.take()?;
+        self.current = node.borrow().next.clone();
+        self.list.unlink(&node);
+        Some(into_value(node))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn push_pop_at_both_ends() {
+        let mut list = DList::new();
+        list.push_back(2);
+        list.push_front(1);
+        list.push_back(3);
+        assert_eq!(list.to_vec(), vec![1, 2, 3]);
+        assert_eq!(*list.front().unwrap(), 1);
+        assert_eq!(*list.back().unwrap(), 3);
+        *list.front_mut().unwrap() = 10;
+
+        assert_eq!(list.pop_back(), Some(3));
+        assert_eq!(list.pop_front(), Some(10));
+        assert_eq!(list.pop_front(), Some(2));
+        assert_eq!(list.pop_back(), None);
+        assert!(list.is_empty());
+        assert!(list.back().is_none());
+    }
+
+    #[test]
+    fn cursor_walks_both_ways_and_edits() {
+        let mut list: DList<i32> = vec![1, 2, 4].into_iter().collect();
+        {
+            let mut cursor = list.cursor_front_mut();
+            cursor.move_next();
+            assert_eq!(*cursor.current().unwrap(), 2);
+            cursor.insert_after(3);
+            cursor.insert_before(0);
+            *cursor.current_mut().unwrap() *= 100;
+
+            cursor.move_prev();
+            assert_eq!(cursor.remove_current(), Some(0));
+            assert_eq!(*cursor.current().unwrap(), 200);
+
+            // off the back end onto the ghost, then around to the front
+            cursor.move_next();
+            cursor.move_next();
+            cursor.move_next();
+            assert!(cursor.current().is_none());
+            cursor.insert_after(-1);
+            cursor.insert_before(5);
+            cursor.move_next();
+            assert_eq!(*cursor.current().unwrap(), -1);
+        }
+        assert_eq!(format!("{:?}", list), "[-1, 1, 200, 3, 4, 5]");
+
+        let mut cursor = list.cursor_back_mut();
+        assert_eq!(cursor.remove_current(), Some(5));
+        assert!(cursor.current().is_none());
+        assert_eq!(list.to_vec(), vec![-1, 1, 200, 3, 4]);
+    }
+
+    #[test]
+    fn handles_move_and_remove_in_place() {
+        let mut list = DList::new();
+        let a = list.push_back("a");
+        let b = list.push_back("b");
+        let c = list.push_back("c");
+
+        assert!(list.move_to_front(&c));
+        assert!(list.move_to_front(&c));
+        assert_eq!(list.to_vec(), vec!["c", "a", "b"]);
+        assert_eq!(list.remove(&b), Some("b"));
+        assert_eq!(*list.back().unwrap(), "a");
+        assert!(!b.is_alive());
+        assert_eq!(list.remove(&b), None);
+        assert!(!list.move_to_front(&b));
+
+        let mut other = DList::new();
+        other.push_back("z");
+        assert_eq!(other.remove(&a), None);
+        assert_eq!(list.len(), 2);
+        assert_eq!(list.into_iter().rev().collect::<Vec<_>>(), vec!["a", "c"]);
+    }
+
+    #[test]
+    fn every_node_is_freed() {
+        let mut list = DList::new();
+        let mut handles: Vec<_> = (0..100).map(|n| list.push_back(n)).collect();
+        {
+            let mut cursor = list.cursor_front_mut();
+            for n in 0..50 {
+                handles.push(cursor.insert_after(n));
+                cursor.move_next();
+                cursor.move_next();
+            }
+        }
+        list.move_to_front(&handles[99]);
+        list.remove(&handles[10]);
+        assert_eq!(handles[10].strong_count(), 0);
+        assert_eq!(list.len(), 149);
+
+        drop(list);
+        assert!(handles.iter().all(|h| h.strong_count() == 0));
+    }
+
+    #[test]
+    fn dropping_a_long_list_does_not_overflow() {
+        let list: DList<u32> = (0..1_000_000).collect();
+        let first = {
+            let mut list = list;
+            let handle = list.push_front(0);
+            drop(list);
+            handle
+        };
+        assert!(!first.is_alive());
+    }
+}
@@ -0,0 +1,8 @@
+pub mod dlist;
+pub mod lru;
+pub mod messenger;
+pub mod mock;
+pub mod quota;
+pub mod shared;
+
+pub use messenger::Messenger;
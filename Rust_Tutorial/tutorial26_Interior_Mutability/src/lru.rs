@@ -0,0 +1,135 @@
+use std::cell::Ref;
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use crate::dlist::{DList, Handle};
+
+/* Least recently used cache. The DList keeps entries in use order, most recent
+at the front, and the map finds an entry's node in O(1) so it can be moved */
+
+pub struct LruCache<K, V> {
+    capacity: usize,
+    map: HashMap<K, Handle<(K, V)>>,
+    order: DList<(K, V)>,
+}
+
+impl<K: Hash + Eq + Clone, V> LruCache<K, V> {
+    pub fn new(capacity: usize) -> LruCache<K, V> {
+        assert!(
+            capacity > 0,
+            "an LRU cache needs room for at least one entry"
+        );
+        LruCache {
+            capacity,
+            map: HashMap::with_capacity(capacity),
+            order: DList::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Marks the entry as just used
+    pub fn get(&mut self, key: &K) -> Option<Ref<'_, V>> {
+        let handle = self.map.get(key)?;
+        self.order.move_to_front(handle);
+        self.order.front().map(|entry| Ref::map(entry, |(_, v)| v))
+    }
+
+    /// Looks without counting as a use
+    pub fn contains(&self, key: &K) -> bool {
+        self.map.contains_key(key)
+    }
+
+    /// Inserts or replaces `key`, making it the most recent entry.
+    /// Returns the least recently used entry if it had to go to make room
+    pub fn put(&mut self, key: K, value: V) -> Option<(K, V)> {
+        if let Some(handle) = self.map.get(&key) {
+            self.order.move_to_front(handle);
+            if let Some(mut entry) = self.order.front_mut() {
+                entry.1 = value;
+            }
+            return None;
+        }
+
+        let evicted = if self.map.len() == self.capacity {
+            let (old_key, old_value) = self.order.pop_back()?;
+            self.map.remove(&old_key);
+            Some((old_key, old_value))
+        } else {
+            None
+        };
+
+        let handle = self.order.push_front((key.clone(), value));
+        self.map.insert(key, handle);
+        evicted
+    }
+
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        let handle = self.map.remove(key)?;
+        self.order.remove(&handle).map(|(_, v)| v)
+    }
+
+    /// Most recently used first
+    pub fn keys(&self) -> Vec<K> {
+        self.order.map_to_vec(|(k, _)| k.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn evicts_the_least_recently_used() {
+        let mut cache = LruCache::new(2);
+        assert_eq!(cache.put("a", 1), None);
+        assert_eq!(cache.put("b", 2), None);
+        assert_eq!(*cache.get(&"a").unwrap(), 1);
+
+        assert_eq!(cache.put("c", 3), Some(("b", 2)));
+        assert!(!cache.contains(&"b"));
+        assert!(cache.get(&"b").is_none());
+        assert_eq!(cache.keys(), vec!["c", "a"]);
+
+        assert_eq!(cache.put("a", 10), None);
+        assert_eq!(cache.keys(), vec!["a", "c"]);
+        assert_eq!(cache.put("d", 4), Some(("c", 3)));
+        assert_eq!(*cache.get(&"a").unwrap(), 10);
+        assert_eq!(cache.len(), 2);
+    }
+
+    #[test]
+    fn remove_frees_a_slot() {
+        let mut cache = LruCache::new(2);
+        cache.put(1, "one".to_string());
+        cache.put(2, "two".to_string());
+        assert_eq!(cache.remove(&1), Some("one".to_string()));
+        assert_eq!(cache.remove(&1), None);
+        assert_eq!(cache.put(3, "three".to_string()), None);
+        assert_eq!(cache.keys(), vec![3, 2]);
+    }
+
+    #[test]
+    fn no_entry_outlives_the_cache() {
+        let mut cache = LruCache::new(3);
+        for n in 0..10 {
+            cache.put(n, vec![n; 4]);
+            cache.get(&(n / 2));
+        }
+        let handles: Vec<_> = cache.map.values().cloned().collect();
+        assert!(handles.iter().all(|h| h.is_alive()));
+
+        drop(cache);
+        assert!(handles.iter().all(|h| h.strong_count() == 0));
+    }
+}
@@ -30,13 +30,60 @@ fn main() {
     println!("a after = {:?}", a);
     println!("b after = {:?}", b);
     println!("c after = {:?}", c);
+
+    /*Rc<RefCell> links forwards, Weak links backwards */
+    use tutorial26_interior_mutability::lru::LruCache;
+    let mut cache = LruCache::new(2);
+    cache.put("a", 1);
+    cache.put("b", 2);
+    cache.get(&"a");
+    println!("evicted = {:?}", cache.put("c", 3));
+    println!("most recent first = {:?}", cache.keys());
+
+    /*Quota alerts only fire when a threshold is crossed */
+    use tutorial26_interior_mutability::quota::QuotaTracker;
+    let console = Console;
+    let mut quota = QuotaTracker::new(&console, 100);
+    for used in [50, 80, 85, 95] {
+        quota.set_value("alice", used);
+    }
+
+    /*Shared between threads, alerts delivered on a consumer thread */
+    use std::sync::Arc;
+    use tutorial26_interior_mutability::messenger::ChannelMessenger;
+    use tutorial26_interior_mutability::shared::AtomicLimitTracker;
+    let (messenger, consumer) = ChannelMessenger::spawn(|msg| println!("async alert: {}", msg));
+    let tracker = AtomicLimitTracker::new(Arc::new(messenger), 100);
+    std::thread::scope(|s| {
+        for _ in 0..4 {
+            s.spawn(|| {
+                for _ in 0..25 {
+                    tracker.add(1);
+                }
+            });
+        }
+    });
+    drop(tracker);
+    consumer.join().unwrap();
     
 
 }
 
 /* Messenger Trait and LimitTracker Example */
-pub trait Messenger {
-    fn send(&self, msg: &str);
+// pub trait Messenger {
+//     fn send(&self, msg: &str);
+// }
+
+// the trait now lives in the library so quota.rs can send through it too
+pub use tutorial26_interior_mutability::Messenger;
+
+/// Prints alerts instead of sending them anywhere
+struct Console;
+
+impl Messenger for Console {
+    fn send(&self, msg: &str) {
+        println!("alert: {}", msg);
+    }
 }
 
 pub struct LimitTracker<'a, T: Messenger> {
@@ -79,6 +126,7 @@ where
 mod tests {
     use super::*;
     use std::cell::RefCell;
+    use tutorial26_interior_mutability::mock::{self, borrow_mut_checked, DoubleBorrow};
 
     struct MockMessenger {
         sent_messages: RefCell<Vec<String>>,
@@ -100,12 +148,23 @@ mod tests {
 
     impl MockMessenger {
         /* Runtime Borrow Checker */
-        fn send2(&self, message: &str) {
-            let mut one_borrow = self.sent_messages.borrow_mut();
-            let mut two_borrow = self.sent_messages.borrow_mut();
+        // fn send2(&self, message: &str) {
+        //     let mut one_borrow = self.sent_messages.borrow_mut();
+        //     let mut two_borrow = self.sent_messages.borrow_mut();
+
+        //     one_borrow.push(String::from(message));
+        //     two_borrow.push(String::from(message));
+        // }
+
+        /* The second borrow_mut above panics and takes the whole test down.
+        Checked, it comes back as an error the test can assert on */
+        fn send2(&self, message: &str) -> Result<(), DoubleBorrow> {
+            let mut one_borrow = borrow_mut_checked(&self.sent_messages, "sent_messages")?;
+            let mut two_borrow = borrow_mut_checked(&self.sent_messages, "sent_messages")?;
 
             one_borrow.push(String::from(message));
             two_borrow.push(String::from(message));
+            Ok(())
         }
     }
 
@@ -116,10 +175,31 @@ mod tests {
 
         limit_tracker.set_value(80);
 
-        mock_messenger.send2("test");
+        let double_borrow = mock_messenger.send2("test");
+        assert_eq!(
+            double_borrow,
+            Err(DoubleBorrow {
+                what: "sent_messages"
+            })
+        );
 
         assert_eq!(mock_messenger.sent_messages.borrow().len(), 1);
     }
+
+    /* Same test with the reusable double from mock.rs */
+    #[test]
+    fn it_sends_an_over_75_percent_warning_message_once() {
+        let mock_messenger = mock::MockMessenger::new();
+        mock_messenger
+            .expect_send()
+            .with(mock::Matcher::contains("75%"))
+            .times(1);
+        let mut limit_tracker = LimitTracker::new(&mock_messenger, 100);
+
+        limit_tracker.set_value(80);
+
+        mock_messenger.assert_satisfied();
+    }
 }
 
 /*Failed Mock Object Example */
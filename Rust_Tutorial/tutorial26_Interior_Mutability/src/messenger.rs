@@ -0,0 +1,54 @@
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+
+/* The Messenger trait from main, shared by everything that sends alerts */
+
+pub trait Messenger {
+    fn send(&self, msg: &str);
+}
+
+/// Lets one messenger be shared between threads as `Arc<M>`
+impl<M: Messenger + ?Sized> Messenger for Arc<M> {
+    fn send(&self, msg: &str) {
+        (**self).send(msg)
+    }
+}
+
+/* Messenger that hands messages to another thread instead of delivering them
+itself, so a slow consumer never holds up whoever is sending */
+
+#[derive(Clone)]
+pub struct ChannelMessenger {
+    sender: Sender<String>,
+}
+
+impl ChannelMessenger {
+    /// Messages show up on the receiver in the order they were sent
+    pub fn channel() -> (ChannelMessenger, Receiver<String>) {
+        let (sender, receiver) = mpsc::channel();
+        (ChannelMessenger { sender }, receiver)
+    }
+
+    /// Runs `consumer` on its own thread for every message. The thread finishes
+    /// once every clone of the messenger is dropped, join it to wait for delivery
+    pub fn spawn<F>(mut consumer: F) -> (ChannelMessenger, JoinHandle<()>)
+    where
+        F: FnMut(String) + Send + 'static,
+    {
+        let (messenger, receiver) = ChannelMessenger::channel();
+        let handle = thread::spawn(move || {
+            for message in receiver {
+                consumer(message);
+            }
+        });
+        (messenger, handle)
+    }
+}
+
+impl Messenger for ChannelMessenger {
+    /// A consumer that has gone away just means nobody is listening
+    fn send(&self, msg: &str) {
+        let _ = self.sender.send(msg.to_string());
+    }
+}
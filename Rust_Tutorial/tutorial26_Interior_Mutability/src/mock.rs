@@ -0,0 +1,515 @@
+use std::cell::{RefCell, RefMut};
+use std::fmt;
+
+use crate::Messenger;
+
+/* Reusable test doubles, generalizing the MockMessenger in main's tests.
+A Recorder remembers every call made to a double (method, arguments, order)
+and checks them against expectations set up before the code under test runs:
+
+    let mock = MockMessenger::new();
+    mock.expect_send().with("Warning: ...").times(1);
+    ... run the code ...
+    mock.assert_satisfied();
+
+Everything lives in RefCells like the original mock, but borrows go through
+`borrow_mut_checked` so a double borrow becomes a reported failure instead of
+the panic `send2` runs into */
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Call {
+    pub method: &'static str,
+    pub args: Vec<String>,
+}
+
+impl fmt::Display for Call {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}({})", self.method, self.args.join(", "))
+    }
+}
+
+/// What `borrow_mut_checked` hands back when the cell is already borrowed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DoubleBorrow {
+    pub what: &'static str,
+}
+
+impl fmt::Display for DoubleBorrow {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} was already borrowed", self.what)
+    }
+}
+
+impl std::error::Error for DoubleBorrow {}
+
+/// `RefCell::borrow_mut` that returns an error instead of panicking
+pub fn borrow_mut_checked<'a, T>(
+    cell: &'a RefCell<T>,
+    what: &'static str,
+) -> Result<RefMut<'a, T>, DoubleBorrow> {
+    cell.try_borrow_mut().map_err(|_| DoubleBorrow { what })
+}
+
+/* Expectations */
+
+pub enum Matcher {
+    Any,
+    Eq(String),
+    Contains(String),
+    Fn(Box<dyn Fn(&str) -> bool>),
+}
+
+impl Matcher {
+    pub fn contains(part: &str) -> Matcher {
+        Matcher::Contains(part.to_string())
+    }
+
+    pub fn matches(&self, arg: &str) -> bool {
+        match self {
+            Matcher::Any => true,
+            Matcher::Eq(expected) => arg == expected,
+            Matcher::Contains(part) => arg.contains(part.as_str()),
+            Matcher::Fn(f) => f(arg),
+        }
+    }
+}
+
+impl From<&str> for Matcher {
+    fn from(expected: &str) -> Matcher {
+        Matcher::Eq(expected.to_string())
+    }
+}
+
+impl fmt::Display for Matcher {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Matcher::Any => write!(f, "_"),
+            Matcher::Eq(expected) => write!(f, "{:?}", expected),
+            Matcher::Contains(part) => write!(f, "contains {:?}", part),
+            Matcher::Fn(_) => write!(f, "<predicate>"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Times {
+    Exactly(usize),
+    AtLeast(usize),
+    AtMost(usize),
+}
+
+impl Times {
+    fn allows(self, count: usize) -> bool {
+        match self {
+            Times::Exactly(n) => count == n,
+            Times::AtLeast(n) => count >= n,
+            Times::AtMost(n) => count <= n,
+        }
+    }
+}
+
+impl fmt::Display for Times {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Times::Exactly(n) => write!(f, "exactly {}", n),
+            Times::AtLeast(n) => write!(f, "at least {}", n),
+            Times::AtMost(n) => write!(f, "at most {}", n),
+        }
+    }
+}
+
+struct Expectation {
+    method: &'static str,
+    args: Vec<Matcher>,
+    times: Times,
+}
+
+impl Expectation {
+    /// Arguments without a matcher match anything
+    fn matches(&self, call: &Call) -> bool {
+        call.method == self.method
+            && self
+                .args
+                .iter()
+                .enumerate()
+                .all(|(i, matcher)| call.args.get(i).is_some_and(|arg| matcher.matches(arg)))
+    }
+}
+
+impl fmt::Display for Expectation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let args: Vec<String> = self.args.iter().map(|m| m.to_string()).collect();
+        write!(
+            f,
+            "{}({}) {} times",
+            self.method,
+            args.join(", "),
+            self.times
+        )
+    }
+}
+
+/// Returned by `Recorder::expect`, refines the expectation it was made for
+pub struct Expect<'a> {
+    recorder: &'a Recorder,
+    index: usize,
+}
+
+impl Expect<'_> {
+    /// Matcher for the next argument, `.with("a").with("b")` for a two argument call
+    pub fn with(self, matcher: impl Into<Matcher>) -> Self {
+        self.update(|e| e.args.push(matcher.into()));
+        self
+    }
+
+    pub fn times(self, n: usize) -> Self {
+        self.update(|e| e.times = Times::Exactly(n));
+        self
+    }
+
+    pub fn at_least(self, n: usize) -> Self {
+        self.update(|e| e.times = Times::AtLeast(n));
+        self
+    }
+
+    pub fn at_most(self, n: usize) -> Self {
+        self.update(|e| e.times = Times::AtMost(n));
+        self
+    }
+
+    pub fn never(self) -> Self {
+        self.times(0)
+    }
+
+    fn update(&self, f: impl FnOnce(&mut Expectation)) {
+        match borrow_mut_checked(&self.recorder.expectations, "expectations") {
+            Ok(mut expectations) => f(&mut expectations[self.index]),
+            // the refinement is lost, so the expectation would check less than it says
+            Err(e) => self.recorder.report_double_borrow(e),
+        }
+    }
+}
+
+/* Failure report */
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Unmet {
+    pub expected: String,
+    pub actual: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Report {
+    pub unmet: Vec<Unmet>,
+    pub double_borrows: Vec<DoubleBorrow>,
+    pub calls: Vec<Call>,
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "test double expectations failed:")?;
+        for unmet in &self.unmet {
+            writeln!(
+                f,
+                "  expected {}, was called {} times",
+                unmet.expected, unmet.actual
+            )?;
+        }
+        for borrow in &self.double_borrows {
+            writeln!(f, "  double borrow: {}", borrow)?;
+        }
+        writeln!(f, "calls received, in order:")?;
+        if self.calls.is_empty() {
+            writeln!(f, "  (none)")?;
+        }
+        for (i, call) in self.calls.iter().enumerate() {
+            writeln!(f, "  {}. {}", i + 1, call)?;
+        }
+        Ok(())
+    }
+}
+
+/* Recorder. Embed one in any hand written double and call `record` from
+each trait method */
+
+#[derive(Default)]
+pub struct Recorder {
+    calls: RefCell<Vec<Call>>,
+    expectations: RefCell<Vec<Expectation>>,
+    double_borrows: RefCell<Vec<DoubleBorrow>>,
+}
+
+impl Recorder {
+    pub fn new() -> Recorder {
+        Recorder::default()
+    }
+
+    pub fn record<I, S>(&self, method: &'static str, args: I)
+    where
+        I: IntoIterator<Item = S>,
+        S: ToString,
+    {
+        let call = Call {
+            method,
+            args: args.into_iter().map(|arg| arg.to_string()).collect(),
+        };
+        match borrow_mut_checked(&self.calls, "call log") {
+            Ok(mut calls) => calls.push(call),
+            Err(e) => self.report_double_borrow(e),
+        }
+    }
+
+    /// Keeps a double borrow found by the double itself for the report
+    pub fn report_double_borrow(&self, borrow: DoubleBorrow) {
+        self.double_borrows.borrow_mut().push(borrow);
+    }
+
+    /// Expects `method` once with any arguments, until told otherwise
+    pub fn expect(&self, method: &'static str) -> Expect<'_> {
+        let mut expectations = self.expectations.borrow_mut();
+        expectations.push(Expectation {
+            method,
+            args: Vec::new(),
+            times: Times::Exactly(1),
+        });
+        Expect {
+            recorder: self,
+            index: expectations.len() - 1,
+        }
+    }
+
+    pub fn calls(&self) -> Vec<Call> {
+        self.calls.borrow().clone()
+    }
+
+    pub fn count(&self, method: &str) -> usize {
+        self.calls
+            .borrow()
+            .iter()
+            .filter(|c| c.method == method)
+            .count()
+    }
+
+    /// Arguments of every call to `method`, in order
+    pub fn args_of(&self, method: &str) -> Vec<Vec<String>> {
+        self.calls
+            .borrow()
+            .iter()
+            .filter(|c| c.method == method)
+            .map(|c| c.args.clone())
+            .collect()
+    }
+
+    /// The methods called, in the order they were called
+    pub fn order(&self) -> Vec<&'static str> {
+        self.calls.borrow().iter().map(|c| c.method).collect()
+    }
+
+    pub fn verify(&self) -> Result<(), Report> {
+        let calls = self.calls.borrow();
+        let unmet: Vec<Unmet> = self
+            .expectations
+            .borrow()
+            .iter()
+            .filter_map(|expectation| {
+                let actual = calls.iter().filter(|c| expectation.matches(c)).count();
+                (!expectation.times.allows(actual)).then(|| Unmet {
+                    expected: expectation.to_string(),
+                    actual,
+                })
+            })
+            .collect();
+        let double_borrows = self.double_borrows.borrow().clone();
+
+        if unmet.is_empty() && double_borrows.is_empty() {
+            Ok(())
+        } else {
+            Err(Report {
+                unmet,
+                double_borrows,
+                calls: calls.clone(),
+            })
+        }
+    }
+
+    /// Panics with the full report when anything was off
+    #[track_caller]
+    pub fn assert_satisfied(&self) {
+        if let Err(report) = self.verify() {
+            panic!("{}", report);
+        }
+    }
+}
+
+/* The MockMessenger from main, built on the Recorder */
+
+#[derive(Default)]
+pub struct MockMessenger {
+    recorder: Recorder,
+}
+
+impl MockMessenger {
+    pub fn new() -> MockMessenger {
+        MockMessenger::default()
+    }
+
+    pub fn expect_send(&self) -> Expect<'_> {
+        self.recorder.expect("send")
+    }
+
+    pub fn sent(&self) -> Vec<String> {
+        self.recorder
+            .args_of("send")
+            .into_iter()
+            .map(|mut args| args.remove(0))
+            .collect()
+    }
+
+    pub fn recorder(&self) -> &Recorder {
+        &self.recorder
+    }
+
+    pub fn verify(&self) -> Result<(), Report> {
+        self.recorder.verify()
+    }
+
+    #[track_caller]
+    pub fn assert_satisfied(&self) {
+        self.recorder.assert_satisfied()
+    }
+}
+
+impl Messenger for MockMessenger {
+    fn send(&self, msg: &str) {
+        self.recorder.record("send", [msg]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::quota::QuotaTracker;
+
+    #[test]
+    fn expectations_pass_when_calls_match() {
+        let mock = MockMessenger::new();
+        mock.expect_send()
+            .with("alice: Warning: You've used up over 75% of your quota!")
+            .times(1);
+        mock.expect_send().with(Matcher::contains("90%")).never();
+
+        let mut tracker = QuotaTracker::new(&mock, 100);
+        tracker.set_value("alice", 80);
+        tracker.set_value("alice", 85);
+
+        mock.assert_satisfied();
+        assert_eq!(mock.sent().len(), 1);
+    }
+
+    #[test]
+    fn report_lists_unmet_expectations_and_every_call() {
+        let mock = MockMessenger::new();
+        mock.expect_send()
+            .with(Matcher::contains("Warning"))
+            .times(2);
+        mock.expect_send()
+            .with(Matcher::Fn(Box::new(|msg| msg.len() > 100)))
+            .at_least(1);
+        mock.send("Warning: one");
+        mock.send("Error: two");
+
+        let report = mock.verify().unwrap_err();
+        assert_eq!(report.unmet.len(), 2);
+        assert_eq!(report.unmet[0].actual, 1);
+        assert_eq!(
+            report.to_string(),
+            "test double expectations failed:\n\
+             \x20 expected send(contains \"Warning\") exactly 2 times, was called 1 times\n\
+             \x20 expected send(<predicate>) at least 1 times, was called 0 times\n\
+             calls received, in order:\n\
+             \x20 1. send(Warning: one)\n\
+             \x20 2. send(Error: two)\n"
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "exactly 1 times, was called 0 times")]
+    fn assert_satisfied_fails_the_test() {
+        let mock = MockMessenger::new();
+        mock.expect_send();
+        mock.assert_satisfied();
+    }
+
+    /* A double for some other trait, to show the Recorder isn't tied to Messenger */
+    trait Store {
+        fn put(&self, key: &str, value: u32);
+        fn flush(&self);
+    }
+
+    struct MockStore {
+        recorder: Recorder,
+    }
+
+    impl Store for MockStore {
+        fn put(&self, key: &str, value: u32) {
+            self.recorder
+                .record("put", [key.to_string(), value.to_string()]);
+        }
+
+        fn flush(&self) {
+            self.recorder.record("flush", Vec::<String>::new());
+        }
+    }
+
+    #[test]
+    fn recorder_works_for_any_trait() {
+        let store = MockStore {
+            recorder: Recorder::new(),
+        };
+        store.recorder.expect("put").with("a").with("1");
+        store.recorder.expect("put").at_least(2);
+        store.recorder.expect("flush");
+
+        store.put("a", 1);
+        store.put("b", 2);
+        store.flush();
+
+        store.recorder.assert_satisfied();
+        assert_eq!(store.recorder.order(), vec!["put", "put", "flush"]);
+        assert_eq!(store.recorder.args_of("put")[1], vec!["b", "2"]);
+        assert_eq!(store.recorder.count("flush"), 1);
+    }
+
+    #[test]
+    fn double_borrows_are_reported_not_panicked() {
+        let cell = RefCell::new(vec![1]);
+        let held = cell.borrow_mut();
+        let err = borrow_mut_checked(&cell, "numbers").unwrap_err();
+        assert_eq!(err.to_string(), "numbers was already borrowed");
+        drop(held);
+        assert!(borrow_mut_checked(&cell, "numbers").is_ok());
+
+        let recorder = Recorder::new();
+        recorder.report_double_borrow(err);
+        let report = recorder.verify().unwrap_err();
+        assert!(report
+            .to_string()
+            .contains("double borrow: numbers was already borrowed"));
+
+        // a refinement that can't be applied fails verify instead of vanishing
+        let recorder = Recorder::new();
+        let expect = recorder.expect("put");
+        let held = recorder.expectations.borrow_mut();
+        expect.times(2);
+        drop(held);
+        recorder.record("put", ["a"]);
+        let report = recorder.verify().unwrap_err();
+        assert!(report.unmet.is_empty());
+        assert_eq!(
+            report.double_borrows,
+            vec![DoubleBorrow {
+                what: "expectations"
+            }]
+        );
+    }
+}
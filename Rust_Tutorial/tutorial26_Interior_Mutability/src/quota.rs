@@ -0,0 +1,374 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::time::Duration;
+
+use crate::Messenger;
+
+/* Quota enforcement grown out of LimitTracker in main.
+LimitTracker sends its warning on every set_value past 75%, so a user who keeps
+working gets the same message over and over. Here each threshold fires once when
+usage crosses it and not again until usage drops back under it or a new billing
+period starts */
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Threshold {
+    /// Fraction of the limit, 0.75 for 75%
+    pub at: f64,
+    pub message: String,
+}
+
+impl Threshold {
+    pub fn new(at: f64, message: &str) -> Threshold {
+        Threshold {
+            at,
+            message: message.to_string(),
+        }
+    }
+}
+
+/// The three levels LimitTracker hard codes
+pub fn default_thresholds() -> Vec<Threshold> {
+    vec![
+        Threshold::new(0.75, "Warning: You've used up over 75% of your quota!"),
+        Threshold::new(
+            0.9,
+            "Urgent warning: You've used up over 90% of your quota!",
+        ),
+        Threshold::new(1.0, "Error: You are over your quota!"),
+    ]
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QuotaExceeded {
+    pub key: String,
+    pub used: usize,
+    pub requested: usize,
+    pub limit: usize,
+}
+
+impl fmt::Display for QuotaExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} asked for {} more but has used {} of {}",
+            self.key, self.requested, self.used, self.limit
+        )
+    }
+}
+
+impl Error for QuotaExceeded {}
+
+#[derive(Debug, Default)]
+struct Usage {
+    used: usize,
+    // how many thresholds, lowest first, have already been announced
+    announced: usize,
+}
+
+pub struct QuotaTracker<'a, T: Messenger> {
+    messenger: &'a T,
+    thresholds: Vec<Threshold>,
+    default_limit: usize,
+    limits: HashMap<String, usize>,
+    usage: HashMap<String, Usage>,
+}
+
+impl<'a, T> QuotaTracker<'a, T>
+where
+    T: Messenger,
+{
+    pub fn new(messenger: &'a T, default_limit: usize) -> QuotaTracker<'a, T> {
+        QuotaTracker::with_thresholds(messenger, default_limit, default_thresholds())
+    }
+
+    pub fn with_thresholds(
+        messenger: &'a T,
+        default_limit: usize,
+        mut thresholds: Vec<Threshold>,
+    ) -> QuotaTracker<'a, T> {
+        thresholds.sort_by(|a, b| a.at.total_cmp(&b.at));
+        QuotaTracker {
+            messenger,
+            thresholds,
+            default_limit,
+            limits: HashMap::new(),
+            usage: HashMap::new(),
+        }
+    }
+
+    /// Gives `key` its own limit instead of the default
+    pub fn set_limit(&mut self, key: &str, limit: usize) {
+        self.limits.insert(key.to_string(), limit);
+        self.announce(key);
+    }
+
+    pub fn limit(&self, key: &str) -> usize {
+        self.limits.get(key).copied().unwrap_or(self.default_limit)
+    }
+
+    pub fn used(&self, key: &str) -> usize {
+        self.usage.get(key).map_or(0, |usage| usage.used)
+    }
+
+    pub fn remaining(&self, key: &str) -> usize {
+        self.limit(key).saturating_sub(self.used(key))
+    }
+
+    /// Records usage like `LimitTracker::set_value`, but only sends a message when
+    /// a threshold is crossed. Jumping past several at once sends the highest one
+    pub fn set_value(&mut self, key: &str, value: usize) {
+        self.usage.entry(key.to_string()).or_default().used = value;
+        self.announce(key);
+    }
+
+    /// Takes `amount` out of the quota, or refuses without changing anything
+    pub fn try_consume(&mut self, key: &str, amount: usize) -> Result<usize, QuotaExceeded> {
+        let used = self.used(key);
+        let limit = self.limit(key);
+        // checked, an amount that overflows is certainly over the limit
+        let total = match used.checked_add(amount) {
+            Some(total) if total <= limit => total,
+            _ => {
+                return Err(QuotaExceeded {
+                    key: key.to_string(),
+                    used,
+                    requested: amount,
+                    limit,
+                })
+            }
+        };
+        self.set_value(key, total);
+        Ok(limit - total)
+    }
+
+    /// Start of a new billing period: every key is back to zero and every
+    /// threshold can fire again
+    pub fn reset_period(&mut self) {
+        self.usage.clear();
+    }
+
+    pub fn reset(&mut self, key: &str) {
+        self.usage.remove(key);
+    }
+
+    fn announce(&mut self, key: &str) {
+        let limit = self.limit(key);
+        let usage = match self.usage.get_mut(key) {
+            Some(usage) => usage,
+            None => return,
+        };
+
+        let reached = reached(&self.thresholds, usage.used, limit);
+        if reached > usage.announced {
+            let threshold = &self.thresholds[reached - 1];
+            self.messenger
+                .send(&format!("{}: {}", key, threshold.message));
+        }
+        // dropping back under a threshold lets it fire again on the next crossing
+        usage.announced = reached;
+    }
+}
+
+/* How many of the sorted `thresholds` `used` out of `limit` is at or past */
+pub(crate) fn reached(thresholds: &[Threshold], used: usize, limit: usize) -> usize {
+    let fraction = if limit == 0 {
+        f64::INFINITY
+    } else {
+        used as f64 / limit as f64
+    };
+    thresholds
+        .iter()
+        .take_while(|threshold| fraction >= threshold.at)
+        .count()
+}
+
+/* Token bucket rate limiter. Holds up to `capacity` tokens and earns `per_second`
+back over time, each request spends some. Time comes in as an argument so tests
+don't have to sleep */
+
+pub struct TokenBucket<'a, T: Messenger> {
+    messenger: &'a T,
+    name: String,
+    capacity: f64,
+    per_second: f64,
+    tokens: f64,
+    last: Duration,
+    limited: bool,
+}
+
+impl<'a, T> TokenBucket<'a, T>
+where
+    T: Messenger,
+{
+    /// Starts full at time `now`
+    pub fn new(
+        messenger: &'a T,
+        name: &str,
+        capacity: u32,
+        per_second: f64,
+        now: Duration,
+    ) -> TokenBucket<'a, T> {
+        TokenBucket {
+            messenger,
+            name: name.to_string(),
+            capacity: capacity as f64,
+            per_second,
+            tokens: capacity as f64,
+            last: now,
+            limited: false,
+        }
+    }
+
+    pub fn available(&mut self, now: Duration) -> u32 {
+        self.refill(now);
+        self.tokens as u32
+    }
+
+    /// Spends `tokens` if there are enough. The first refusal sends one alert,
+    /// the next one is only sent after a request has gone through again
+    pub fn try_acquire(&mut self, tokens: u32, now: Duration) -> bool {
+        self.refill(now);
+        if self.tokens >= tokens as f64 {
+            self.tokens -= tokens as f64;
+            self.limited = false;
+            return true;
+        }
+
+        if !self.limited {
+            self.limited = true;
+            self.messenger
+                .send(&format!("{}: Rate limited, slow down!", self.name));
+        }
+        false
+    }
+
+    fn refill(&mut self, now: Duration) {
+        let elapsed = now.saturating_sub(self.last).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.per_second).min(self.capacity);
+        self.last = self.last.max(now);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    struct Recorder {
+        sent: RefCell<Vec<String>>,
+    }
+
+    impl Recorder {
+        fn new() -> Recorder {
+            Recorder {
+                sent: RefCell::new(vec![]),
+            }
+        }
+
+        fn take(&self) -> Vec<String> {
+            self.sent.borrow_mut().drain(..).collect()
+        }
+    }
+
+    impl Messenger for Recorder {
+        fn send(&self, msg: &str) {
+            self.sent.borrow_mut().push(msg.to_string());
+        }
+    }
+
+    #[test]
+    fn each_threshold_fires_once_per_crossing() {
+        let messenger = Recorder::new();
+        let mut tracker = QuotaTracker::new(&messenger, 100);
+
+        tracker.set_value("alice", 80);
+        tracker.set_value("alice", 85);
+        tracker.set_value("alice", 89);
+        assert_eq!(
+            messenger.take(),
+            vec!["alice: Warning: You've used up over 75% of your quota!"]
+        );
+
+        // straight past 90 and 100 only reports the worst
+        tracker.set_value("alice", 120);
+        assert_eq!(
+            messenger.take(),
+            vec!["alice: Error: You are over your quota!"]
+        );
+        tracker.set_value("alice", 130);
+        assert!(messenger.take().is_empty());
+
+        // falling back under 75% re-arms everything
+        tracker.set_value("alice", 10);
+        tracker.set_value("alice", 76);
+        assert_eq!(messenger.take().len(), 1);
+    }
+
+    #[test]
+    fn keys_are_tracked_separately_with_their_own_limits() {
+        let messenger = Recorder::new();
+        let thresholds = vec![Threshold::new(0.5, "half way")];
+        let mut tracker = QuotaTracker::with_thresholds(&messenger, 10, thresholds);
+        tracker.set_limit("bob", 100);
+
+        tracker.set_value("alice", 5);
+        tracker.set_value("bob", 5);
+        assert_eq!(messenger.take(), vec!["alice: half way"]);
+        assert_eq!(tracker.remaining("bob"), 95);
+
+        // lowering the limit can cross a threshold by itself
+        tracker.set_limit("bob", 8);
+        assert_eq!(messenger.take(), vec!["bob: half way"]);
+    }
+
+    #[test]
+    fn consuming_is_refused_past_the_limit_until_the_period_resets() {
+        let messenger = Recorder::new();
+        let mut tracker = QuotaTracker::new(&messenger, 10);
+
+        assert_eq!(tracker.try_consume("carol", 7), Ok(3));
+        let err = tracker.try_consume("carol", 4).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "carol asked for 4 more but has used 7 of 10"
+        );
+        assert_eq!(tracker.used("carol"), 7);
+        let err = tracker.try_consume("carol", usize::MAX).unwrap_err();
+        assert_eq!(err.requested, usize::MAX);
+        assert_eq!(tracker.used("carol"), 7);
+        assert_eq!(tracker.try_consume("carol", 3), Ok(0));
+        assert_eq!(
+            messenger.take(),
+            vec!["carol: Error: You are over your quota!"]
+        );
+
+        tracker.reset_period();
+        assert_eq!(tracker.used("carol"), 0);
+        assert_eq!(tracker.try_consume("carol", 8), Ok(2));
+        assert_eq!(
+            messenger.take(),
+            vec!["carol: Warning: You've used up over 75% of your quota!"]
+        );
+    }
+
+    #[test]
+    fn token_bucket_refills_and_alerts_once_per_burst() {
+        let messenger = Recorder::new();
+        let secs = Duration::from_secs_f64;
+        let mut bucket = TokenBucket::new(&messenger, "api", 3, 1.0, secs(0.0));
+
+        assert!(bucket.try_acquire(2, secs(0.0)));
+        assert!(bucket.try_acquire(1, secs(0.0)));
+        assert!(!bucket.try_acquire(1, secs(0.1)));
+        assert!(!bucket.try_acquire(1, secs(0.5)));
+        assert_eq!(messenger.take(), vec!["api: Rate limited, slow down!"]);
+
+        assert!(bucket.try_acquire(1, secs(1.0)));
+        assert!(!bucket.try_acquire(1, secs(1.2)));
+        assert_eq!(messenger.take().len(), 1);
+
+        // never more than capacity, however long it sat idle
+        assert_eq!(bucket.available(secs(100.0)), 3);
+    }
+}
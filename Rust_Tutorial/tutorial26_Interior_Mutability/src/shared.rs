@@ -0,0 +1,270 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::quota::{default_thresholds, reached, Threshold};
+use crate::Messenger;
+
+/* Send + Sync versions of the trackers. LimitTracker borrows its messenger and
+needs &mut self, the test mock needs RefCell, none of which can be shared
+between threads. These own an Arc of the messenger and only need &self.
+
+Unlike QuotaTracker, every threshold crossed gets its own message: when two
+threads cross 75% and 90% at the same time, whichever claims a threshold
+first sends it and the other doesn't, but neither message is lost */
+
+/// One quota, lock free
+pub struct AtomicLimitTracker<M: Messenger + Send + Sync> {
+    messenger: Arc<M>,
+    thresholds: Vec<Threshold>,
+    max: usize,
+    value: AtomicUsize,
+    announced: AtomicUsize,
+}
+
+impl<M> AtomicLimitTracker<M>
+where
+    M: Messenger + Send + Sync,
+{
+    pub fn new(messenger: Arc<M>, max: usize) -> AtomicLimitTracker<M> {
+        AtomicLimitTracker::with_thresholds(messenger, max, default_thresholds())
+    }
+
+    pub fn with_thresholds(
+        messenger: Arc<M>,
+        max: usize,
+        mut thresholds: Vec<Threshold>,
+    ) -> AtomicLimitTracker<M> {
+        thresholds.sort_by(|a, b| a.at.total_cmp(&b.at));
+        AtomicLimitTracker {
+            messenger,
+            thresholds,
+            max,
+            value: AtomicUsize::new(0),
+            announced: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn value(&self) -> usize {
+        self.value.load(Ordering::SeqCst)
+    }
+
+    /// Adds to the usage and returns the new total
+    pub fn add(&self, amount: usize) -> usize {
+        let value = self.value.fetch_add(amount, Ordering::SeqCst) + amount;
+        self.announce_up_to(reached(&self.thresholds, value, self.max));
+        value
+    }
+
+    /// Like `LimitTracker::set_value`. Going back down re-arms the thresholds
+    /// above the new value
+    pub fn set_value(&self, value: usize) {
+        self.value.store(value, Ordering::SeqCst);
+        let level = reached(&self.thresholds, value, self.max);
+        if self.announced.fetch_min(level, Ordering::SeqCst) <= level {
+            self.announce_up_to(level);
+        }
+    }
+
+    /// Back to zero for a new billing period
+    pub fn reset(&self) {
+        self.set_value(0);
+    }
+
+    /* Claims thresholds one at a time, a claim that succeeds sends its message */
+    fn announce_up_to(&self, level: usize) {
+        let mut current = self.announced.load(Ordering::SeqCst);
+        while current < level {
+            match self.announced.compare_exchange(
+                current,
+                current + 1,
+                Ordering::SeqCst,
+                Ordering::SeqCst,
+            ) {
+                Ok(_) => {
+                    self.messenger.send(&self.thresholds[current].message);
+                    current += 1;
+                }
+                Err(actual) => current = actual,
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+struct Usage {
+    used: usize,
+    announced: usize,
+}
+
+/// Per-key quotas behind one mutex. Messages go out after the lock is released
+pub struct SharedQuotaTracker<M: Messenger + Send + Sync> {
+    messenger: Arc<M>,
+    thresholds: Vec<Threshold>,
+    limit: usize,
+    usage: Mutex<HashMap<String, Usage>>,
+}
+
+impl<M> SharedQuotaTracker<M>
+where
+    M: Messenger + Send + Sync,
+{
+    pub fn new(messenger: Arc<M>, limit: usize) -> SharedQuotaTracker<M> {
+        let mut thresholds = default_thresholds();
+        thresholds.sort_by(|a, b| a.at.total_cmp(&b.at));
+        SharedQuotaTracker {
+            messenger,
+            thresholds,
+            limit,
+            usage: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn used(&self, key: &str) -> usize {
+        let usage = self.usage.lock().unwrap();
+        usage.get(key).map_or(0, |usage| usage.used)
+    }
+
+    /// Adds to `key`'s usage and returns the new total
+    pub fn add(&self, key: &str, amount: usize) -> usize {
+        let (used, messages) = {
+            let mut all = self.usage.lock().unwrap();
+            let usage = all.entry(key.to_string()).or_default();
+            usage.used += amount;
+
+            let level = reached(&self.thresholds, usage.used, self.limit);
+            let messages: Vec<String> = self.thresholds[usage.announced.min(level)..level]
+                .iter()
+                .map(|threshold| format!("{}: {}", key, threshold.message))
+                .collect();
+            usage.announced = usage.announced.max(level);
+            (usage.used, messages)
+        };
+
+        for message in messages {
+            self.messenger.send(&message);
+        }
+        used
+    }
+
+    /// Start of a new billing period for every key
+    pub fn reset_period(&self) {
+        self.usage.lock().unwrap().clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::messenger::ChannelMessenger;
+    use std::thread;
+
+    /* Collects everything sent, safe to share between threads */
+    #[derive(Default)]
+    struct Collector {
+        sent: Mutex<Vec<String>>,
+    }
+
+    impl Messenger for Collector {
+        fn send(&self, msg: &str) {
+            self.sent.lock().unwrap().push(msg.to_string());
+        }
+    }
+
+    fn count(sent: &[String], needle: &str) -> usize {
+        sent.iter().filter(|msg| msg.contains(needle)).count()
+    }
+
+    #[test]
+    fn atomic_tracker_fires_each_threshold_once_under_contention() {
+        for _ in 0..20 {
+            let collector = Arc::new(Collector::default());
+            let tracker = AtomicLimitTracker::new(Arc::clone(&collector), 1000);
+
+            thread::scope(|s| {
+                for _ in 0..16 {
+                    s.spawn(|| {
+                        for _ in 0..100 {
+                            tracker.add(1);
+                        }
+                    });
+                }
+            });
+
+            assert_eq!(tracker.value(), 1600);
+            let sent = collector.sent.lock().unwrap();
+            assert_eq!(sent.len(), 3, "{:?}", sent);
+            assert_eq!(count(&sent, "75%"), 1);
+            assert_eq!(count(&sent, "90%"), 1);
+            assert_eq!(count(&sent, "over your quota"), 1);
+        }
+    }
+
+    #[test]
+    fn atomic_tracker_rearms_after_a_reset() {
+        let collector = Arc::new(Collector::default());
+        let tracker = AtomicLimitTracker::new(Arc::clone(&collector), 100);
+        tracker.set_value(80);
+        tracker.set_value(85);
+        tracker.reset();
+        tracker.add(95);
+        assert_eq!(collector.sent.lock().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn shared_tracker_keeps_keys_apart_across_threads() {
+        let collector = Arc::new(Collector::default());
+        let tracker = SharedQuotaTracker::new(Arc::clone(&collector), 100);
+
+        thread::scope(|s| {
+            for t in 0..8 {
+                let tracker = &tracker;
+                s.spawn(move || {
+                    let key = if t % 2 == 0 { "even" } else { "odd" };
+                    for _ in 0..25 {
+                        tracker.add(key, 1);
+                    }
+                });
+            }
+        });
+
+        assert_eq!(tracker.used("even"), 100);
+        assert_eq!(tracker.used("odd"), 100);
+        let sent = collector.sent.lock().unwrap();
+        assert_eq!(sent.len(), 6);
+        for key in ["even", "odd"] {
+            assert_eq!(count(&sent, &format!("{}: Warning", key)), 1);
+            assert_eq!(count(&sent, &format!("{}: Urgent", key)), 1);
+            assert_eq!(count(&sent, &format!("{}: Error", key)), 1);
+        }
+    }
+
+    #[test]
+    fn channel_messenger_delivers_on_the_consumer_thread() {
+        let delivered = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&delivered);
+        let (messenger, consumer) = ChannelMessenger::spawn(move |msg| {
+            sink.lock().unwrap().push((thread::current().id(), msg));
+        });
+
+        let tracker = AtomicLimitTracker::new(Arc::new(messenger), 10);
+        thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| {
+                    for _ in 0..5 {
+                        tracker.add(1);
+                    }
+                });
+            }
+        });
+        // the tracker holds the last messenger, dropping it ends the consumer
+        drop(tracker);
+        consumer.join().unwrap();
+
+        let delivered = delivered.lock().unwrap();
+        assert_eq!(delivered.len(), 3);
+        assert!(delivered
+            .iter()
+            .all(|(id, _)| *id != thread::current().id()));
+    }
+}
@@ -0,0 +1,56 @@
+use std::hint::black_box;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use tutorial27_reference_cycles::arena_tree::{NodeId, Tree};
+use tutorial27_reference_cycles::tree::Node;
+
+/* Rc/Weak tree against arena tree, `cargo bench -p tutorial27_Reference_Cycles`.
+Both build the same shape, node i hangs under node i / 4. Prints the best of
+a few runs, which is steadier than the mean */
+
+const N: usize = 100_000;
+
+fn bench<R>(name: &str, mut f: impl FnMut() -> R) {
+    let mut best = Duration::MAX;
+    for _ in 0..20 {
+        let start = Instant::now();
+        black_box(f());
+        best = best.min(start.elapsed());
+    }
+    println!("{:<36} {:>12.2?}", name, best);
+}
+
+fn rc_tree() -> Rc<Node<usize>> {
+    let nodes: Vec<_> = (0..N).map(Node::new).collect();
+    for i in 1..N {
+        nodes[i / 4].add_child(&nodes[i]).unwrap();
+    }
+    Rc::clone(&nodes[0])
+}
+
+fn arena_tree() -> (Tree<usize>, NodeId<usize>) {
+    let mut tree = Tree::with_capacity(N);
+    let ids: Vec<_> = (0..N).map(|n| tree.node(n)).collect();
+    for i in 1..N {
+        tree.add_child(ids[i / 4], ids[i]).unwrap();
+    }
+    (tree, ids[0])
+}
+
+fn main() {
+    bench("rc tree: build and drop", rc_tree);
+    bench("arena tree: build and drop", arena_tree);
+
+    let root = rc_tree();
+    let (tree, arena_root) = arena_tree();
+    bench("rc tree: depth first sum", || {
+        root.depth_first().iter().map(|n| *n.value()).sum::<usize>()
+    });
+    bench("arena tree: depth first sum", || {
+        tree.depth_first(arena_root)
+            .iter()
+            .map(|&id| *tree.value(id))
+            .sum::<usize>()
+    });
+}
@@ -0,0 +1,273 @@
+use std::collections::{HashSet, VecDeque};
+use std::fmt;
+
+use tutorial22_box_pointers::arena::{Arena, Id};
+
+use crate::tree::TreeError;
+
+/* The tree from `tree`, with every node in one Arena. Parent and child links
+are both plain Ids, so there is no Rc/Weak split to get right and no cycle
+to leak: the tree owns all of its nodes and dropping it frees them together.
+Removing a subtree frees its nodes straight away, and their Ids stop
+working.
+
+Methods take Ids and panic on one whose node has been removed, the way
+indexing a Vec panics out of bounds */
+
+pub type NodeId<T> = Id<Node<T>>;
+
+pub struct Node<T> {
+    value: T,
+    parent: Option<NodeId<T>>,
+    children: Vec<NodeId<T>>,
+}
+
+pub struct Tree<T> {
+    nodes: Arena<Node<T>>,
+}
+
+impl<T> Tree<T> {
+    pub fn new() -> Tree<T> {
+        Tree {
+            nodes: Arena::new(),
+        }
+    }
+
+    pub fn with_capacity(capacity: usize) -> Tree<T> {
+        Tree {
+            nodes: Arena::with_capacity(capacity),
+        }
+    }
+
+    /// Number of nodes, in all the trees this one holds
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// A new node with no parent
+    pub fn node(&mut self, value: T) -> NodeId<T> {
+        self.nodes.alloc(Node {
+            value,
+            parent: None,
+            children: vec![],
+        })
+    }
+
+    pub fn contains(&self, id: NodeId<T>) -> bool {
+        self.nodes.contains(id)
+    }
+
+    pub fn value(&self, id: NodeId<T>) -> &T {
+        &self.nodes[id].value
+    }
+
+    pub fn value_mut(&mut self, id: NodeId<T>) -> &mut T {
+        &mut self.nodes[id].value
+    }
+
+    pub fn parent(&self, id: NodeId<T>) -> Option<NodeId<T>> {
+        self.nodes[id].parent
+    }
+
+    pub fn children(&self, id: NodeId<T>) -> &[NodeId<T>] {
+        &self.nodes[id].children
+    }
+
+    pub fn is_root(&self, id: NodeId<T>) -> bool {
+        self.parent(id).is_none()
+    }
+
+    /// Makes `child` the last child of `parent`, moving it with its subtree
+    /// if it already had a parent
+    pub fn add_child(&mut self, parent: NodeId<T>, child: NodeId<T>) -> Result<(), TreeError> {
+        if self.path_to_root(parent).contains(&child) {
+            return Err(TreeError::WouldCycle);
+        }
+
+        self.detach(child);
+        self.nodes[child].parent = Some(parent);
+        self.nodes[parent].children.push(child);
+        Ok(())
+    }
+
+    /// Cuts `id` loose from its parent, it becomes the root of its own tree
+    pub fn detach(&mut self, id: NodeId<T>) {
+        if let Some(parent) = self.nodes[id].parent.take() {
+            self.nodes[parent].children.retain(|&c| c != id);
+        }
+    }
+
+    /// Frees `id` and everything under it, returning how many nodes went
+    pub fn remove_subtree(&mut self, id: NodeId<T>) -> usize {
+        self.detach(id);
+        let doomed = self.depth_first(id);
+        for &node in &doomed {
+            self.nodes.remove(node);
+        }
+        doomed.len()
+    }
+
+    /// Frees every node at once
+    pub fn clear(&mut self) {
+        self.nodes.clear();
+    }
+
+    /// `id`, its parent, and so on up to the root
+    pub fn path_to_root(&self, id: NodeId<T>) -> Vec<NodeId<T>> {
+        let mut path = vec![id];
+        while let Some(parent) = self.parent(path[path.len() - 1]) {
+            path.push(parent);
+        }
+        path
+    }
+
+    pub fn root(&self, id: NodeId<T>) -> NodeId<T> {
+        self.path_to_root(id)
+            .pop()
+            .expect("the path has at least this node")
+    }
+
+    /// 0 for a root
+    pub fn depth(&self, id: NodeId<T>) -> usize {
+        self.path_to_root(id).len() - 1
+    }
+
+    /// Deepest node that has both `a` and `b` under it. `None` when they are
+    /// in different trees
+    pub fn lowest_common_ancestor(&self, a: NodeId<T>, b: NodeId<T>) -> Option<NodeId<T>> {
+        let above_a: HashSet<NodeId<T>> = self.path_to_root(a).into_iter().collect();
+        self.path_to_root(b)
+            .into_iter()
+            .find(|n| above_a.contains(n))
+    }
+
+    /// Pre-order: a node, then each child's subtree left to right
+    pub fn depth_first(&self, id: NodeId<T>) -> Vec<NodeId<T>> {
+        let mut order = Vec::new();
+        let mut stack = vec![id];
+        while let Some(node) = stack.pop() {
+            stack.extend(self.children(node).iter().rev());
+            order.push(node);
+        }
+        order
+    }
+
+    /// Level by level, left to right
+    pub fn breadth_first(&self, id: NodeId<T>) -> Vec<NodeId<T>> {
+        let mut order = Vec::new();
+        let mut queue = VecDeque::from([id]);
+        while let Some(node) = queue.pop_front() {
+            queue.extend(self.children(node));
+            order.push(node);
+        }
+        order
+    }
+}
+
+impl<T: fmt::Display> Tree<T> {
+    /// Draws the subtree under `id` like `tree::Node::pretty`
+    pub fn pretty(&self, id: NodeId<T>) -> String {
+        let mut out = format!("{}\n", self.value(id));
+        self.pretty_children(id, "", &mut out);
+        out
+    }
+
+    fn pretty_children(&self, id: NodeId<T>, prefix: &str, out: &mut String) {
+        let children = self.children(id);
+        for (i, &child) in children.iter().enumerate() {
+            let last = i + 1 == children.len();
+            let (branch, indent) = if last {
+                ("└── ", "    ")
+            } else {
+                ("├── ", "│   ")
+            };
+            out.push_str(&format!("{}{}{}\n", prefix, branch, self.value(child)));
+            self.pretty_children(child, &format!("{}{}", prefix, indent), out);
+        }
+    }
+}
+
+impl<T> Default for Tree<T> {
+    fn default() -> Tree<T> {
+        Tree::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values(tree: &Tree<i32>, ids: &[NodeId<i32>]) -> Vec<i32> {
+        ids.iter().map(|&id| *tree.value(id)).collect()
+    }
+
+    //       1
+    //      / \
+    //     2   3
+    //    / \   \
+    //   4   5   6
+    fn sample() -> (Tree<i32>, Vec<NodeId<i32>>) {
+        let mut tree = Tree::new();
+        let ids: Vec<_> = (0..=6).map(|n| tree.node(n)).collect();
+        for (parent, child) in [(1, 2), (1, 3), (2, 4), (2, 5), (3, 6)] {
+            tree.add_child(ids[parent], ids[child]).unwrap();
+        }
+        (tree, ids)
+    }
+
+    #[test]
+    fn matches_the_rc_tree() {
+        let (mut tree, n) = sample();
+        assert_eq!(
+            values(&tree, &tree.depth_first(n[1])),
+            vec![1, 2, 4, 5, 3, 6]
+        );
+        assert_eq!(
+            values(&tree, &tree.breadth_first(n[1])),
+            vec![1, 2, 3, 4, 5, 6]
+        );
+        assert_eq!(tree.lowest_common_ancestor(n[4], n[6]), Some(n[1]));
+        assert_eq!(tree.lowest_common_ancestor(n[4], n[0]), None);
+        assert_eq!(
+            tree.pretty(n[1]),
+            "1\n\
+             ├── 2\n\
+             │   ├── 4\n\
+             │   └── 5\n\
+             └── 3\n\
+             \x20   └── 6\n"
+        );
+
+        tree.add_child(n[6], n[2]).unwrap();
+        assert_eq!(values(&tree, &tree.path_to_root(n[5])), vec![5, 2, 6, 3, 1]);
+        assert_eq!(tree.add_child(n[5], n[3]), Err(TreeError::WouldCycle));
+        assert_eq!(tree.depth(n[4]), 4);
+        assert_eq!(tree.root(n[4]), n[1]);
+    }
+
+    #[test]
+    fn removed_subtrees_invalidate_their_ids() {
+        let (mut tree, n) = sample();
+        assert_eq!(tree.remove_subtree(n[2]), 3);
+        assert_eq!(tree.len(), 4);
+        assert_eq!(tree.children(n[1]), &[n[3]]);
+        assert!(!tree.contains(n[4]));
+
+        // the freed slots are reused, the old Ids still don't work
+        let fresh = tree.node(7);
+        assert!(!tree.contains(n[2]));
+        assert_eq!(*tree.value(fresh), 7);
+    }
+
+    #[test]
+    #[should_panic(expected = "points at a value that has been removed")]
+    fn using_a_removed_node_panics() {
+        let (mut tree, n) = sample();
+        tree.remove_subtree(n[3]);
+        tree.value(n[6]);
+    }
+}
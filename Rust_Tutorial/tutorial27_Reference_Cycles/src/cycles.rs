@@ -0,0 +1,289 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+
+/* Finding the kind of cycle main builds between a and b.
+Printing `a.tail()` there recurses through a -> b -> a -> ... until the stack
+runs out, and neither list is ever freed. Anything that says which Rcs it
+holds can be checked for cycles here, and printed with `<cycle>` where
+the derived Debug would go round forever */
+
+pub trait Trace: Sized {
+    /// Every Rc this value holds strongly. Weak pointers are left out, they
+    /// can't keep anything alive
+    fn edges(&self) -> Vec<Rc<Self>>;
+
+    /// Like `Debug::fmt`, but children are printed through `tracer.child(..)`
+    /// so a child that leads back round prints as `<cycle>`
+    fn fmt_traced(&self, f: &mut fmt::Formatter, tracer: &Tracer<'_, Self>) -> fmt::Result;
+}
+
+/// Every strong cycle reachable from `root`, each listed in the order the
+/// links run, starting at the node the cycle was entered from. Walks the
+/// graph once, without recursion, so long chains are fine
+pub fn find_cycles<T: Trace>(root: &Rc<T>) -> Vec<Vec<Rc<T>>> {
+    enum State {
+        OnPath,
+        Done,
+    }
+
+    let mut cycles = Vec::new();
+    let mut state: HashMap<*const T, State> = HashMap::new();
+    let mut path = vec![Rc::clone(root)];
+    let mut pending = vec![root.edges().into_iter()];
+    state.insert(Rc::as_ptr(root), State::OnPath);
+
+    while let Some(edges) = pending.last_mut() {
+        match edges.next() {
+            Some(next) => match state.get(&Rc::as_ptr(&next)) {
+                Some(State::OnPath) => {
+                    let start = path
+                        .iter()
+                        .position(|n| Rc::ptr_eq(n, &next))
+                        .expect("nodes marked OnPath are on the path");
+                    cycles.push(path[start..].to_vec());
+                }
+                Some(State::Done) => {}
+                None => {
+                    state.insert(Rc::as_ptr(&next), State::OnPath);
+                    pending.push(next.edges().into_iter());
+                    path.push(next);
+                }
+            },
+            None => {
+                pending.pop();
+                if let Some(done) = path.pop() {
+                    state.insert(Rc::as_ptr(&done), State::Done);
+                }
+            }
+        }
+    }
+    cycles
+}
+
+pub fn has_cycle<T: Trace>(root: &Rc<T>) -> bool {
+    !find_cycles(root).is_empty()
+}
+
+/* Cycle-safe Debug */
+
+/// Handed to `Trace::fmt_traced`, knows which nodes are being printed above this one
+pub struct Tracer<'a, T> {
+    path: &'a RefCell<Vec<*const T>>,
+}
+
+impl<T: Trace> Tracer<'_, T> {
+    /// Debug for a child, `<cycle>` if it is one of the nodes above it
+    pub fn child(&self, node: &Rc<T>) -> impl fmt::Debug + '_ {
+        Traced {
+            node: Rc::clone(node),
+            path: self.path,
+        }
+    }
+}
+
+struct Traced<'a, T> {
+    node: Rc<T>,
+    path: &'a RefCell<Vec<*const T>>,
+}
+
+impl<T: Trace> fmt::Debug for Traced<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let ptr = Rc::as_ptr(&self.node);
+        if self.path.borrow().contains(&ptr) {
+            return write!(f, "<cycle>");
+        }
+
+        self.path.borrow_mut().push(ptr);
+        let result = self.node.fmt_traced(f, &Tracer { path: self.path });
+        self.path.borrow_mut().pop();
+        result
+    }
+}
+
+/// `{:?}` for `root` that stops at cycles instead of overflowing the stack.
+/// A node shared by two branches without a cycle is printed in both places
+pub fn debug<T: Trace>(root: &Rc<T>) -> impl fmt::Debug {
+    SafeDebug {
+        root: Rc::clone(root),
+    }
+}
+
+struct SafeDebug<T> {
+    root: Rc<T>,
+}
+
+impl<T: Trace> fmt::Debug for SafeDebug<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let path = RefCell::new(Vec::new());
+        let traced = Traced {
+            node: Rc::clone(&self.root),
+            path: &path,
+        };
+        fmt::Debug::fmt(&traced, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /* The List from main */
+    #[derive(Debug)]
+    enum List {
+        Cons(i32, RefCell<Rc<List>>),
+        Nil,
+    }
+
+    use List::{Cons, Nil};
+
+    impl Trace for List {
+        fn edges(&self) -> Vec<Rc<List>> {
+            match self {
+                Cons(_, next) => vec![Rc::clone(&next.borrow())],
+                Nil => vec![],
+            }
+        }
+
+        fn fmt_traced(&self, f: &mut fmt::Formatter, tracer: &Tracer<'_, List>) -> fmt::Result {
+            match self {
+                Cons(value, next) => f
+                    .debug_tuple("Cons")
+                    .field(value)
+                    .field(&tracer.child(&next.borrow()))
+                    .finish(),
+                Nil => write!(f, "Nil"),
+            }
+        }
+    }
+
+    fn value(list: &List) -> i32 {
+        match list {
+            Cons(value, _) => *value,
+            Nil => -1,
+        }
+    }
+
+    fn cons(value: i32, next: &Rc<List>) -> Rc<List> {
+        Rc::new(Cons(value, RefCell::new(Rc::clone(next))))
+    }
+
+    /* Points a Cons somewhere else, used to close and then break cycles */
+    fn relink(list: &Rc<List>, to: &Rc<List>) {
+        if let Cons(_, next) = &**list {
+            *next.borrow_mut() = Rc::clone(to);
+        }
+    }
+
+    #[test]
+    fn finds_the_cycle_from_main() {
+        let nil = Rc::new(Nil);
+        let a = cons(5, &nil);
+        let b = cons(10, &a);
+        assert!(!has_cycle(&b));
+
+        relink(&a, &b);
+        let cycles = find_cycles(&a);
+        assert_eq!(cycles.len(), 1);
+        let values: Vec<i32> = cycles[0].iter().map(|n| value(n)).collect();
+        assert_eq!(values, vec![5, 10]);
+        assert_eq!(format!("{:?}", debug(&a)), "Cons(5, Cons(10, <cycle>))");
+
+        // break it again so the test doesn't leak
+        relink(&a, &nil);
+        assert!(!has_cycle(&a));
+        assert_eq!(format!("{:?}", debug(&b)), "Cons(10, Cons(5, Nil))");
+    }
+
+    #[test]
+    fn reports_only_the_nodes_in_the_loop() {
+        // 1 -> 2 -> 3 -> 4 -> back to 2
+        let nil = Rc::new(Nil);
+        let four = cons(4, &nil);
+        let three = cons(3, &four);
+        let two = cons(2, &three);
+        let one = cons(1, &two);
+        relink(&four, &two);
+
+        let cycles = find_cycles(&one);
+        let values: Vec<i32> = cycles[0].iter().map(|n| value(n)).collect();
+        assert_eq!(values, vec![2, 3, 4]);
+        assert_eq!(
+            format!("{:?}", debug(&one)),
+            "Cons(1, Cons(2, Cons(3, Cons(4, <cycle>))))"
+        );
+
+        relink(&four, &nil);
+    }
+
+    /* A node with any number of children, to check sharing without a cycle */
+    struct Graph {
+        name: &'static str,
+        edges: RefCell<Vec<Rc<Graph>>>,
+    }
+
+    impl Trace for Graph {
+        fn edges(&self) -> Vec<Rc<Graph>> {
+            self.edges.borrow().clone()
+        }
+
+        fn fmt_traced(&self, f: &mut fmt::Formatter, tracer: &Tracer<'_, Graph>) -> fmt::Result {
+            let edges = self.edges.borrow();
+            let mut list = f.debug_list();
+            list.entry(&self.name);
+            for edge in edges.iter() {
+                list.entry(&tracer.child(edge));
+            }
+            list.finish()
+        }
+    }
+
+    fn graph(name: &'static str, edges: &[&Rc<Graph>]) -> Rc<Graph> {
+        Rc::new(Graph {
+            name,
+            edges: RefCell::new(edges.iter().map(|e| Rc::clone(e)).collect()),
+        })
+    }
+
+    #[test]
+    fn shared_nodes_are_not_cycles() {
+        let shared = graph("shared", &[]);
+        let left = graph("left", &[&shared]);
+        let right = graph("right", &[&shared]);
+        let top = graph("top", &[&left, &right]);
+        assert!(!has_cycle(&top));
+        assert_eq!(
+            format!("{:?}", debug(&top)),
+            r#"["top", ["left", ["shared"]], ["right", ["shared"]]]"#
+        );
+
+        // a self loop and a second, separate loop
+        shared.edges.borrow_mut().push(Rc::clone(&shared));
+        right.edges.borrow_mut().push(Rc::clone(&top));
+        let names: Vec<Vec<&str>> = find_cycles(&top)
+            .iter()
+            .map(|cycle| cycle.iter().map(|n| n.name).collect())
+            .collect();
+        assert_eq!(names, vec![vec!["shared"], vec!["top", "right"]]);
+
+        shared.edges.borrow_mut().clear();
+        right.edges.borrow_mut().clear();
+    }
+
+    #[test]
+    fn long_chains_do_not_overflow_the_walk() {
+        let nil = Rc::new(Nil);
+        let mut list = Rc::clone(&nil);
+        for n in 0..100_000 {
+            list = cons(n, &list);
+        }
+        assert!(!has_cycle(&list));
+
+        // unlink one node at a time, dropping a long Rc chain recurses
+        while let Cons(_, next) = &*list {
+            let next = Rc::clone(&next.borrow());
+            list = next;
+        }
+    }
+}
@@ -0,0 +1,548 @@
+use std::alloc::{self, Layout};
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::marker::PhantomData;
+use std::ops::Deref;
+use std::ptr::{self, addr_of, addr_of_mut, NonNull};
+
+/* Gc<T>: reference counting like Rc, plus a collector for the cycles Rc leaks.
+Counting frees everything that isn't in a cycle as soon as the last Gc goes,
+same as Rc. `collect()` finds the rest by trial deletion: subtract every count
+that comes from inside the heap, whatever still has a count left is held from
+outside (a local, a field of something not on this heap). Anything those
+can't reach is only kept alive by other garbage, so it is freed.
+
+Single threaded, every thread has its own heap. Gc is neither Send nor Sync.
+
+The gc tests pass under Miri, with both the default Stacked Borrows and
+-Zmiri-tree-borrows, without UB or leaks:
+`cargo +nightly miri test -p tutorial27_Reference_Cycles --lib gc::` */
+
+/// Tells the collector where the Gc pointers inside a value are.
+///
+/// # Safety
+///
+/// `collect()` frees whatever it thinks only the heap itself points at, so
+/// an impl that lies can get a value freed while a Gc to it is still in use.
+/// Implementing it means promising that
+///
+/// - `trace` calls `tracer.edge(..)` at most once for every Gc the value owns,
+///   directly or in fields, and never for a Gc it doesn't own. Leaving one out
+///   is fine, a cycle through it is just never found
+/// - the type's Drop doesn't dereference any Gc it holds, which may point at
+///   garbage freed in the same `collect()`, and doesn't move one anywhere
+///   that outlives the value (no resurrecting)
+pub unsafe trait Trace {
+    fn trace(&self, tracer: &mut Tracer);
+}
+
+pub struct Tracer<'a> {
+    visit: &'a mut dyn FnMut(Erased),
+}
+
+impl Tracer<'_> {
+    pub fn edge<T: Trace + 'static>(&mut self, gc: &Gc<T>) {
+        (self.visit)(gc.erased());
+    }
+}
+
+struct GcBox<T: ?Sized> {
+    strong: Cell<usize>,
+    // set while collect() tears this box down, Gc::drop then only counts down
+    collecting: Cell<bool>,
+    // dropped in place and the box deallocated separately, never as a Box
+    value: T,
+}
+
+type Erased = NonNull<GcBox<dyn Trace>>;
+
+fn address(ptr: Erased) -> usize {
+    ptr.as_ptr() as *const u8 as usize
+}
+
+thread_local! {
+    // every box on this thread's heap, by address
+    static HEAP: RefCell<HashMap<usize, Erased>> = RefCell::new(HashMap::new());
+}
+
+pub struct Gc<T: Trace + 'static> {
+    ptr: NonNull<GcBox<T>>,
+    // not Send or Sync, the counts aren't atomic and the heap is per thread
+    _marker: PhantomData<*const T>,
+}
+
+impl<T: Trace + 'static> Gc<T> {
+    pub fn new(value: T) -> Gc<T> {
+        let boxed = Box::new(GcBox {
+            strong: Cell::new(1),
+            collecting: Cell::new(false),
+            value,
+        });
+        let ptr = NonNull::from(Box::leak(boxed));
+        let gc = Gc {
+            ptr,
+            _marker: PhantomData,
+        };
+        let erased = gc.erased();
+        HEAP.with(|heap| heap.borrow_mut().insert(address(erased), erased));
+        gc
+    }
+
+    pub fn ptr_eq(a: &Gc<T>, b: &Gc<T>) -> bool {
+        a.ptr == b.ptr
+    }
+
+    pub fn strong_count(gc: &Gc<T>) -> usize {
+        gc.strong().get()
+    }
+
+    fn inner(&self) -> &GcBox<T> {
+        // SAFETY: the box stays allocated while any Gc to it exists. collect()
+        // only frees boxes no root can reach, and a live Gc is a root
+        unsafe { self.ptr.as_ref() }
+    }
+
+    /* The counts are reached through field pointers, not `inner()`: a Gc in a
+    value that is being dropped would otherwise borrow the whole box, value
+    included, while the drop still holds a &mut to it */
+
+    fn strong(&self) -> &Cell<usize> {
+        // SAFETY: as in inner, and `strong` is never borrowed mutably
+        unsafe { &*addr_of!((*self.ptr.as_ptr()).strong) }
+    }
+
+    fn collecting(&self) -> &Cell<bool> {
+        // SAFETY: as in strong
+        unsafe { &*addr_of!((*self.ptr.as_ptr()).collecting) }
+    }
+
+    fn erased(&self) -> Erased {
+        let ptr: NonNull<GcBox<dyn Trace>> = self.ptr;
+        ptr
+    }
+}
+
+impl<T: Trace + 'static> Clone for Gc<T> {
+    fn clone(&self) -> Gc<T> {
+        let strong = self.strong();
+        strong.set(strong.get() + 1);
+        Gc {
+            ptr: self.ptr,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T: Trace + 'static> Deref for Gc<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.inner().value
+    }
+}
+
+impl<T: Trace + 'static> Drop for Gc<T> {
+    fn drop(&mut self) {
+        let strong = self.strong().get() - 1;
+        self.strong().set(strong);
+        if strong > 0 || self.collecting().get() {
+            return;
+        }
+
+        let erased = self.erased();
+        HEAP.with(|heap| heap.borrow_mut().remove(&address(erased)));
+        // SAFETY: that was the last Gc and collect() isn't freeing this box,
+        // so nothing else will touch it again. The heap borrow is released
+        // first because dropping the value can drop more Gcs. The box was
+        // allocated by Box::new, so it is freed with the same layout
+        unsafe {
+            let ptr = self.ptr.as_ptr();
+            ptr::drop_in_place(addr_of_mut!((*ptr).value));
+            alloc::dealloc(ptr.cast(), Layout::new::<GcBox<T>>());
+        }
+    }
+}
+
+impl<T: Trace + fmt::Debug + 'static> fmt::Debug for Gc<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+/// Trace for a Gc held inside another Gc's value
+unsafe impl<T: Trace + 'static> Trace for Gc<T> {
+    fn trace(&self, tracer: &mut Tracer) {
+        tracer.edge(self);
+    }
+}
+
+/// Number of boxes alive on this thread's heap
+pub fn heap_size() -> usize {
+    HEAP.with(|heap| heap.borrow().len())
+}
+
+/// Frees every box on this thread's heap that is only kept alive by cycles.
+/// Returns how many were freed.
+///
+/// Panics, before freeing anything, if a `Trace` impl reports more edges to
+/// a value than it has Gcs pointing at it
+pub fn collect() -> usize {
+    let boxes: Vec<Erased> = HEAP.with(|heap| heap.borrow().values().copied().collect());
+
+    let trace = |ptr: Erased, visit: &mut dyn FnMut(Erased)| {
+        // SAFETY: every box in HEAP is allocated and its value not yet dropped
+        let value: &dyn Trace = unsafe { &ptr.as_ref().value };
+        value.trace(&mut Tracer { visit });
+    };
+
+    // trial deletion: what's left after removing references from inside the heap
+    let mut outside: HashMap<usize, usize> = boxes
+        .iter()
+        // SAFETY: as above
+        .map(|&b| (address(b), unsafe { b.as_ref() }.strong.get()))
+        .collect();
+    for &b in &boxes {
+        trace(b, &mut |child| {
+            if let Some(count) = outside.get_mut(&address(child)) {
+                *count = count
+                    .checked_sub(1)
+                    .expect("a Trace impl reported a Gc it doesn't own, or one twice");
+            }
+        });
+    }
+
+    // everything reachable from a box that something outside still holds is live
+    let mut live = HashSet::new();
+    let mut stack: Vec<Erased> = boxes
+        .iter()
+        .copied()
+        .filter(|&b| outside[&address(b)] > 0)
+        .collect();
+    while let Some(b) = stack.pop() {
+        if live.insert(address(b)) {
+            trace(b, &mut |child| stack.push(child));
+        }
+    }
+
+    let garbage: Vec<Erased> = boxes
+        .into_iter()
+        .filter(|&b| !live.contains(&address(b)))
+        .collect();
+    HEAP.with(|heap| {
+        let mut heap = heap.borrow_mut();
+        for &b in &garbage {
+            heap.remove(&address(b));
+        }
+    });
+
+    // SAFETY: nothing outside the garbage can reach it, so the only Gcs to these
+    // boxes live in each other's values. With `collecting` set, dropping those
+    // Gcs only counts down, so every value is dropped exactly once below and
+    // no box is freed before all the values are gone. A value is dropped in
+    // place through a raw pointer, its Gcs may point back at its own box
+    unsafe {
+        let mut layouts = Vec::with_capacity(garbage.len());
+        for &b in &garbage {
+            b.as_ref().collecting.set(true);
+            layouts.push(Layout::for_value(b.as_ref()));
+        }
+        for &b in &garbage {
+            ptr::drop_in_place(addr_of_mut!((*b.as_ptr()).value));
+        }
+        for (&b, layout) in garbage.iter().zip(layouts) {
+            alloc::dealloc(b.as_ptr().cast(), layout);
+        }
+    }
+    garbage.len()
+}
+
+/* Trace for types that hold no Gc, or hold them in the usual containers.
+Each reports exactly the Gcs it owns, once */
+
+macro_rules! trace_nothing {
+    ($($t:ty),*) => {
+        $(
+            unsafe impl Trace for $t {
+                fn trace(&self, _: &mut Tracer) {}
+            }
+        )*
+    };
+}
+
+trace_nothing!((), bool, char, String, &'static str);
+trace_nothing!(u8, u16, u32, u64, usize, i8, i16, i32, i64, isize, f32, f64);
+
+unsafe impl<T: Trace> Trace for Option<T> {
+    fn trace(&self, tracer: &mut Tracer) {
+        if let Some(value) = self {
+            value.trace(tracer);
+        }
+    }
+}
+
+unsafe impl<T: Trace> Trace for Vec<T> {
+    fn trace(&self, tracer: &mut Tracer) {
+        for value in self {
+            value.trace(tracer);
+        }
+    }
+}
+
+unsafe impl<T: Trace + ?Sized> Trace for Box<T> {
+    fn trace(&self, tracer: &mut Tracer) {
+        (**self).trace(tracer);
+    }
+}
+
+/// A value that is mutably borrowed right now is skipped, which keeps it alive
+unsafe impl<T: Trace> Trace for RefCell<T> {
+    fn trace(&self, tracer: &mut Tracer) {
+        if let Ok(value) = self.try_borrow() {
+            value.trace(tracer);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    thread_local! {
+        static DROPPED: RefCell<Vec<i32>> = const { RefCell::new(Vec::new()) };
+    }
+
+    fn dropped() -> Vec<i32> {
+        let mut all = DROPPED.with(|d| d.borrow_mut().drain(..).collect::<Vec<_>>());
+        all.sort();
+        all
+    }
+
+    /* The List from main, on Gc, with a Drop that records its value */
+    enum List {
+        Cons(i32, RefCell<Gc<List>>),
+        Nil,
+    }
+
+    use List::{Cons, Nil};
+
+    impl List {
+        fn tail(&self) -> Option<&RefCell<Gc<List>>> {
+            match self {
+                Cons(_, item) => Some(item),
+                Nil => None,
+            }
+        }
+    }
+
+    unsafe impl Trace for List {
+        fn trace(&self, tracer: &mut Tracer) {
+            if let Cons(_, item) = self {
+                item.trace(tracer);
+            }
+        }
+    }
+
+    impl Drop for List {
+        fn drop(&mut self) {
+            let value = match self {
+                Cons(value, _) => *value,
+                Nil => 0,
+            };
+            DROPPED.with(|d| d.borrow_mut().push(value));
+        }
+    }
+
+    // tests share nothing but run on separate threads, so each has its own heap
+
+    #[test]
+    fn acyclic_values_are_freed_by_counting_alone() {
+        let nil = Gc::new(Nil);
+        let a = Gc::new(Cons(5, RefCell::new(nil.clone())));
+        let b = a.clone();
+        assert_eq!(Gc::strong_count(&a), 2);
+        assert_eq!(heap_size(), 2);
+
+        drop(nil);
+        drop(a);
+        assert!(dropped().is_empty());
+        drop(b);
+        assert_eq!(dropped(), vec![0, 5]);
+        assert_eq!(heap_size(), 0);
+    }
+
+    #[test]
+    fn the_cycle_from_main_is_reclaimed() {
+        let a = Gc::new(Cons(5, RefCell::new(Gc::new(Nil))));
+        let b = Gc::new(Cons(10, RefCell::new(a.clone())));
+        if let Some(link) = a.tail() {
+            *link.borrow_mut() = b.clone();
+        }
+        // Nil lost its only reference and went straight away
+        assert_eq!(dropped(), vec![0]);
+
+        // still reachable from the locals, nothing to collect
+        assert_eq!(collect(), 0);
+        drop(a);
+        assert_eq!(collect(), 0);
+
+        drop(b);
+        assert_eq!(heap_size(), 2);
+        assert!(dropped().is_empty());
+        assert_eq!(collect(), 2);
+        assert_eq!(dropped(), vec![5, 10]);
+        assert_eq!(heap_size(), 0);
+    }
+
+    #[test]
+    fn garbage_pointing_at_live_values_leaves_them_alone() {
+        let keep = Gc::new(Cons(1, RefCell::new(Gc::new(Nil))));
+        let x = Gc::new(Cons(2, RefCell::new(keep.clone())));
+        let y = Gc::new(Cons(3, RefCell::new(x.clone())));
+        // x -> y -> x, and x used to point at keep
+        *x.tail().unwrap().borrow_mut() = y.clone();
+        dropped();
+        let z = Gc::new(Cons(4, RefCell::new(keep.clone())));
+        *y.tail().unwrap().borrow_mut() = z.clone();
+        *z.tail().unwrap().borrow_mut() = x.clone();
+        drop((x, y, z));
+
+        assert_eq!(collect(), 3);
+        assert_eq!(dropped(), vec![2, 3, 4]);
+        assert_eq!(Gc::strong_count(&keep), 1);
+        assert!(matches!(*keep, Cons(1, _)));
+        assert_eq!(heap_size(), 2);
+    }
+
+    /* A graph node with any number of edges, for self loops and bigger cycles */
+    struct Graph {
+        id: i32,
+        edges: RefCell<Vec<Gc<Graph>>>,
+    }
+
+    unsafe impl Trace for Graph {
+        fn trace(&self, tracer: &mut Tracer) {
+            self.edges.trace(tracer);
+        }
+    }
+
+    impl Drop for Graph {
+        fn drop(&mut self) {
+            DROPPED.with(|d| d.borrow_mut().push(self.id));
+        }
+    }
+
+    #[test]
+    fn every_node_of_a_tangled_graph_is_dropped_once() {
+        let nodes: Vec<Gc<Graph>> = (0..50)
+            .map(|id| {
+                Gc::new(Graph {
+                    id,
+                    edges: RefCell::new(vec![]),
+                })
+            })
+            .collect();
+        for (i, node) in nodes.iter().enumerate() {
+            let mut edges = node.edges.borrow_mut();
+            edges.push(node.clone());
+            edges.push(nodes[(i * 7 + 3) % 50].clone());
+            edges.push(nodes[(i + 1) % 50].clone());
+        }
+
+        let survivor = nodes[0].clone();
+        drop(nodes);
+        // every node is reachable from survivor through the i + 1 edges
+        assert_eq!(collect(), 0);
+
+        drop(survivor);
+        assert_eq!(collect(), 50);
+        assert_eq!(dropped(), (0..50).collect::<Vec<_>>());
+        assert_eq!(heap_size(), 0);
+    }
+
+    /* A value with no Gcs in it, held both from a local and from garbage */
+    struct Leaf(i32);
+
+    unsafe impl Trace for Leaf {
+        fn trace(&self, _: &mut Tracer) {}
+    }
+
+    impl Drop for Leaf {
+        fn drop(&mut self) {
+            DROPPED.with(|d| d.borrow_mut().push(self.0));
+        }
+    }
+
+    struct Holder {
+        id: i32,
+        leaf: Gc<Leaf>,
+        next: RefCell<Option<Gc<Holder>>>,
+        // how often `trace` reports `leaf`, anything but 1 breaks the contract
+        leaf_edges: usize,
+    }
+
+    unsafe impl Trace for Holder {
+        fn trace(&self, tracer: &mut Tracer) {
+            for _ in 0..self.leaf_edges {
+                tracer.edge(&self.leaf);
+            }
+            self.next.trace(tracer);
+        }
+    }
+
+    impl Drop for Holder {
+        fn drop(&mut self) {
+            DROPPED.with(|d| d.borrow_mut().push(self.id));
+        }
+    }
+
+    /// Two holders pointing at each other, returns the first
+    fn holder_cycle(leaf: &Gc<Leaf>, leaf_edges: usize) -> Gc<Holder> {
+        let a = Gc::new(Holder {
+            id: 1,
+            leaf: leaf.clone(),
+            next: RefCell::new(None),
+            leaf_edges,
+        });
+        let b = Gc::new(Holder {
+            id: 2,
+            leaf: leaf.clone(),
+            next: RefCell::new(Some(a.clone())),
+            leaf_edges,
+        });
+        *a.next.borrow_mut() = Some(b);
+        a
+    }
+
+    #[test]
+    fn values_still_held_from_outside_survive_their_garbage_owners() {
+        let leaf = Gc::new(Leaf(7));
+        holder_cycle(&leaf, 1);
+        assert_eq!(Gc::strong_count(&leaf), 3);
+
+        assert_eq!(collect(), 2);
+        assert_eq!(dropped(), vec![1, 2]);
+        assert_eq!(Gc::strong_count(&leaf), 1);
+        assert_eq!(leaf.0, 7);
+        drop(leaf);
+        assert_eq!(dropped(), vec![7]);
+    }
+
+    #[test]
+    fn over_reported_edges_panic_before_anything_is_freed() {
+        let leaf = Gc::new(Leaf(7));
+        let a = holder_cycle(&leaf, 2);
+        // the locals plus one per holder, but four edges to the leaf get reported
+        let result = std::panic::catch_unwind(collect);
+        assert!(result.is_err());
+        assert!(dropped().is_empty());
+        assert_eq!(heap_size(), 3);
+        assert_eq!(leaf.0, 7);
+
+        // breaking the cycle by hand still frees everything by counting
+        a.next.borrow_mut().take();
+        drop((a, leaf));
+        assert_eq!(dropped(), vec![1, 2, 7]);
+        assert_eq!(heap_size(), 0);
+    }
+}
@@ -0,0 +1,4 @@
+pub mod arena_tree;
+pub mod cycles;
+pub mod gc;
+pub mod tree;
@@ -19,6 +19,30 @@ impl List {
     }
 }
 
+/*Lets the cycle detector walk List */
+use std::fmt;
+use tutorial27_reference_cycles::cycles::{self, Trace, Tracer};
+
+impl Trace for List {
+    fn edges(&self) -> Vec<Rc<List>> {
+        match self {
+            Cons(_, item) => vec![Rc::clone(&item.borrow())],
+            Nil => vec![],
+        }
+    }
+
+    fn fmt_traced(&self, f: &mut fmt::Formatter, tracer: &Tracer<'_, List>) -> fmt::Result {
+        match self {
+            Cons(value, item) => f
+                .debug_tuple("Cons")
+                .field(value)
+                .field(&tracer.child(&item.borrow()))
+                .finish(),
+            Nil => write!(f, "Nil"),
+        }
+    }
+}
+
 /*Node Struct Weak Pointer */
 use std::rc::{Weak};
 #[derive(Debug)]
@@ -53,6 +77,43 @@ fn main() {
     // Uncomment the next line to see that we have a cycle;
     // it will overflow the stack
     // println!("a next item = {:?}", a.tail());
+
+    // the cycle detector can print it safely, and say which nodes loop
+    println!("a = {:?}", cycles::debug(&a));
+    println!("cycles found from a = {}", cycles::find_cycles(&a).len());
+
+    /*The same cycle on Gc, which the collector can free */
+    {
+        use tutorial27_reference_cycles::gc::{self, Gc};
+
+        enum GcList {
+            Cons(i32, RefCell<Gc<GcList>>),
+            Nil,
+        }
+
+        // reports its one Gc once, and has no Drop of its own
+        unsafe impl gc::Trace for GcList {
+            fn trace(&self, tracer: &mut gc::Tracer) {
+                if let GcList::Cons(_, item) = self {
+                    item.trace(tracer);
+                }
+            }
+        }
+
+        let a = Gc::new(GcList::Cons(5, RefCell::new(Gc::new(GcList::Nil))));
+        let b = Gc::new(GcList::Cons(10, RefCell::new(a.clone())));
+        if let GcList::Cons(_, item) = &*a {
+            *item.borrow_mut() = b.clone();
+        }
+        if let GcList::Cons(value, item) = &*b {
+            if let GcList::Cons(next, _) = &**item.borrow() {
+                println!("gc list b = {} -> {} -> ...", value, next);
+            }
+        }
+        drop((a, b));
+        println!("gc heap before collect = {}", gc::heap_size());
+        println!("freed by collect = {}", gc::collect());
+    }
     
     /*Tree Data Structure */
     
@@ -116,5 +177,24 @@ fn main() {
         Rc::strong_count(&leaf),
         Rc::weak_count(&leaf),
     );
-    
+
+    /*Same tree through the tree module, links kept in sync for us */
+    use tutorial27_reference_cycles::tree;
+    let branch = tree::Node::new(5);
+    let leaf = tree::Node::new(3);
+    branch.add_child(&leaf).unwrap();
+    branch.add_child(&tree::Node::new(4)).unwrap();
+    println!("leaf parent = {:?}", leaf.parent().map(|p| *p.value()));
+    print!("{}", branch.pretty());
+
+    /*And once more in an arena, links are Ids instead of Rc and Weak */
+    use tutorial27_reference_cycles::arena_tree::Tree;
+    let mut tree = Tree::new();
+    let branch = tree.node(5);
+    let leaf = tree.node(3);
+    tree.add_child(branch, leaf).unwrap();
+    let other = tree.node(4);
+    tree.add_child(branch, other).unwrap();
+    println!("leaf parent = {:?}", tree.parent(leaf).map(|p| *tree.value(p)));
+    print!("{}", tree.pretty(branch));
 }
@@ -0,0 +1,283 @@
+use std::cell::RefCell;
+use std::collections::{HashSet, VecDeque};
+use std::error::Error;
+use std::fmt;
+use std::rc::{Rc, Weak};
+
+/* The Node from main with the hand wiring moved into methods.
+Children are owned through Rc, parents are only pointed at through Weak,
+so a whole tree goes away as soon as nothing outside holds its root.
+Every method that changes a link changes the matching link on the
+other side too, a child's parent and the parent's children never disagree */
+
+#[derive(Debug)]
+pub struct Node<T> {
+    value: T,
+    parent: RefCell<Weak<Node<T>>>,
+    children: RefCell<Vec<Rc<Node<T>>>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TreeError {
+    /// The new parent is the node itself or one of its descendants
+    WouldCycle,
+}
+
+impl fmt::Display for TreeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TreeError::WouldCycle => write!(f, "a node can't be moved under itself"),
+        }
+    }
+}
+
+impl Error for TreeError {}
+
+impl<T> Node<T> {
+    pub fn new(value: T) -> Rc<Node<T>> {
+        Rc::new(Node {
+            value,
+            parent: RefCell::new(Weak::new()),
+            children: RefCell::new(vec![]),
+        })
+    }
+
+    pub fn value(&self) -> &T {
+        &self.value
+    }
+
+    pub fn parent(&self) -> Option<Rc<Node<T>>> {
+        self.parent.borrow().upgrade()
+    }
+
+    pub fn children(&self) -> Vec<Rc<Node<T>>> {
+        self.children.borrow().clone()
+    }
+
+    pub fn is_root(&self) -> bool {
+        self.parent().is_none()
+    }
+
+    /// Makes `child` the last child of this node. A child that already has a
+    /// parent is moved, taking its whole subtree with it
+    pub fn add_child(self: &Rc<Self>, child: &Rc<Node<T>>) -> Result<(), TreeError> {
+        if self.path_to_root().iter().any(|n| Rc::ptr_eq(n, child)) {
+            return Err(TreeError::WouldCycle);
+        }
+
+        child.detach();
+        *child.parent.borrow_mut() = Rc::downgrade(self);
+        self.children.borrow_mut().push(Rc::clone(child));
+        Ok(())
+    }
+
+    /// Takes `child` out of this node's children. Returns false if it wasn't one.
+    /// The subtree is freed unless something else still holds it
+    pub fn remove_child(&self, child: &Rc<Node<T>>) -> bool {
+        let mut children = self.children.borrow_mut();
+        match children.iter().position(|c| Rc::ptr_eq(c, child)) {
+            Some(index) => {
+                let removed = children.remove(index);
+                *removed.parent.borrow_mut() = Weak::new();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Cuts this node loose from its parent, it becomes the root of its own tree
+    pub fn detach(self: &Rc<Self>) {
+        if let Some(parent) = self.parent() {
+            parent.remove_child(self);
+        }
+    }
+
+    /// Moves this node and everything under it to the end of `new_parent`'s children
+    pub fn move_to(self: &Rc<Self>, new_parent: &Rc<Node<T>>) -> Result<(), TreeError> {
+        new_parent.add_child(self)
+    }
+
+    /// This node, its parent, and so on up to the root
+    pub fn path_to_root(self: &Rc<Self>) -> Vec<Rc<Node<T>>> {
+        let mut path = vec![Rc::clone(self)];
+        while let Some(parent) = path[path.len() - 1].parent() {
+            path.push(parent);
+        }
+        path
+    }
+
+    pub fn root(self: &Rc<Self>) -> Rc<Node<T>> {
+        self.path_to_root()
+            .pop()
+            .expect("the path has at least this node")
+    }
+
+    /// 0 for a root
+    pub fn depth(self: &Rc<Self>) -> usize {
+        self.path_to_root().len() - 1
+    }
+
+    /// Deepest node that has both `a` and `b` under it (a node counts as under
+    /// itself). `None` when they are in different trees
+    pub fn lowest_common_ancestor(a: &Rc<Node<T>>, b: &Rc<Node<T>>) -> Option<Rc<Node<T>>> {
+        let above_a: HashSet<*const Node<T>> = a.path_to_root().iter().map(Rc::as_ptr).collect();
+        b.path_to_root()
+            .into_iter()
+            .find(|n| above_a.contains(&Rc::as_ptr(n)))
+    }
+
+    /// Pre-order: a node, then each child's subtree left to right
+    pub fn depth_first(self: &Rc<Self>) -> Vec<Rc<Node<T>>> {
+        let mut order = Vec::new();
+        let mut stack = vec![Rc::clone(self)];
+        while let Some(node) = stack.pop() {
+            stack.extend(node.children.borrow().iter().rev().cloned());
+            order.push(node);
+        }
+        order
+    }
+
+    /// Level by level, left to right
+    pub fn breadth_first(self: &Rc<Self>) -> Vec<Rc<Node<T>>> {
+        let mut order = Vec::new();
+        let mut queue = VecDeque::from([Rc::clone(self)]);
+        while let Some(node) = queue.pop_front() {
+            queue.extend(node.children.borrow().iter().cloned());
+            order.push(node);
+        }
+        order
+    }
+}
+
+impl<T: fmt::Display> Node<T> {
+    /// Draws the subtree the way `tree` prints directories
+    ///
+    /// ```text
+    /// 5
+    /// ├── 3
+    /// │   └── 1
+    /// └── 4
+    /// ```
+    pub fn pretty(&self) -> String {
+        let mut out = format!("{}\n", self.value);
+        self.pretty_children("", &mut out);
+        out
+    }
+
+    fn pretty_children(&self, prefix: &str, out: &mut String) {
+        let children = self.children.borrow();
+        for (i, child) in children.iter().enumerate() {
+            let last = i + 1 == children.len();
+            let (branch, indent) = if last {
+                ("└── ", "    ")
+            } else {
+                ("├── ", "│   ")
+            };
+            out.push_str(&format!("{}{}{}\n", prefix, branch, child.value));
+            child.pretty_children(&format!("{}{}", prefix, indent), out);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values(nodes: &[Rc<Node<i32>>]) -> Vec<i32> {
+        nodes.iter().map(|n| *n.value()).collect()
+    }
+
+    //       1
+    //      / \
+    //     2   3
+    //    / \   \
+    //   4   5   6
+    fn sample() -> Vec<Rc<Node<i32>>> {
+        let nodes: Vec<_> = (0..=6).map(Node::new).collect();
+        for (parent, child) in [(1, 2), (1, 3), (2, 4), (2, 5), (3, 6)] {
+            nodes[parent].add_child(&nodes[child]).unwrap();
+        }
+        nodes
+    }
+
+    #[test]
+    fn links_stay_in_sync_both_ways() {
+        let n = sample();
+        assert!(Rc::ptr_eq(&n[4].parent().unwrap(), &n[2]));
+        assert_eq!(values(&n[2].children()), vec![4, 5]);
+        assert!(n[1].is_root());
+
+        assert!(n[2].remove_child(&n[4]));
+        assert!(!n[2].remove_child(&n[4]));
+        assert!(n[4].parent().is_none());
+        assert_eq!(values(&n[2].children()), vec![5]);
+    }
+
+    #[test]
+    fn moving_a_subtree_takes_its_children_along() {
+        let n = sample();
+        n[2].move_to(&n[6]).unwrap();
+        assert_eq!(values(&n[1].children()), vec![3]);
+        assert_eq!(values(&n[5].path_to_root()), vec![5, 2, 6, 3, 1]);
+        assert_eq!(n[4].depth(), 4);
+
+        assert_eq!(n[3].move_to(&n[5]), Err(TreeError::WouldCycle));
+        assert_eq!(n[1].add_child(&n[1]), Err(TreeError::WouldCycle));
+        assert!(Rc::ptr_eq(&n[3].parent().unwrap(), &n[1]));
+    }
+
+    #[test]
+    fn traversals() {
+        let n = sample();
+        assert_eq!(values(&n[1].depth_first()), vec![1, 2, 4, 5, 3, 6]);
+        assert_eq!(values(&n[1].breadth_first()), vec![1, 2, 3, 4, 5, 6]);
+        assert_eq!(values(&n[3].depth_first()), vec![3, 6]);
+        assert!(Rc::ptr_eq(&n[5].root(), &n[1]));
+    }
+
+    #[test]
+    fn lowest_common_ancestor() {
+        let n = sample();
+        let lca =
+            |a: usize, b: usize| Node::lowest_common_ancestor(&n[a], &n[b]).map(|n| *n.value());
+        assert_eq!(lca(4, 5), Some(2));
+        assert_eq!(lca(4, 6), Some(1));
+        assert_eq!(lca(2, 4), Some(2));
+        assert_eq!(lca(6, 6), Some(6));
+        assert_eq!(lca(4, 0), None);
+    }
+
+    #[test]
+    fn pretty_printer() {
+        let n = sample();
+        assert_eq!(
+            n[1].pretty(),
+            "1\n\
+             ├── 2\n\
+             │   ├── 4\n\
+             │   └── 5\n\
+             └── 3\n\
+             \x20   └── 6\n"
+        );
+    }
+
+    #[test]
+    fn removed_subtrees_are_freed() {
+        let root = Node::new(1);
+        let weak: Vec<Weak<Node<i32>>> = {
+            let branch = Node::new(2);
+            let leaf = Node::new(3);
+            root.add_child(&branch).unwrap();
+            branch.add_child(&leaf).unwrap();
+            vec![Rc::downgrade(&branch), Rc::downgrade(&leaf)]
+        };
+        assert!(weak.iter().all(|w| w.strong_count() == 1));
+
+        let branch = weak[0].upgrade().unwrap();
+        assert!(root.remove_child(&branch));
+        drop(branch);
+        assert!(weak.iter().all(|w| w.strong_count() == 0));
+        assert!(root.children().is_empty());
+        assert_eq!(Rc::weak_count(&root), 0);
+    }
+}